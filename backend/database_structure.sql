@@ -1,3 +1,15 @@
+-- Búsqueda de texto completo: español sin tildes
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+CREATE TEXT SEARCH CONFIGURATION spanish_unaccent (COPY = spanish);
+ALTER TEXT SEARCH CONFIGURATION spanish_unaccent
+    ALTER MAPPING FOR hword, hword_part, word WITH unaccent, spanish_stem;
+
+-- Versión IMMUTABLE de unaccent() para las columnas generadas
+CREATE FUNCTION search_unaccent(text) RETURNS text
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+    AS $$ SELECT public.unaccent('public.unaccent'::regdictionary, $1) $$;
+
 -- Tabla 1: users
 CREATE TABLE users (
     id SERIAL PRIMARY KEY,
@@ -10,9 +22,19 @@ CREATE TABLE users (
     last_login TIMESTAMPTZ,
     created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
     updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
-    is_active BOOLEAN DEFAULT TRUE
+    is_active BOOLEAN DEFAULT TRUE,
+    email_verified_at TIMESTAMPTZ,
+    totp_secret TEXT,
+    totp_enabled_at TIMESTAMPTZ,
+    totp_last_step BIGINT,
+    deletion_scheduled_for TIMESTAMPTZ,
+    deletion_mode VARCHAR(20) CHECK (deletion_mode IN ('delete', 'anonymize')),
+    -- false en las cuentas creadas con un proveedor OIDC, cuya contraseña nadie conoce
+    has_password BOOLEAN NOT NULL DEFAULT TRUE
 );
 
+CREATE INDEX users_deletion_idx ON users (deletion_scheduled_for) WHERE deletion_scheduled_for IS NOT NULL;
+
 -- Tabla 2: posts
 CREATE TABLE posts (
     id SERIAL PRIMARY KEY,
@@ -23,13 +45,23 @@ CREATE TABLE posts (
     created_at TIMESTAMPTZ DEFAULT CURRENT_TIMESTAMP,
     updated_at TIMESTAMPTZ,
     likes_count INTEGER DEFAULT 0,
-    comments_count INTEGER DEFAULT 0
+    comments_count INTEGER DEFAULT 0,
+    search_vector tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(COALESCE(title, ''))), 'A') ||
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(COALESCE(content, ''))), 'B')
+    ) STORED
 );
 
+CREATE INDEX posts_created_idx ON posts (created_at, id);
+CREATE INDEX posts_search_idx ON posts USING GIN (search_vector);
+
 -- Tabla 3: tags
 CREATE TABLE tags (
     id SERIAL PRIMARY KEY,
-    name VARCHAR(50) UNIQUE NOT NULL
+    name VARCHAR(50) UNIQUE NOT NULL,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
 );
 
 -- Tabla 4: post_tags (M:M)
@@ -39,6 +71,8 @@ CREATE TABLE post_tags (
     PRIMARY KEY (post_id, tag_id)
 );
 
+CREATE INDEX post_tags_tag_idx ON post_tags (tag_id);
+
 -- Tabla 5: comments
 CREATE TABLE comments (
     id SERIAL PRIMARY KEY,
@@ -46,9 +80,15 @@ CREATE TABLE comments (
     user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
     content TEXT NOT NULL,
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-    likes_count INTEGER DEFAULT 0
+    likes_count INTEGER DEFAULT 0,
+    search_vector tsvector GENERATED ALWAYS AS (
+        to_tsvector('spanish_unaccent', search_unaccent(content))
+    ) STORED
 );
 
+CREATE INDEX comments_post_created_idx ON comments (post_id, created_at, id);
+CREATE INDEX comments_search_idx ON comments USING GIN (search_vector);
+
 -- Tabla 6: post_likes (M:M)
 CREATE TABLE post_likes (
     user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
@@ -83,16 +123,23 @@ CREATE TABLE groups (
     creator_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
     image_url TEXT,
     color VARCHAR(20),
-    members_count INTEGER DEFAULT 0
+    members_count INTEGER DEFAULT 0,
+    visibility VARCHAR(20) NOT NULL DEFAULT 'public' CHECK (visibility IN ('public', 'private', 'invite_only')),
+    search_vector tsvector GENERATED ALWAYS AS (
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(name)), 'A') ||
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(COALESCE(description, ''))), 'B')
+    ) STORED
 );
 
+CREATE INDEX groups_search_idx ON groups USING GIN (search_vector);
+
 -- Tabla 10: group_members (M:M)
 CREATE TABLE group_members (
     group_id INTEGER REFERENCES groups(id) ON DELETE CASCADE,
     user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
-    role VARCHAR(20) DEFAULT 'member',
+    role VARCHAR(20) DEFAULT 'member' CHECK (role IN ('owner', 'moderator', 'member', 'banned')),
     join_date TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
-    status VARCHAR(10) DEFAULT 'offline',
+    status VARCHAR(10) DEFAULT 'offline' CHECK (status IN ('online', 'away', 'offline')),
     PRIMARY KEY (group_id, user_id)
 );
 
@@ -104,9 +151,12 @@ CREATE TABLE group_posts (
     content TEXT NOT NULL,
     created_at TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     likes_count INTEGER DEFAULT 0,
-    comments_count INTEGER DEFAULT 0
+    comments_count INTEGER DEFAULT 0,
+    is_pinned BOOLEAN NOT NULL DEFAULT FALSE
 );
 
+CREATE INDEX group_posts_group_created_idx ON group_posts (group_id, is_pinned, created_at, id);
+
 -- Tabla 12: messages
 CREATE TABLE messages (
     id SERIAL PRIMARY KEY,
@@ -132,7 +182,6 @@ CREATE TABLE mood_records (
     anxiety_level_id INTEGER REFERENCES anxiety_levels(id) ON DELETE SET NULL,
     record_date TIMESTAMP DEFAULT CURRENT_TIMESTAMP,
     notes TEXT,
-    triggers TEXT,
     mood_score INTEGER CHECK (mood_score BETWEEN 1 AND 10)
 );
 
@@ -152,3 +201,190 @@ CREATE TABLE user_stats (
     completed_tasks INTEGER DEFAULT 0,
     level INTEGER DEFAULT 1
 );
+
+-- Tabla 17: triggers (user_id NULL = desencadenante del sistema)
+CREATE TABLE triggers (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX triggers_owner_name_idx ON triggers (COALESCE(user_id, 0), LOWER(name));
+
+INSERT INTO triggers (name) VALUES
+    ('Trabajo'), ('Estudios'), ('Familia'), ('Pareja'), ('Salud'),
+    ('Dinero'), ('Sueño'), ('Redes sociales'), ('Soledad');
+
+-- Tabla 18: mood_record_triggers (M:M)
+CREATE TABLE mood_record_triggers (
+    mood_record_id INTEGER REFERENCES mood_records(id) ON DELETE CASCADE,
+    trigger_id INTEGER REFERENCES triggers(id) ON DELETE CASCADE,
+    PRIMARY KEY (mood_record_id, trigger_id)
+);
+
+-- Tabla 19: group_join_requests (solicitudes para grupos privados)
+CREATE TABLE group_join_requests (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    message TEXT,
+    status VARCHAR(20) NOT NULL DEFAULT 'pending' CHECK (status IN ('pending', 'approved', 'rejected')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    reviewed_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    reviewed_at TIMESTAMPTZ
+);
+
+-- Solo puede haber una solicitud pendiente por usuario y grupo
+CREATE UNIQUE INDEX group_join_requests_pending_idx ON group_join_requests (group_id, user_id)
+    WHERE status = 'pending';
+
+-- Tabla 20: group_invites (enlaces de invitación)
+CREATE TABLE group_invites (
+    id SERIAL PRIMARY KEY,
+    group_id INTEGER NOT NULL REFERENCES groups(id) ON DELETE CASCADE,
+    code VARCHAR(64) NOT NULL UNIQUE,
+    created_by INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ,
+    max_uses INTEGER CHECK (max_uses > 0),
+    uses INTEGER NOT NULL DEFAULT 0,
+    revoked BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Tabla 21: refresh_tokens (solo se guarda el hash SHA-256 del token)
+CREATE TABLE refresh_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    family_id UUID NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ,
+    revoked_at TIMESTAMPTZ,
+    replaced_by INTEGER REFERENCES refresh_tokens(id) ON DELETE SET NULL
+);
+
+CREATE INDEX refresh_tokens_family_idx ON refresh_tokens (family_id);
+CREATE INDEX refresh_tokens_user_idx ON refresh_tokens (user_id);
+
+-- Tabla 22: revoked_tokens (access tokens revocados antes de caducar)
+CREATE TABLE revoked_tokens (
+    jti UUID PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    expires_at TIMESTAMPTZ NOT NULL,
+    revoked_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Tabla 23: password_reset_tokens (un solo uso; se guarda el hash SHA-256)
+CREATE TABLE password_reset_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX password_reset_tokens_user_idx ON password_reset_tokens (user_id);
+
+-- Tabla 24: email_verification_tokens (confirmación de una dirección de email)
+CREATE TABLE email_verification_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(100) NOT NULL,
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX email_verification_tokens_user_idx ON email_verification_tokens (user_id);
+
+-- Tabla 25: audit_log (eventos de seguridad de las cuentas)
+CREATE TABLE audit_log (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE SET NULL,
+    event VARCHAR(50) NOT NULL,
+    ip_address VARCHAR(45),
+    details JSONB NOT NULL DEFAULT '{}',
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX audit_log_user_idx ON audit_log (user_id, created_at);
+CREATE INDEX audit_log_event_idx ON audit_log (event, created_at);
+
+-- Tabla 26: totp_recovery_codes (códigos de recuperación de la verificación en dos pasos)
+CREATE TABLE totp_recovery_codes (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    code_hash CHAR(64) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    used_at TIMESTAMPTZ,
+    UNIQUE (user_id, code_hash)
+);
+
+-- Tabla 27: oidc_auth_requests (inicios de sesión con OpenID Connect en curso)
+CREATE TABLE oidc_auth_requests (
+    id SERIAL PRIMARY KEY,
+    state_hash CHAR(64) NOT NULL UNIQUE,
+    provider VARCHAR(50) NOT NULL,
+    code_verifier TEXT NOT NULL,
+    nonce TEXT NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+-- Tabla 28: user_identities (cuentas de proveedores externos vinculadas a cada usuario)
+CREATE TABLE user_identities (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    provider VARCHAR(50) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    email VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    last_login TIMESTAMPTZ,
+    UNIQUE (provider, subject)
+);
+
+CREATE INDEX user_identities_user_idx ON user_identities (user_id);
+
+-- Tabla 29: categories (categorías de los posts, gestionadas por los administradores)
+CREATE TABLE categories (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX categories_name_lower_idx ON categories (LOWER(name));
+
+INSERT INTO categories (name, description) VALUES
+    ('Ansiedad', 'Categoría para temas relacionados con la ansiedad'),
+    ('Depresión', 'Categoría para temas relacionados con la depresión'),
+    ('Estrés', 'Categoría para temas relacionados con el estrés'),
+    ('Superación', NULL), ('Técnicas', NULL), ('Consejos', NULL),
+    ('Testimonios', NULL), ('Logros', NULL), ('Motivación', NULL);
+
+-- Al renombrar una categoría se actualizan sus posts; no se puede borrar si tiene alguno
+ALTER TABLE posts
+    ADD CONSTRAINT posts_category_fkey FOREIGN KEY (category)
+    REFERENCES categories (name) ON UPDATE CASCADE ON DELETE RESTRICT;
+
+CREATE INDEX posts_category_idx ON posts (category);
+
+-- Tabla 30: account_confirmation_tokens (confirmación por email de la eliminación o la
+-- restauración de una cuenta; un solo uso, se guarda el hash SHA-256)
+CREATE TABLE account_confirmation_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    action VARCHAR(10) NOT NULL CHECK (action IN ('delete', 'restore')),
+    deletion_mode VARCHAR(20) CHECK (deletion_mode IN ('delete', 'anonymize')),
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX account_confirmation_tokens_user_idx ON account_confirmation_tokens (user_id);
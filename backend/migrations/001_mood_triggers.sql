@@ -0,0 +1,60 @@
+-- Sustituye el texto libre de mood_records.triggers por la taxonomía
+-- triggers / mood_record_triggers. Solo es necesaria en bases de datos
+-- creadas antes de que database_structure.sql incluyera estas tablas.
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS triggers (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER REFERENCES users(id) ON DELETE CASCADE,
+    name VARCHAR(100) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS triggers_owner_name_idx ON triggers (COALESCE(user_id, 0), LOWER(name));
+
+INSERT INTO triggers (name) VALUES
+    ('Trabajo'), ('Estudios'), ('Familia'), ('Pareja'), ('Salud'),
+    ('Dinero'), ('Sueño'), ('Redes sociales'), ('Soledad')
+ON CONFLICT DO NOTHING;
+
+CREATE TABLE IF NOT EXISTS mood_record_triggers (
+    mood_record_id INTEGER REFERENCES mood_records(id) ON DELETE CASCADE,
+    trigger_id INTEGER REFERENCES triggers(id) ON DELETE CASCADE,
+    PRIMARY KEY (mood_record_id, trigger_id)
+);
+
+-- Cada frase separada por comas, punto y coma o saltos de línea pasa a ser un desencadenante
+CREATE TEMP TABLE legacy_triggers ON COMMIT DROP AS
+SELECT m.id AS mood_record_id, m.user_id, phrase.name
+FROM mood_records m,
+LATERAL (
+    SELECT LEFT(BTRIM(REGEXP_REPLACE(p, '\s+', ' ', 'g')), 100) AS name
+    FROM REGEXP_SPLIT_TO_TABLE(m.triggers, '[,;\n]') p
+) phrase
+WHERE m.triggers IS NOT NULL
+  AND m.user_id IS NOT NULL
+  AND phrase.name <> '';
+
+-- Los nombres que coinciden con un desencadenante del sistema se enlazan a él
+INSERT INTO triggers (user_id, name)
+SELECT DISTINCT ON (l.user_id, LOWER(l.name)) l.user_id, l.name
+FROM legacy_triggers l
+WHERE NOT EXISTS (
+    SELECT 1 FROM triggers s WHERE s.user_id IS NULL AND LOWER(s.name) = LOWER(l.name)
+)
+ON CONFLICT DO NOTHING;
+
+INSERT INTO mood_record_triggers (mood_record_id, trigger_id)
+SELECT DISTINCT l.mood_record_id, t.id
+FROM legacy_triggers l
+JOIN LATERAL (
+    SELECT id FROM triggers t
+    WHERE (t.user_id = l.user_id OR t.user_id IS NULL) AND LOWER(t.name) = LOWER(l.name)
+    ORDER BY t.user_id NULLS LAST
+    LIMIT 1
+) t ON true
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE mood_records DROP COLUMN IF EXISTS triggers;
+
+COMMIT;
@@ -0,0 +1,19 @@
+-- Roles de grupo (owner, moderator, member, banned) y publicaciones fijadas
+BEGIN;
+
+UPDATE group_members SET role = 'member'
+WHERE role IS NULL OR role NOT IN ('owner', 'moderator', 'member', 'banned');
+
+ALTER TABLE group_members DROP CONSTRAINT IF EXISTS group_members_role_check;
+ALTER TABLE group_members
+    ADD CONSTRAINT group_members_role_check CHECK (role IN ('owner', 'moderator', 'member', 'banned'));
+
+-- Los creadores que sigan en su grupo pasan a ser propietarios
+UPDATE group_members gm SET role = 'owner'
+FROM groups g
+WHERE gm.group_id = g.id AND gm.user_id = g.creator_id
+  AND NOT EXISTS (SELECT 1 FROM group_members o WHERE o.group_id = g.id AND o.role = 'owner');
+
+ALTER TABLE group_posts ADD COLUMN IF NOT EXISTS is_pinned BOOLEAN NOT NULL DEFAULT FALSE;
+
+COMMIT;
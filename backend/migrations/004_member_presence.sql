@@ -0,0 +1,11 @@
+-- Estados de presencia de group_members.status
+BEGIN;
+
+UPDATE group_members SET status = 'offline'
+WHERE status IS NULL OR status NOT IN ('online', 'away', 'offline');
+
+ALTER TABLE group_members DROP CONSTRAINT IF EXISTS group_members_status_check;
+ALTER TABLE group_members
+    ADD CONSTRAINT group_members_status_check CHECK (status IN ('online', 'away', 'offline'));
+
+COMMIT;
@@ -0,0 +1,9 @@
+-- Verificación del email de las cuentas nuevas
+BEGIN;
+
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_verified_at TIMESTAMPTZ;
+
+-- Las cuentas existentes se dan por verificadas para no bloquearlas
+UPDATE users SET email_verified_at = created_at WHERE email_verified_at IS NULL;
+
+COMMIT;
@@ -0,0 +1,14 @@
+-- Eliminación de cuentas con periodo de gracia
+BEGIN;
+
+-- Mientras dura el periodo de gracia la cuenta está desactivada (is_active = false);
+-- al llegar deletion_scheduled_for se borra. deletion_mode indica qué hacer con
+-- los posts y comentarios: borrarlos o conservarlos sin autor.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deletion_scheduled_for TIMESTAMPTZ;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS deletion_mode VARCHAR(20)
+    CHECK (deletion_mode IN ('delete', 'anonymize'));
+
+CREATE INDEX IF NOT EXISTS users_deletion_idx ON users (deletion_scheduled_for)
+    WHERE deletion_scheduled_for IS NOT NULL;
+
+COMMIT;
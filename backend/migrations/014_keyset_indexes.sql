@@ -0,0 +1,8 @@
+-- Índices para paginar con cursor por (created_at, id)
+BEGIN;
+
+CREATE INDEX IF NOT EXISTS posts_created_idx ON posts (created_at, id);
+CREATE INDEX IF NOT EXISTS comments_post_created_idx ON comments (post_id, created_at, id);
+CREATE INDEX IF NOT EXISTS group_posts_group_created_idx ON group_posts (group_id, is_pinned, created_at, id);
+
+COMMIT;
@@ -0,0 +1,44 @@
+-- Búsqueda de texto completo en español en posts, comentarios y grupos
+BEGIN;
+
+CREATE EXTENSION IF NOT EXISTS unaccent;
+
+-- Español sin tildes, para que "estres" encuentre "estrés"
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_ts_config WHERE cfgname = 'spanish_unaccent') THEN
+        CREATE TEXT SEARCH CONFIGURATION spanish_unaccent (COPY = spanish);
+        ALTER TEXT SEARCH CONFIGURATION spanish_unaccent
+            ALTER MAPPING FOR hword, hword_part, word WITH unaccent, spanish_stem;
+    END IF;
+END
+$$;
+
+-- unaccent() no es IMMUTABLE y no se puede usar en columnas generadas. Quitar las tildes
+-- antes de separar las palabras evita además que las bases de datos sin locale UTF-8
+-- corten las palabras por las letras acentuadas
+CREATE OR REPLACE FUNCTION search_unaccent(text) RETURNS text
+    LANGUAGE sql IMMUTABLE PARALLEL SAFE STRICT
+    AS $$ SELECT public.unaccent('public.unaccent'::regdictionary, $1) $$;
+
+ALTER TABLE posts ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(COALESCE(title, ''))), 'A') ||
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(COALESCE(content, ''))), 'B')
+    ) STORED;
+CREATE INDEX IF NOT EXISTS posts_search_idx ON posts USING GIN (search_vector);
+
+ALTER TABLE comments ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        to_tsvector('spanish_unaccent', search_unaccent(content))
+    ) STORED;
+CREATE INDEX IF NOT EXISTS comments_search_idx ON comments USING GIN (search_vector);
+
+ALTER TABLE groups ADD COLUMN IF NOT EXISTS search_vector tsvector
+    GENERATED ALWAYS AS (
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(name)), 'A') ||
+        setweight(to_tsvector('spanish_unaccent', search_unaccent(COALESCE(description, ''))), 'B')
+    ) STORED;
+CREATE INDEX IF NOT EXISTS groups_search_idx ON groups USING GIN (search_vector);
+
+COMMIT;
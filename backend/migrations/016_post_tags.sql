@@ -0,0 +1,11 @@
+-- Etiquetas de los posts: columnas que espera models::categories::Tag
+BEGIN;
+
+ALTER TABLE tags ADD COLUMN IF NOT EXISTS description TEXT;
+ALTER TABLE tags ADD COLUMN IF NOT EXISTS created_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE tags ADD COLUMN IF NOT EXISTS updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+
+-- Para filtrar el feed por etiqueta; la clave primaria ya cubre las búsquedas por post
+CREATE INDEX IF NOT EXISTS post_tags_tag_idx ON post_tags (tag_id);
+
+COMMIT;
@@ -0,0 +1,60 @@
+-- Categorías de los posts en base de datos, gestionadas por los administradores
+BEGIN;
+
+CREATE TABLE IF NOT EXISTS categories (
+    id SERIAL PRIMARY KEY,
+    name VARCHAR(50) NOT NULL UNIQUE,
+    description TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Los nombres se comparan sin distinguir mayúsculas
+CREATE UNIQUE INDEX IF NOT EXISTS categories_name_lower_idx ON categories (LOWER(name));
+
+-- Las que devolvía la API y las que ofrecía el formulario del frontend
+INSERT INTO categories (name, description) VALUES
+    ('Ansiedad', 'Categoría para temas relacionados con la ansiedad'),
+    ('Depresión', 'Categoría para temas relacionados con la depresión'),
+    ('Estrés', 'Categoría para temas relacionados con el estrés'),
+    ('Superación', NULL),
+    ('Técnicas', NULL),
+    ('Consejos', NULL),
+    ('Testimonios', NULL),
+    ('Logros', NULL),
+    ('Motivación', NULL)
+ON CONFLICT DO NOTHING;
+
+-- El formulario guardaba el identificador sin tildes en lugar del nombre
+UPDATE posts p
+SET category = c.name
+FROM (VALUES
+    ('superacion', 'Superación'),
+    ('tecnicas', 'Técnicas'),
+    ('motivacion', 'Motivación')
+) AS c (slug, name)
+WHERE p.category = c.slug;
+
+-- Cualquier otra categoría que ya tengan los posts se conserva como categoría nueva
+INSERT INTO categories (name)
+SELECT DISTINCT ON (LOWER(TRIM(category))) TRIM(category)
+FROM posts
+WHERE category IS NOT NULL AND TRIM(category) <> ''
+ORDER BY LOWER(TRIM(category)), TRIM(category)
+ON CONFLICT DO NOTHING;
+
+UPDATE posts SET category = NULL WHERE TRIM(category) = '';
+
+UPDATE posts p
+SET category = c.name
+FROM categories c
+WHERE LOWER(TRIM(p.category)) = LOWER(c.name) AND p.category <> c.name;
+
+-- Al renombrar una categoría se actualizan sus posts; no se puede borrar si tiene alguno
+ALTER TABLE posts
+    ADD CONSTRAINT posts_category_fkey FOREIGN KEY (category)
+    REFERENCES categories (name) ON UPDATE CASCADE ON DELETE RESTRICT;
+
+CREATE INDEX IF NOT EXISTS posts_category_idx ON posts (category);
+
+COMMIT;
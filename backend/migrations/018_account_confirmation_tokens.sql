@@ -0,0 +1,25 @@
+-- Confirmación por email de la eliminación y la restauración de cuentas sin contraseña propia
+BEGIN;
+
+-- Las cuentas creadas al entrar con un proveedor OIDC tienen una contraseña aleatoria que nadie conoce
+ALTER TABLE users ADD COLUMN IF NOT EXISTS has_password BOOLEAN NOT NULL DEFAULT TRUE;
+
+-- La cuenta y su identidad se crean en la misma transacción, así que comparten created_at
+UPDATE users u SET has_password = false
+FROM user_identities i
+WHERE i.user_id = u.id AND i.created_at = u.created_at;
+
+CREATE TABLE IF NOT EXISTS account_confirmation_tokens (
+    id SERIAL PRIMARY KEY,
+    user_id INTEGER NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    action VARCHAR(10) NOT NULL CHECK (action IN ('delete', 'restore')),
+    deletion_mode VARCHAR(20) CHECK (deletion_mode IN ('delete', 'anonymize')),
+    token_hash CHAR(64) NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    expires_at TIMESTAMPTZ NOT NULL,
+    used_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS account_confirmation_tokens_user_idx ON account_confirmation_tokens (user_id);
+
+COMMIT;
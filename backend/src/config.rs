@@ -9,6 +9,29 @@ pub struct Config {
     pub jwt_secret: String,
     pub jwt_expires_in: String,
     pub jwt_maxage: i32,
+    pub jwt_keys_dir: String,
+    pub jwt_key_id: Option<String>,
+    pub jwt_audience: String,
+    pub refresh_token_expires_in: String,
+    pub password_reset_expires_in: String,
+    pub email_verification_expires_in: String,
+    pub email_verification_resend_interval: String,
+    pub require_verified_email: bool,
+    pub admin_emails: Vec<String>,
+    pub login_max_failures: u32,
+    pub login_ip_max_failures: u32,
+    pub login_failure_window: String,
+    pub login_lockout_duration: String,
+    pub login_backoff_base: String,
+    pub login_backoff_max: String,
+    pub totp_issuer: String,
+    pub account_deletion_grace_period: String,
+    pub account_confirmation_expires_in: String,
+    pub oidc_providers: Vec<OidcProviderConfig>,
+    pub frontend_url: String,
+    pub mailer: String,
+    pub mail_from: String,
+    pub mail_dir: String,
 }
 
 impl Config {
@@ -26,6 +49,79 @@ impl Config {
                 .unwrap_or("3600".to_string())
                 .parse()
                 .unwrap_or(3600),
+            jwt_keys_dir: env::var("JWT_KEYS_DIR").unwrap_or("keys".to_string()),
+            jwt_key_id: env::var("JWT_KEY_ID").ok().filter(|kid| !kid.is_empty()),
+            jwt_audience: env::var("JWT_AUDIENCE").unwrap_or("api".to_string()),
+            refresh_token_expires_in: env::var("REFRESH_TOKEN_EXPIRES_IN").unwrap_or("30d".to_string()),
+            password_reset_expires_in: env::var("PASSWORD_RESET_EXPIRES_IN").unwrap_or("1h".to_string()),
+            email_verification_expires_in: env::var("EMAIL_VERIFICATION_EXPIRES_IN").unwrap_or("24h".to_string()),
+            email_verification_resend_interval: env::var("EMAIL_VERIFICATION_RESEND_INTERVAL").unwrap_or("1m".to_string()),
+            require_verified_email: env::var("REQUIRE_VERIFIED_EMAIL")
+                .unwrap_or("false".to_string())
+                .parse()
+                .unwrap_or(false),
+            admin_emails: env::var("ADMIN_EMAILS")
+                .unwrap_or_default()
+                .split(',')
+                .map(|email| email.trim().to_lowercase())
+                .filter(|email| !email.is_empty())
+                .collect(),
+            login_max_failures: env::var("LOGIN_MAX_FAILURES")
+                .unwrap_or("5".to_string())
+                .parse()
+                .unwrap_or(5),
+            login_ip_max_failures: env::var("LOGIN_IP_MAX_FAILURES")
+                .unwrap_or("20".to_string())
+                .parse()
+                .unwrap_or(20),
+            login_failure_window: env::var("LOGIN_FAILURE_WINDOW").unwrap_or("15m".to_string()),
+            login_lockout_duration: env::var("LOGIN_LOCKOUT_DURATION").unwrap_or("15m".to_string()),
+            login_backoff_base: env::var("LOGIN_BACKOFF_BASE").unwrap_or("1s".to_string()),
+            login_backoff_max: env::var("LOGIN_BACKOFF_MAX").unwrap_or("1m".to_string()),
+            totp_issuer: env::var("TOTP_ISSUER").unwrap_or("Mi Aplicación".to_string()),
+            account_deletion_grace_period: env::var("ACCOUNT_DELETION_GRACE_PERIOD").unwrap_or("30d".to_string()),
+            account_confirmation_expires_in: env::var("ACCOUNT_CONFIRMATION_EXPIRES_IN").unwrap_or("1h".to_string()),
+            oidc_providers: OidcProviderConfig::from_env()?,
+            frontend_url: env::var("FRONTEND_URL").unwrap_or("http://localhost:3000".to_string()),
+            mailer: env::var("MAILER").unwrap_or("log".to_string()),
+            mail_from: env::var("MAIL_FROM").unwrap_or("no-reply@localhost".to_string()),
+            mail_dir: env::var("MAIL_DIR").unwrap_or("mail".to_string()),
         })
     }
+}
+
+/// Proveedor OpenID Connect con el que se puede iniciar sesión.
+#[derive(Debug, Clone, Deserialize)]
+pub struct OidcProviderConfig {
+    /// Nombre usado en las rutas, p. ej. `google` en `/api/auth/oidc/google/authorize`.
+    pub name: String,
+    pub display_name: String,
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    pub scopes: String,
+}
+
+impl OidcProviderConfig {
+    // OIDC_PROVIDERS=google,gitlab y, por cada uno, OIDC_GOOGLE_ISSUER, OIDC_GOOGLE_CLIENT_ID...
+    fn from_env() -> Result<Vec<Self>, env::VarError> {
+        let names = env::var("OIDC_PROVIDERS").unwrap_or_default();
+
+        names
+            .split(',')
+            .map(str::trim)
+            .filter(|name| !name.is_empty())
+            .map(|name| {
+                let var = |key: &str| format!("OIDC_{}_{}", name.to_uppercase(), key);
+                Ok(Self {
+                    name: name.to_lowercase(),
+                    display_name: env::var(var("DISPLAY_NAME")).unwrap_or(name.to_string()),
+                    issuer: env::var(var("ISSUER"))?.trim_end_matches('/').to_string(),
+                    client_id: env::var(var("CLIENT_ID"))?,
+                    client_secret: env::var(var("CLIENT_SECRET"))?,
+                    scopes: env::var(var("SCOPES")).unwrap_or("openid email profile".to_string()),
+                })
+            })
+            .collect()
+    }
 }
\ No newline at end of file
@@ -0,0 +1,884 @@
+use std::io::{Cursor, Write};
+
+use actix_web::{http::header, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Duration, Utc};
+use log::{error, info};
+use serde_json::Value;
+use validator::Validate;
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{
+    db::DbPool,
+    handlers::{auth::too_many_attempts, two_factor},
+    models::auth::{
+        AccountConfirmation, AccountDeletionMode, DeleteAccount, RestoreAccount, RestoreAccountRequest,
+        TokenClaims, User,
+    },
+    services::{
+        login_guard::LoginGuard,
+        mailer::{Email, Mailer},
+    },
+    utils::{audit, error::HttpError, jwt::parse_duration, refresh_token, revocation, secure_token},
+    AppState,
+};
+
+// Pide el segundo factor si la cuenta lo tiene activo
+async fn check_second_factor(pool: &DbPool, user_id: i32, code: Option<&str>) -> Result<(), HttpError> {
+    if !two_factor::is_enabled(pool, user_id).await? {
+        return Ok(());
+    }
+    let code = code.ok_or_else(|| HttpError::bad_request("Two-factor code is required"))?;
+    if !two_factor::verify_second_factor(pool, user_id, code).await? {
+        return Err(HttpError::bad_request("Invalid verification code"));
+    }
+    Ok(())
+}
+
+// Las cuentas creadas con un proveedor OIDC tienen una contraseña aleatoria que nadie conoce;
+// esas confirman la eliminación y la restauración con un enlace enviado por email
+async fn has_password(pool: &DbPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!("SELECT has_password FROM users WHERE id = $1", user_id)
+        .fetch_one(pool)
+        .await
+}
+
+/// Lo que confirma el enlace enviado por email.
+#[derive(Debug, Clone, Copy)]
+enum Confirmation {
+    Delete(AccountDeletionMode),
+    Restore,
+}
+
+impl Confirmation {
+    fn action(self) -> &'static str {
+        match self {
+            Confirmation::Delete(_) => "delete",
+            Confirmation::Restore => "restore",
+        }
+    }
+}
+
+/// Envía un enlace de confirmación de un solo uso. Solo vale el último de cada acción.
+async fn send_confirmation(
+    data: &AppState,
+    mailer: web::Data<dyn Mailer>,
+    user_id: i32,
+    to: String,
+    name: Option<String>,
+    confirmation: Confirmation,
+) -> Result<(), HttpError> {
+    let action = confirmation.action();
+    let deletion_mode = match confirmation {
+        Confirmation::Delete(mode) => Some(mode.as_str()),
+        Confirmation::Restore => None,
+    };
+    let token = secure_token::generate();
+    let expires_in = parse_duration(&data.config.account_confirmation_expires_in);
+    let expires_at = Utc::now() + Duration::seconds(expires_in);
+
+    let mut tx = data.pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE account_confirmation_tokens SET used_at = NOW()
+        WHERE user_id = $1 AND action = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        action
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO account_confirmation_tokens (user_id, action, deletion_mode, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        user_id,
+        action,
+        deletion_mode,
+        secure_token::hash(&token),
+        expires_at
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let link = format!(
+        "{}/account/{}/confirm?token={}",
+        data.config.frontend_url.trim_end_matches('/'),
+        action,
+        token
+    );
+    let (subject, request) = match confirmation {
+        Confirmation::Delete(_) => ("Confirma la eliminación de tu cuenta", "eliminar tu cuenta"),
+        Confirmation::Restore => ("Recupera tu cuenta", "cancelar la eliminación de tu cuenta"),
+    };
+    let email = Email {
+        to,
+        subject: subject.to_string(),
+        body: format!(
+            "Hola {},\n\nHemos recibido una solicitud para {}. Abre este enlace para confirmarla; \
+             caduca en {} minutos:\n\n{}\n\nSi no has sido tú, ignora este correo.",
+            name.unwrap_or_default(),
+            request,
+            expires_in / 60,
+            link
+        ),
+    };
+    let mailer = mailer.into_inner();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = mailer.send(email).await {
+            error!("Failed to send account confirmation email to user {}: {}", user_id, e);
+        }
+    });
+
+    Ok(())
+}
+
+/// Canjea un enlace de confirmación. Se marca como usado en la misma sentencia para que
+/// dos peticiones no lo canjeen a la vez.
+async fn redeem_confirmation(
+    pool: &DbPool,
+    token: &str,
+    action: &str,
+) -> Result<(i32, Option<String>), HttpError> {
+    let confirmation = sqlx::query!(
+        r#"
+        UPDATE account_confirmation_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND action = $2 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id, deletion_mode
+        "#,
+        secure_token::hash(token),
+        action
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| HttpError::bad_request("Invalid or expired confirmation link"))?;
+
+    Ok((confirmation.user_id, confirmation.deletion_mode))
+}
+
+/// Programa la eliminación de la cuenta. Hasta que termine el periodo de gracia la
+/// cuenta queda desactivada y se puede restaurar.
+///
+/// Las cuentas sin contraseña propia reciben un enlace para confirmarla en
+/// `/account/delete/confirm`.
+pub async fn request_deletion(
+    user: web::ReqData<User>,
+    claims: web::ReqData<TokenClaims>,
+    body: web::Json<DeleteAccount>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    let has_password = has_password(&data.pool, user.id).await?;
+    if has_password {
+        let password = body.password.as_deref().ok_or_else(|| HttpError::bad_request("Password is required"))?;
+        if !bcrypt::verify(password, &user.password_hash)? {
+            return Err(HttpError::bad_request("Password is incorrect"));
+        }
+    }
+    check_second_factor(&data.pool, user.id, body.code.as_deref()).await?;
+
+    if !has_password {
+        send_confirmation(
+            &data,
+            mailer,
+            user.id,
+            user.email.clone(),
+            user.name.clone(),
+            Confirmation::Delete(body.content),
+        )
+        .await?;
+        info!("Account deletion confirmation sent to user {}", user.id);
+
+        return Ok(HttpResponse::Accepted().json(serde_json::json!({
+            "status": "pending_confirmation",
+            "message": "Check your email to confirm the deletion"
+        })));
+    }
+
+    let scheduled_for = schedule_deletion(&data, mailer, user.id, body.content).await?;
+    // La sesión actual también se cierra
+    revocation::revoke_access_token(&data.pool, &claims).await?;
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "status": "success",
+        "message": "Account scheduled for deletion",
+        "scheduled_for": scheduled_for,
+        "content": body.content
+    })))
+}
+
+/// Confirma con el enlace del email la eliminación de una cuenta sin contraseña.
+pub async fn confirm_deletion(
+    body: web::Json<AccountConfirmation>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    let (user_id, mode) = redeem_confirmation(&data.pool, &body.token, "delete").await?;
+    let content = AccountDeletionMode::from_db(mode.as_deref());
+
+    let scheduled_for = schedule_deletion(&data, mailer, user_id, content).await?;
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "status": "success",
+        "message": "Account scheduled for deletion",
+        "scheduled_for": scheduled_for,
+        "content": content
+    })))
+}
+
+// Desactiva la cuenta, cierra sus sesiones y avisa por email de la fecha de borrado
+async fn schedule_deletion(
+    data: &AppState,
+    mailer: web::Data<dyn Mailer>,
+    user_id: i32,
+    content: AccountDeletionMode,
+) -> Result<DateTime<Utc>, HttpError> {
+    let scheduled_for = Utc::now() + Duration::seconds(parse_duration(&data.config.account_deletion_grace_period));
+
+    let account = sqlx::query!(
+        r#"
+        UPDATE users
+        SET is_active = false, deletion_scheduled_for = $1, deletion_mode = $2, updated_at = NOW()
+        WHERE id = $3 AND is_active = true
+        RETURNING email, name
+        "#,
+        scheduled_for,
+        content.as_str(),
+        user_id
+    )
+    .fetch_optional(&data.pool)
+    .await?
+    .ok_or_else(|| HttpError::conflict("Account is already scheduled for deletion"))?;
+
+    refresh_token::revoke_all(&data.pool, user_id).await?;
+
+    if let Err(e) = audit::record(&data.pool, Some(user_id), "account_deletion_requested", None, serde_json::json!({
+        "scheduled_for": scheduled_for,
+        "content": content.as_str()
+    }))
+    .await
+    {
+        error!("Failed to write audit log: {}", e);
+    }
+
+    let email = Email {
+        to: account.email,
+        subject: "Tu cuenta se eliminará".to_string(),
+        body: format!(
+            "Hola {},\n\nHemos recibido tu solicitud para eliminar tu cuenta. La cuenta está \
+             desactivada y se borrará definitivamente el {} (UTC).\n\n\
+             Si cambias de opinión, puedes recuperarla antes de esa fecha con tu email y tu contraseña, \
+             o pidiendo un enlace de restauración.",
+            account.name.unwrap_or_default(),
+            scheduled_for.format("%d/%m/%Y %H:%M")
+        ),
+    };
+    let mailer = mailer.into_inner();
+    actix_web::rt::spawn(async move {
+        if let Err(e) = mailer.send(email).await {
+            error!("Failed to send account deletion email to user {}: {}", user_id, e);
+        }
+    });
+
+    info!("Account deletion scheduled for user {} at {}", user_id, scheduled_for);
+    Ok(scheduled_for)
+}
+
+/// Cancela una eliminación pendiente y reactiva la cuenta.
+pub async fn restore_account(
+    req: HttpRequest,
+    body: web::Json<RestoreAccount>,
+    data: web::Data<AppState>,
+    guard: web::Data<LoginGuard>,
+) -> Result<HttpResponse, HttpError> {
+    body.0.validate()?;
+
+    // Cuenta como un intento de login a efectos de fuerza bruta
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+    let attempt = guard.begin(&body.email, ip.as_deref()).map_err(too_many_attempts)?;
+
+    let account = sqlx::query!(
+        r#"
+        SELECT id, password_hash FROM users
+        WHERE LOWER(email) = LOWER($1) AND is_active = false AND deletion_scheduled_for > NOW()
+          AND has_password
+        "#,
+        body.email
+    )
+    .fetch_optional(&data.pool)
+    .await?;
+
+    let account = match account {
+        Some(account) if bcrypt::verify(&body.password, &account.password_hash)? => account,
+        _ => {
+            guard.record_failure(attempt);
+            return Err(HttpError::unauthorized("Invalid email or password, or no deletion pending"));
+        }
+    };
+    check_second_factor(&data.pool, account.id, body.code.as_deref()).await?;
+
+    reactivate(&data.pool, account.id, ip.as_deref()).await?;
+    guard.record_success(attempt);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Account restored, you can log in again"
+    })))
+}
+
+/// Envía un enlace para restaurar la cuenta sin la contraseña, que es la única vía para
+/// las cuentas creadas con un proveedor OIDC.
+pub async fn request_restore(
+    body: web::Json<RestoreAccountRequest>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    body.0.validate()?;
+
+    let account = sqlx::query!(
+        r#"
+        SELECT id, email, name FROM users
+        WHERE LOWER(email) = LOWER($1) AND is_active = false AND deletion_scheduled_for > NOW()
+        "#,
+        body.email
+    )
+    .fetch_optional(&data.pool)
+    .await?;
+
+    if let Some(account) = account {
+        send_confirmation(
+            &data,
+            mailer,
+            account.id,
+            account.email,
+            account.name,
+            Confirmation::Restore,
+        )
+        .await?;
+        info!("Account restore link sent to user {}", account.id);
+    }
+
+    // La respuesta no revela si hay una eliminación pendiente para ese email
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "status": "success",
+        "message": "If the account is pending deletion, a restore link has been sent"
+    })))
+}
+
+/// Cancela la eliminación con el enlace recibido por email.
+pub async fn confirm_restore(
+    req: HttpRequest,
+    body: web::Json<AccountConfirmation>,
+    data: web::Data<AppState>,
+    guard: web::Data<LoginGuard>,
+) -> Result<HttpResponse, HttpError> {
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+
+    let account = sqlx::query!(
+        r#"
+        SELECT u.id, u.email FROM account_confirmation_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.token_hash = $1 AND t.action = 'restore' AND t.used_at IS NULL AND t.expires_at > NOW()
+          AND u.is_active = false AND u.deletion_scheduled_for > NOW()
+        "#,
+        secure_token::hash(&body.token)
+    )
+    .fetch_optional(&data.pool)
+    .await?
+    .ok_or_else(|| HttpError::bad_request("Invalid or expired confirmation link"))?;
+
+    // El enlace no se gasta hasta comprobar el segundo factor, que cuenta como un intento de login
+    let attempt = guard.begin(&account.email, ip.as_deref()).map_err(too_many_attempts)?;
+    if let Err(e) = check_second_factor(&data.pool, account.id, body.code.as_deref()).await {
+        guard.record_failure(attempt);
+        return Err(e);
+    }
+
+    redeem_confirmation(&data.pool, &body.token, "restore").await?;
+    reactivate(&data.pool, account.id, ip.as_deref()).await?;
+    guard.record_success(attempt);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Account restored, you can log in again"
+    })))
+}
+
+async fn reactivate(pool: &DbPool, user_id: i32, ip: Option<&str>) -> Result<(), HttpError> {
+    let restored = sqlx::query!(
+        r#"
+        UPDATE users
+        SET is_active = true, deletion_scheduled_for = NULL, deletion_mode = NULL, updated_at = NOW()
+        WHERE id = $1 AND is_active = false AND deletion_scheduled_for > NOW()
+        "#,
+        user_id
+    )
+    .execute(pool)
+    .await?;
+
+    // La tarea de borrado puede haberse adelantado
+    if restored.rows_affected() == 0 {
+        return Err(HttpError::bad_request("No deletion pending"));
+    }
+
+    if let Err(e) = audit::record(pool, Some(user_id), "account_restored", ip, serde_json::json!({})).await {
+        error!("Failed to write audit log: {}", e);
+    }
+    info!("Account deletion cancelled for user {}", user_id);
+    Ok(())
+}
+
+/// Datos personales del usuario, un fichero JSON por cada tipo.
+async fn collect_export(pool: &DbPool, user_id: i32) -> Result<Vec<(&'static str, Value)>, sqlx::Error> {
+    let profile = sqlx::query_scalar!(
+        r#"
+        SELECT row_to_json(u) as "data!" FROM (
+            SELECT id, email, name, bio, date_of_birth, avatar, last_login, created_at, updated_at,
+                   email_verified_at, totp_enabled_at IS NOT NULL as two_factor_enabled
+            FROM users WHERE id = $1
+        ) u
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let preferences = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(
+            (SELECT row_to_json(p) FROM (
+                SELECT theme, email_notifications, app_notifications, public_profile
+                FROM user_preferences WHERE user_id = $1
+            ) p),
+            'null'::json
+        ) as "data!"
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let posts = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(p ORDER BY p.created_at), '[]'::json) as "data!" FROM (
+            SELECT id, title, content, category, created_at, updated_at, likes_count, comments_count
+            FROM posts WHERE user_id = $1
+        ) p
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let comments = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(c ORDER BY c.created_at), '[]'::json) as "data!" FROM (
+            SELECT id, post_id, content, created_at, likes_count
+            FROM comments WHERE user_id = $1
+        ) c
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let likes = sqlx::query_scalar!(
+        r#"
+        SELECT json_build_object(
+            'posts', COALESCE((SELECT json_agg(post_id ORDER BY post_id) FROM post_likes WHERE user_id = $1), '[]'::json),
+            'comments', COALESCE((SELECT json_agg(comment_id ORDER BY comment_id) FROM comment_likes WHERE user_id = $1), '[]'::json)
+        ) as "data!"
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let saves = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(post_id ORDER BY post_id), '[]'::json) as "data!"
+        FROM post_saves WHERE user_id = $1
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let groups = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(m ORDER BY m.join_date), '[]'::json) as "data!" FROM (
+            SELECT gm.group_id, g.name as group_name, gm.role, gm.join_date
+            FROM group_members gm
+            JOIN groups g ON g.id = gm.group_id
+            WHERE gm.user_id = $1
+        ) m
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let group_posts = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(p ORDER BY p.created_at), '[]'::json) as "data!" FROM (
+            SELECT id, group_id, content, created_at, likes_count, comments_count
+            FROM group_posts WHERE user_id = $1
+        ) p
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let messages = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(m ORDER BY m.id), '[]'::json) as "data!" FROM (
+            SELECT id, group_id, content, timestamp
+            FROM messages WHERE user_id = $1
+        ) m
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let mood_records = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(r ORDER BY r.record_date), '[]'::json) as "data!" FROM (
+            SELECT mr.id, mr.record_date, mr.mood_score, mr.notes,
+                   al.name as anxiety_level,
+                   COALESCE(
+                       (SELECT json_agg(t.name ORDER BY t.name)
+                        FROM mood_record_triggers mrt JOIN triggers t ON t.id = mrt.trigger_id
+                        WHERE mrt.mood_record_id = mr.id),
+                       '[]'::json
+                   ) as triggers
+            FROM mood_records mr
+            LEFT JOIN anxiety_levels al ON al.id = mr.anxiety_level_id
+            WHERE mr.user_id = $1
+        ) r
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let triggers = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(t ORDER BY t.name), '[]'::json) as "data!" FROM (
+            SELECT id, name, created_at FROM triggers WHERE user_id = $1
+        ) t
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let security_events = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(e ORDER BY e.created_at), '[]'::json) as "data!" FROM (
+            SELECT event, ip_address, created_at FROM audit_log WHERE user_id = $1
+        ) e
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    let linked_accounts = sqlx::query_scalar!(
+        r#"
+        SELECT COALESCE(json_agg(i ORDER BY i.created_at), '[]'::json) as "data!" FROM (
+            SELECT provider, email, created_at, last_login FROM user_identities WHERE user_id = $1
+        ) i
+        "#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(vec![
+        ("profile.json", profile),
+        ("preferences.json", preferences),
+        ("posts.json", posts),
+        ("comments.json", comments),
+        ("likes.json", likes),
+        ("saved_posts.json", saves),
+        ("group_memberships.json", groups),
+        ("group_posts.json", group_posts),
+        ("messages.json", messages),
+        ("mood_records.json", mood_records),
+        ("triggers.json", triggers),
+        ("security_events.json", security_events),
+        ("linked_accounts.json", linked_accounts),
+    ])
+}
+
+fn build_zip(files: Vec<(&'static str, Value)>) -> Result<Vec<u8>, HttpError> {
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let zip_error = |e: zip::result::ZipError| {
+        error!("Failed to build data export: {}", e);
+        HttpError::InternalServerError
+    };
+
+    for (name, value) in files {
+        zip.start_file(name, options).map_err(zip_error)?;
+        let json = serde_json::to_vec_pretty(&value).map_err(|_| HttpError::InternalServerError)?;
+        zip.write_all(&json).map_err(|_| HttpError::InternalServerError)?;
+    }
+
+    Ok(zip.finish().map_err(zip_error)?.into_inner())
+}
+
+/// Descarga de todos los datos personales del usuario en un ZIP de ficheros JSON.
+pub async fn export_data(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let files = collect_export(&data.pool, user.id).await?;
+    let archive = build_zip(files)?;
+
+    if let Err(e) = audit::record(&data.pool, Some(user.id), "data_exported", None, serde_json::json!({})).await {
+        error!("Failed to write audit log: {}", e);
+    }
+
+    let filename = format!("datos-{}-{}.zip", user.id, Utc::now().format("%Y%m%d"));
+
+    Ok(HttpResponse::Ok()
+        .content_type("application/zip")
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", filename),
+        ))
+        .body(archive))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    use actix_web::{http::StatusCode, test::TestRequest};
+    use tokio::task::LocalSet;
+
+    use crate::services::login_guard::{InMemoryAttemptStore, LoginPolicy};
+    use crate::test_utils::{self, create_user, link_token, load_schema, RecordingMailer};
+    use crate::utils::totp;
+
+    fn guard() -> web::Data<LoginGuard> {
+        web::Data::new(LoginGuard::new(
+            LoginPolicy::from_config(&test_utils::config()),
+            Arc::new(InMemoryAttemptStore::default()),
+        ))
+    }
+
+    fn confirmation(token: &str, code: Option<String>) -> web::Json<AccountConfirmation> {
+        web::Json(AccountConfirmation { token: token.to_string(), code })
+    }
+
+    async fn account_state(pool: &DbPool, user_id: i32) -> (bool, Option<String>) {
+        sqlx::query_as("SELECT is_active, deletion_mode FROM users WHERE id = $1")
+            .bind(user_id)
+            .fetch_one(pool)
+            .await
+            .unwrap()
+    }
+
+    async fn schedule(pool: &DbPool, user_id: i32) {
+        sqlx::query(
+            r#"
+            UPDATE users SET is_active = false, deletion_mode = 'delete',
+                deletion_scheduled_for = NOW() + INTERVAL '30 days'
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    // Los handlers envían el correo con actix_web::rt::spawn, que necesita un LocalSet
+    async fn send_and_read(data: &web::Data<AppState>, user_id: i32, email: &str, action: Confirmation) -> String {
+        let (mailer, mailer_data) = RecordingMailer::new();
+        send_confirmation(data, mailer_data, user_id, email.to_string(), None, action)
+            .await
+            .unwrap();
+        link_token(&mailer.next().await.expect("confirmation email"))
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn deletion_link_schedules_the_deletion_once(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "oidc@example.com").await;
+        let data = test_utils::app_state(&pool);
+
+        LocalSet::new()
+            .run_until(async {
+                let token = send_and_read(
+                    &data,
+                    user_id,
+                    "oidc@example.com",
+                    Confirmation::Delete(AccountDeletionMode::Anonymize),
+                )
+                .await;
+
+                let (_, mailer) = RecordingMailer::new();
+                let response = confirm_deletion(confirmation(&token, None), data.clone(), mailer.clone())
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::ACCEPTED);
+                assert_eq!(account_state(&pool, user_id).await, (false, Some("anonymize".to_string())));
+
+                let reused = confirm_deletion(confirmation(&token, None), data.clone(), mailer).await;
+                assert!(matches!(reused, Err(HttpError::BadRequest(_))));
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn only_the_latest_link_is_valid(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "oidc@example.com").await;
+        let data = test_utils::app_state(&pool);
+
+        LocalSet::new()
+            .run_until(async {
+                let delete = Confirmation::Delete(AccountDeletionMode::Delete);
+                let first = send_and_read(&data, user_id, "oidc@example.com", delete).await;
+                let second = send_and_read(&data, user_id, "oidc@example.com", delete).await;
+                let (_, mailer) = RecordingMailer::new();
+
+                let result = confirm_deletion(confirmation(&first, None), data.clone(), mailer.clone()).await;
+                assert!(matches!(result, Err(HttpError::BadRequest(_))));
+                assert!(confirm_deletion(confirmation(&second, None), data.clone(), mailer).await.is_ok());
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn expired_links_are_rejected(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "oidc@example.com").await;
+        let data = test_utils::app_state(&pool);
+
+        LocalSet::new()
+            .run_until(async {
+                let delete = Confirmation::Delete(AccountDeletionMode::Delete);
+                let token = send_and_read(&data, user_id, "oidc@example.com", delete).await;
+                sqlx::query("UPDATE account_confirmation_tokens SET expires_at = NOW() - INTERVAL '1 second'")
+                    .execute(&pool)
+                    .await
+                    .unwrap();
+                let (_, mailer) = RecordingMailer::new();
+
+                let result = confirm_deletion(confirmation(&token, None), data.clone(), mailer).await;
+                assert!(matches!(result, Err(HttpError::BadRequest(_))));
+                assert_eq!(account_state(&pool, user_id).await, (true, None));
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn restore_link_reactivates_the_account(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "oidc@example.com").await;
+        schedule(&pool, user_id).await;
+        let data = test_utils::app_state(&pool);
+        let req = TestRequest::default().to_http_request();
+
+        LocalSet::new()
+            .run_until(async {
+                let (mailer, mailer_data) = RecordingMailer::new();
+                let body = web::Json(RestoreAccountRequest { email: "OIDC@example.com".to_string() });
+                request_restore(body, data.clone(), mailer_data).await.unwrap();
+                let token = link_token(&mailer.next().await.expect("restore email"));
+
+                let response = confirm_restore(req.clone(), confirmation(&token, None), data.clone(), guard())
+                    .await
+                    .unwrap();
+                assert_eq!(response.status(), StatusCode::OK);
+                assert_eq!(account_state(&pool, user_id).await, (true, None));
+
+                let reused = confirm_restore(req.clone(), confirmation(&token, None), data.clone(), guard()).await;
+                assert!(matches!(reused, Err(HttpError::BadRequest(_))));
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn restore_is_not_sent_for_active_accounts(pool: DbPool) {
+        load_schema(&pool).await;
+        create_user(&pool, "active@example.com").await;
+        let data = test_utils::app_state(&pool);
+
+        LocalSet::new()
+            .run_until(async {
+                let (mailer, mailer_data) = RecordingMailer::new();
+                let body = web::Json(RestoreAccountRequest { email: "active@example.com".to_string() });
+                let response = request_restore(body, data.clone(), mailer_data).await.unwrap();
+
+                // Misma respuesta que si hubiera una eliminación pendiente
+                assert_eq!(response.status(), StatusCode::ACCEPTED);
+                assert!(mailer.next().await.is_none());
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn links_only_confirm_their_own_action(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "oidc@example.com").await;
+        let data = test_utils::app_state(&pool);
+        let req = TestRequest::default().to_http_request();
+
+        LocalSet::new()
+            .run_until(async {
+                let delete = Confirmation::Delete(AccountDeletionMode::Delete);
+                let token = send_and_read(&data, user_id, "oidc@example.com", delete).await;
+                schedule(&pool, user_id).await;
+
+                let result = confirm_restore(req, confirmation(&token, None), data.clone(), guard()).await;
+                assert!(matches!(result, Err(HttpError::BadRequest(_))));
+                assert!(!account_state(&pool, user_id).await.0);
+            })
+            .await;
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn restore_link_asks_for_the_second_factor(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "oidc@example.com").await;
+        let secret = totp::generate_secret();
+        sqlx::query("UPDATE users SET totp_secret = $1, totp_enabled_at = NOW() WHERE id = $2")
+            .bind(&secret)
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        schedule(&pool, user_id).await;
+        let data = test_utils::app_state(&pool);
+        let req = TestRequest::default().to_http_request();
+
+        LocalSet::new()
+            .run_until(async {
+                let token = send_and_read(&data, user_id, "oidc@example.com", Confirmation::Restore).await;
+
+                let result = confirm_restore(req.clone(), confirmation(&token, None), data.clone(), guard()).await;
+                assert!(matches!(result, Err(HttpError::BadRequest(_))));
+
+                // El intento sin código no gasta el enlace
+                let code = totp::code_for(&secret, Utc::now().timestamp());
+                confirm_restore(req, confirmation(&token, Some(code)), data.clone(), guard())
+                    .await
+                    .unwrap();
+                assert_eq!(account_state(&pool, user_id).await, (true, None));
+            })
+            .await;
+    }
+}
@@ -3,36 +3,110 @@ use actix_web::HttpMessage;  // Add this import for extensions()
 use bcrypt::verify;
 use sqlx::PgPool;
 use validator::Validate;
-use chrono::Utc;
+use chrono::{Duration, Utc};
 use actix_web::{web, HttpResponse, HttpRequest};
-use log::{info, error};
+use log::{info, error, warn};
 
 use crate::{
-    models::{LoginUser, RegisterUser, User},
-    utils::{error::HttpError, jwt::encode_token},
+    handlers::{two_factor, verification},
+    models::{auth::{LoginTwoFactor, RefreshTokenRequest, TokenClaims}, LoginUser, RegisterUser, User},
+    services::{
+        login_guard::{Lockout, LoginAttempt, LoginGuard},
+        mailer::{Email, Mailer},
+    },
+    utils::{audit, error::HttpError, refresh_token, revocation},
     AppState,
 };
 
 // Type alias for better readability
 type DbPool = PgPool;
 
+// Registra el fallo en la protección del login y deja constancia de los bloqueos que provoque
+async fn login_failed(
+    data: &AppState,
+    guard: &LoginGuard,
+    attempt: LoginAttempt,
+    mailer: web::Data<dyn Mailer>,
+    ip: Option<&str>,
+    user: Option<&User>,
+) -> HttpError {
+    for lockout in guard.record_failure(attempt) {
+        let (event, details) = match &lockout {
+            Lockout::Account { email, until } => {
+                warn!("Account {} locked until {} after repeated failed logins", email, until);
+                ("login_account_locked", serde_json::json!({ "email": email, "locked_until": until }))
+            }
+            Lockout::Ip { ip, until } => {
+                warn!("IP {} locked until {} after repeated failed logins", ip, until);
+                ("login_ip_locked", serde_json::json!({ "locked_until": until }))
+            }
+        };
+
+        if let Err(e) = audit::record(&data.pool, user.map(|u| u.id), event, ip, details).await {
+            error!("Failed to write audit log: {}", e);
+        }
+
+        // Se avisa al titular de la cuenta y se le indica cómo desbloquearla
+        if let (Lockout::Account { until, .. }, Some(user)) = (&lockout, user) {
+            let email = Email {
+                to: user.email.clone(),
+                subject: "Tu cuenta se ha bloqueado temporalmente".to_string(),
+                body: format!(
+                    "Hola {},\n\nHemos bloqueado el inicio de sesión en tu cuenta hasta las {} (UTC) \
+                     tras varios intentos fallidos.\n\nSi no has sido tú, te recomendamos cambiar tu \
+                     contraseña. Restablecerla desde {}/recover-password desbloquea la cuenta al momento.",
+                    user.name.clone().unwrap_or_default(),
+                    until.format("%H:%M"),
+                    data.config.frontend_url.trim_end_matches('/')
+                ),
+            };
+            let mailer = mailer.clone().into_inner();
+            let user_id = user.id;
+            actix_web::rt::spawn(async move {
+                if let Err(e) = mailer.send(email).await {
+                    error!("Failed to send lockout email to user {}: {}", user_id, e);
+                }
+            });
+        }
+    }
+
+    HttpError::unauthorized("Invalid email or password")
+}
+
+/// Respuesta cuando la protección del login obliga a esperar.
+pub(crate) fn too_many_attempts(wait: Duration) -> HttpError {
+    HttpError::too_many_requests(format!(
+        "Too many failed login attempts. Try again in {} seconds",
+        wait.num_seconds().max(1)
+    ))
+}
+
 pub async fn login(
     req: HttpRequest,
     user: web::Json<LoginUser>,
     data: web::Data<AppState>,
+    guard: web::Data<LoginGuard>,
+    mailer: web::Data<dyn Mailer>,
 ) -> Result<HttpResponse, HttpError> {
     info!("Login attempt for email: {}", user.email);
     
     // Validate user input
     user.0.validate()?;
 
+    // Se usa la IP de la conexión: las cabeceras de proxy las puede falsear el cliente
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+
+    let attempt = guard.begin(&user.email, ip.as_deref()).map_err(too_many_attempts)?;
+
     // Check if user exists
     let user_db: Option<User> = sqlx::query_as(
         r#"
         SELECT id, email, password_hash, name, bio, date_of_birth, avatar, 
-               last_login, created_at, updated_at, is_active
+               last_login, created_at, updated_at, is_active, email_verified_at
         FROM users 
-        WHERE email = $1 AND is_active = true
+        WHERE LOWER(email) = LOWER($1) AND is_active = true
+        ORDER BY email = $1 DESC, id
+        LIMIT 1
         "#
     )
     .bind(&user.email)
@@ -43,7 +117,7 @@ pub async fn login(
         Some(user) => user,
         None => {
             error!("Login failed: User not found or inactive - {}", user.email);
-            return Err(HttpError::unauthorized("Invalid email or password"));
+            return Err(login_failed(&data, &guard, attempt, mailer, ip.as_deref(), None).await);
         },
     };
 
@@ -56,12 +130,68 @@ pub async fn login(
 
     if !is_valid {
         error!("Invalid password for user: {}", user.email);
-        return Err(HttpError::unauthorized("Invalid email or password"));
+        return Err(login_failed(&data, &guard, attempt, mailer, ip.as_deref(), Some(&user_db)).await);
+    }
+
+    // Con 2FA la sesión no se abre hasta recibir el código; los fallos de la cuenta
+    // no se ponen a cero para que siga limitado el número de códigos que se pueden probar
+    if two_factor::is_enabled(&data.pool, user_db.id).await? {
+        info!("Password accepted, second factor required for user: {}", user_db.email);
+        guard.release(attempt);
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "status": "mfa_required",
+            "mfa_token": data.jwt_keys.encode_mfa_token(user_db.id)?
+        })));
+    }
+
+    guard.record_success(attempt);
+    start_session(&req, &data, user_db).await
+}
+
+/// Segundo paso del login para las cuentas con 2FA.
+pub async fn login_two_factor(
+    req: HttpRequest,
+    body: web::Json<LoginTwoFactor>,
+    data: web::Data<AppState>,
+    guard: web::Data<LoginGuard>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    let user_id = data.jwt_keys.decode_mfa_token(&body.mfa_token)
+        .ok_or_else(|| HttpError::unauthorized("Invalid or expired two-factor session, please log in again"))?;
+
+    let user_db: User = sqlx::query_as(
+        r#"
+        SELECT id, email, password_hash, name, bio, date_of_birth, avatar,
+               last_login, created_at, updated_at, is_active, email_verified_at
+        FROM users
+        WHERE id = $1 AND is_active = true
+        "#
+    )
+    .bind(user_id)
+    .fetch_optional(&data.pool)
+    .await?
+    .ok_or_else(|| HttpError::unauthorized("Invalid or expired two-factor session, please log in again"))?;
+
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+
+    let attempt = guard.begin(&user_db.email, ip.as_deref()).map_err(too_many_attempts)?;
+
+    if !two_factor::verify_second_factor(&data.pool, user_db.id, &body.code).await? {
+        error!("Invalid two-factor code for user: {}", user_db.email);
+        login_failed(&data, &guard, attempt, mailer, ip.as_deref(), Some(&user_db)).await;
+        return Err(HttpError::unauthorized("Invalid verification code"));
     }
 
+    guard.record_success(attempt);
+    start_session(&req, &data, user_db).await
+}
 
+/// Abre la sesión de un usuario ya autenticado y devuelve sus tokens.
+pub(crate) async fn start_session(req: &HttpRequest, data: &AppState, user_db: User) -> Result<HttpResponse, HttpError> {
     // Generate JWT token
-    let token = encode_token(user_db.id)?;
+    let (session_id, refresh_token) =
+        refresh_token::issue(&data.pool, user_db.id, &data.config.refresh_token_expires_in).await?;
+    let token = data.jwt_keys.encode_token(user_db.id, session_id)?;
 
     // Update last login time
     sqlx::query(
@@ -86,11 +216,13 @@ pub async fn login(
     let response = serde_json::json!({
         "status": "success",
         "token": token,
+        "refresh_token": refresh_token,
         "user": {
             "id": user_db.id,
             "email": user_db.email,
             "name": user_db.name,
-            "avatar": user_db.avatar
+            "avatar": user_db.avatar,
+            "email_verified": user_db.is_email_verified()
         }
     });
 
@@ -98,16 +230,22 @@ pub async fn login(
     Ok(HttpResponse::Ok().json(response))
 }
 
-pub async fn logout(id: Identity) -> Result<HttpResponse, HttpError> {
-    let username = id.id().unwrap_or_else(|_| "unknown".to_string());
-    
-    if username == "unknown" {
-        return Err(HttpError::unauthorized("Not authenticated"));
+pub async fn logout(
+    id: Option<Identity>,
+    claims: web::ReqData<TokenClaims>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    // Revocar el access token y cerrar la sesión a la que pertenece
+    revocation::revoke_access_token(&data.pool, &claims).await?;
+    if let (Ok(user_id), Ok(session_id)) = (claims.sub.parse::<i32>(), uuid::Uuid::parse_str(&claims.sid)) {
+        refresh_token::revoke_family(&data.pool, user_id, session_id).await?;
     }
-    
-    id.logout();
-    
-    info!("User logged out: {}", username);
+
+    if let Some(id) = id {
+        id.logout();
+    }
+
+    info!("User logged out: {}", claims.sub);
     
     Ok(HttpResponse::Ok().json(serde_json::json!({
         "status": "success",
@@ -118,6 +256,7 @@ pub async fn logout(id: Identity) -> Result<HttpResponse, HttpError> {
 pub async fn register(
     user: web::Json<RegisterUser>,
     data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
 ) -> Result<HttpResponse, HttpError> {
     info!("Registration attempt for email: {}", user.email);
     
@@ -126,7 +265,7 @@ pub async fn register(
 
     // Check if email already exists
     let exists: bool = sqlx::query_scalar(
-        "SELECT EXISTS(SELECT 1 FROM users WHERE email = $1)"
+        "SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1))"
     )
     .bind(&user.email)
     .fetch_one(&data.pool)
@@ -140,13 +279,15 @@ pub async fn register(
     // Hash password
     let hashed_password = bcrypt::hash(&user.password, 12)?;
 
+    let mut tx = data.pool.begin().await?;
+
     // Insert new user
     let user_db: User = sqlx::query_as(
         r#"
         INSERT INTO users (email, password_hash, name, is_active, created_at, updated_at)
         VALUES ($1, $2, $3, $4, $5, $6)
         RETURNING id, email, password_hash, name, bio, date_of_birth, avatar, 
-                 last_login, created_at, updated_at, is_active
+                 last_login, created_at, updated_at, is_active, email_verified_at
         "#
     )
     .bind(&user.email)
@@ -155,11 +296,28 @@ pub async fn register(
     .bind(true) // is_active
     .bind(Utc::now())
     .bind(Utc::now())
-    .fetch_one(&data.pool)
+    .fetch_one(&mut *tx)
     .await?;
 
+    // La cuenta funciona desde ya, pero el email queda pendiente de verificar
+    let verification_token =
+        verification::create_token(&mut tx, &data.config, user_db.id, &user_db.email).await?;
+
+    tx.commit().await?;
+
+    verification::send_link(
+        mailer.into_inner(),
+        &data.config,
+        user_db.id,
+        user_db.name.as_deref(),
+        user_db.email.clone(),
+        &verification_token,
+    );
+
     // Generate JWT token
-    let token = encode_token(user_db.id)?;
+    let (session_id, refresh_token) =
+        refresh_token::issue(&data.pool, user_db.id, &data.config.refresh_token_expires_in).await?;
+    let token = data.jwt_keys.encode_token(user_db.id, session_id)?;
 
     let response = serde_json::json!({
         "status": "success",
@@ -167,11 +325,96 @@ pub async fn register(
             "id": user_db.id,
             "email": user_db.email,
             "name": user_db.name,
-            "avatar": user_db.avatar
+            "avatar": user_db.avatar,
+            "email_verified": user_db.is_email_verified()
         },
-        "token": token
+        "token": token,
+        "refresh_token": refresh_token
     });
 
     info!("Registration successful for user: {}", user_db.email);
     Ok(HttpResponse::Created().json(response))
-}
\ No newline at end of file
+}
+
+pub async fn refresh(
+    body: web::Json<RefreshTokenRequest>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    // El refresh token usado queda invalidado y se entrega uno nuevo junto al access token
+    let (user_id, session_id, refresh_token) =
+        refresh_token::rotate(&data.pool, &body.refresh_token, &data.config.refresh_token_expires_in).await?;
+
+    // La cuenta puede haberse desactivado después de emitir el token
+    let is_active: bool = sqlx::query_scalar("SELECT COALESCE(is_active, true) FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(&data.pool)
+        .await?
+        .unwrap_or(false);
+
+    if !is_active {
+        return Err(HttpError::unauthorized("Account is disabled"));
+    }
+
+    let token = data.jwt_keys.encode_token(user_id, session_id)?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "token": token,
+        "refresh_token": refresh_token
+    })))
+}
+
+pub async fn logout_all(
+    id: Option<Identity>,
+    user: web::ReqData<User>,
+    claims: web::ReqData<TokenClaims>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    // Cerrar todas las sesiones invalida también los access tokens emitidos para ellas
+    refresh_token::revoke_all(&data.pool, user.id).await?;
+    revocation::revoke_access_token(&data.pool, &claims).await?;
+
+    if let Some(id) = id {
+        id.logout();
+    }
+
+    info!("User logged out from all sessions: {}", user.id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Successfully logged out from all sessions"
+    })))
+}
+
+pub async fn get_sessions(
+    user: web::ReqData<User>,
+    claims: web::ReqData<TokenClaims>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let mut sessions = refresh_token::list_sessions(&data.pool, user.id).await?;
+    for session in &mut sessions {
+        session.current = session.id.to_string() == claims.sid;
+    }
+
+    Ok(HttpResponse::Ok().json(sessions))
+}
+
+pub async fn revoke_session(
+    user: web::ReqData<User>,
+    session_id: web::Path<uuid::Uuid>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    if !refresh_token::revoke_family(&data.pool, user.id, *session_id).await? {
+        return Err(HttpError::not_found("Session not found"));
+    }
+
+    info!("Session {} revoked by user {}", session_id, user.id);
+    Ok(HttpResponse::NoContent().finish())
+}
+
+/// Publica las claves públicas de firma de los JWT (JWK Set).
+pub async fn jwks(data: web::Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok()
+        .insert_header(("Cache-Control", "public, max-age=300"))
+        .json(data.jwt_keys.jwks())
+}
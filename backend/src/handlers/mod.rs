@@ -1 +1,7 @@
-pub mod auth;
\ No newline at end of file
+pub mod account;
+pub mod auth;
+pub mod oidc;
+pub mod password;
+pub mod profile;
+pub mod two_factor;
+pub mod verification;
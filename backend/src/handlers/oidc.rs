@@ -0,0 +1,343 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::{Duration, Utc};
+use log::{error, info};
+
+use crate::{
+    config::{Config, OidcProviderConfig},
+    db::DbPool,
+    handlers::{auth::start_session, two_factor},
+    models::auth::{OidcCallback, User},
+    services::oidc::{IdTokenClaims, OidcClient, OidcError},
+    utils::{audit, error::HttpError, secure_token},
+    AppState,
+};
+
+// Tiempo para completar el inicio de sesión en el proveedor
+const AUTH_REQUEST_EXPIRES_IN: i64 = 10 * 60;
+
+// Página del frontend a la que vuelve el usuario; tiene que estar registrada en el proveedor
+fn redirect_uri(config: &Config, provider: &OidcProviderConfig) -> String {
+    format!(
+        "{}/login/{}/callback",
+        config.frontend_url.trim_end_matches('/'),
+        provider.name
+    )
+}
+
+fn provider_error(e: OidcError) -> HttpError {
+    error!("OIDC error: {}", e);
+    match e {
+        OidcError::UnknownProvider(_) => HttpError::not_found(e),
+        OidcError::Http(_) | OidcError::Discovery(_) => {
+            HttpError::bad_gateway("The identity provider is not available, please try again later")
+        }
+        OidcError::TokenExchange(_) | OidcError::InvalidIdToken(_) => {
+            HttpError::unauthorized("Could not sign in with the identity provider")
+        }
+    }
+}
+
+/// Proveedores con los que se puede iniciar sesión, para mostrar sus botones.
+pub async fn providers(oidc: web::Data<OidcClient>) -> HttpResponse {
+    let providers: Vec<serde_json::Value> = oidc
+        .providers()
+        .iter()
+        .map(|provider| serde_json::json!({
+            "name": provider.name,
+            "display_name": provider.display_name
+        }))
+        .collect();
+
+    HttpResponse::Ok().json(serde_json::json!({ "providers": providers }))
+}
+
+/// Empieza el inicio de sesión: devuelve la URL del proveedor a la que hay que redirigir.
+pub async fn authorize(
+    provider: web::Path<String>,
+    data: web::Data<AppState>,
+    oidc: web::Data<OidcClient>,
+) -> Result<HttpResponse, HttpError> {
+    let provider = oidc.provider(&provider).map_err(provider_error)?;
+
+    let state = secure_token::generate();
+    let nonce = secure_token::generate();
+    let code_verifier = secure_token::generate();
+
+    let authorization_url = oidc
+        .authorization_url(provider, &redirect_uri(&data.config, provider), &state, &nonce, &code_verifier)
+        .await
+        .map_err(provider_error)?;
+
+    sqlx::query!("DELETE FROM oidc_auth_requests WHERE expires_at < NOW()")
+        .execute(&data.pool)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO oidc_auth_requests (state_hash, provider, code_verifier, nonce, expires_at)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+        secure_token::hash(&state),
+        provider.name,
+        code_verifier,
+        nonce,
+        Utc::now() + Duration::seconds(AUTH_REQUEST_EXPIRES_IN)
+    )
+    .execute(&data.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "authorization_url": authorization_url
+    })))
+}
+
+/// Termina el inicio de sesión con el código que el proveedor ha devuelto al frontend.
+pub async fn callback(
+    req: HttpRequest,
+    provider: web::Path<String>,
+    body: web::Json<OidcCallback>,
+    data: web::Data<AppState>,
+    oidc: web::Data<OidcClient>,
+) -> Result<HttpResponse, HttpError> {
+    let provider = oidc.provider(&provider).map_err(provider_error)?;
+
+    // El state es de un solo uso y tiene que ser del mismo proveedor
+    let request = sqlx::query!(
+        r#"
+        DELETE FROM oidc_auth_requests
+        WHERE state_hash = $1 AND provider = $2 AND expires_at > NOW()
+        RETURNING code_verifier, nonce
+        "#,
+        secure_token::hash(&body.state),
+        provider.name
+    )
+    .fetch_optional(&data.pool)
+    .await?
+    .ok_or_else(|| HttpError::bad_request("Invalid or expired login request, please try again"))?;
+
+    let claims = oidc
+        .exchange_code(
+            provider,
+            &body.code,
+            &request.code_verifier,
+            &redirect_uri(&data.config, provider),
+            &request.nonce,
+        )
+        .await
+        .map_err(provider_error)?;
+
+    let ip = req.peer_addr().map(|addr| addr.ip().to_string());
+    let user_db = find_or_create_user(&data.pool, provider, &claims, ip.as_deref()).await?;
+
+    // Igual que con la contraseña, la 2FA se pide también aquí
+    if two_factor::is_enabled(&data.pool, user_db.id).await? {
+        info!("OIDC login accepted, second factor required for user: {}", user_db.email);
+        return Ok(HttpResponse::Ok().json(serde_json::json!({
+            "status": "mfa_required",
+            "mfa_token": data.jwt_keys.encode_mfa_token(user_db.id)?
+        })));
+    }
+
+    start_session(&req, &data, user_db).await
+}
+
+// Devuelve el usuario vinculado a la identidad del proveedor. Si no lo hay, vincula la
+// cuenta con el mismo email verificado o crea una nueva.
+async fn find_or_create_user(
+    pool: &DbPool,
+    provider: &OidcProviderConfig,
+    claims: &IdTokenClaims,
+    ip: Option<&str>,
+) -> Result<User, HttpError> {
+    let mut tx = pool.begin().await?;
+
+    let linked: Option<User> = sqlx::query_as(
+        r#"
+        SELECT u.id, u.email, u.password_hash, u.name, u.bio, u.date_of_birth, u.avatar,
+               u.last_login, u.created_at, u.updated_at, u.is_active, u.email_verified_at
+        FROM user_identities i
+        JOIN users u ON u.id = i.user_id
+        WHERE i.provider = $1 AND i.subject = $2
+        "#
+    )
+    .bind(&provider.name)
+    .bind(&claims.sub)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if let Some(user) = linked {
+        if !user.is_active {
+            return Err(HttpError::unauthorized("Account is disabled"));
+        }
+        sqlx::query!(
+            "UPDATE user_identities SET last_login = NOW(), email = $1 WHERE provider = $2 AND subject = $3",
+            claims.email,
+            provider.name,
+            claims.sub
+        )
+        .execute(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        return Ok(user);
+    }
+
+    // Sin un email verificado por el proveedor no se puede vincular ni crear la cuenta
+    let email = claims
+        .email
+        .as_deref()
+        .filter(|_| claims.email_verified)
+        .ok_or_else(|| {
+            HttpError::bad_request(format!(
+                "Your {} account has no verified email address",
+                provider.display_name
+            ))
+        })?;
+
+    // Los proveedores no respetan las mayúsculas con que se registró el email. Si hubiera
+    // varias cuentas que solo se diferencian en ellas, se elige la que coincide exactamente
+    let existing: Option<User> = sqlx::query_as(
+        r#"
+        SELECT id, email, password_hash, name, bio, date_of_birth, avatar,
+               last_login, created_at, updated_at, is_active, email_verified_at
+        FROM users
+        WHERE LOWER(email) = LOWER($1)
+        ORDER BY email = $1 DESC, id
+        LIMIT 1
+        FOR UPDATE
+        "#
+    )
+    .bind(email)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    let (user, event) = match existing {
+        Some(user) if !user.is_active => return Err(HttpError::unauthorized("Account is disabled")),
+        // Quien registró el email sin verificarlo podría no ser su dueño; vincularla le daría
+        // acceso a la cuenta del proveedor
+        Some(user) if !user.is_email_verified() => {
+            return Err(HttpError::conflict(
+                "An account with this email already exists. Log in with your password and verify your email to link it",
+            ));
+        }
+        Some(user) => (user, "oidc_identity_linked"),
+        None => {
+            // La contraseña queda sin usar (has_password = false); se puede fijar con la
+            // recuperación de contraseña
+            let password_hash = bcrypt::hash(secure_token::generate(), 12)?;
+            let user: User = sqlx::query_as(
+                r#"
+                INSERT INTO users (email, password_hash, has_password, name, avatar, is_active, email_verified_at, created_at, updated_at)
+                VALUES ($1, $2, false, $3, $4, true, NOW(), NOW(), NOW())
+                RETURNING id, email, password_hash, name, bio, date_of_birth, avatar,
+                          last_login, created_at, updated_at, is_active, email_verified_at
+                "#
+            )
+            .bind(email)
+            .bind(&password_hash)
+            .bind(claims.name.as_deref().unwrap_or(email.split('@').next().unwrap_or_default()))
+            .bind(&claims.picture)
+            .fetch_one(&mut *tx)
+            .await?;
+            (user, "oidc_account_created")
+        }
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO user_identities (user_id, provider, subject, email, last_login)
+        VALUES ($1, $2, $3, $4, NOW())
+        "#,
+        user.id,
+        provider.name,
+        claims.sub,
+        email
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    info!("{} for user {} with provider {}", event, user.id, provider.name);
+    if let Err(e) = audit::record(pool, Some(user.id), event, ip, serde_json::json!({
+        "provider": provider.name
+    }))
+    .await
+    {
+        error!("Failed to write audit log: {}", e);
+    }
+
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::oidc::OidcClient;
+    use crate::test_utils::{create_user, load_schema, MockIssuer};
+
+    // Inicia sesión en el proveedor de pruebas con ese email y devuelve los claims validados
+    async fn sign_in(issuer: &MockIssuer, email: &str, email_verified: bool) -> IdTokenClaims {
+        let provider = issuer.provider();
+        let client = OidcClient::with_providers(vec![provider.clone()]);
+        issuer.respond_with(issuer.sign("key-1", &issuer.claims("nonce", email, email_verified)));
+
+        client
+            .exchange_code(&provider, "code", "verifier", "http://localhost/callback", "nonce")
+            .await
+            .unwrap()
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn links_an_account_with_the_same_verified_email(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "Ana@Example.com").await;
+        let issuer = MockIssuer::start();
+        let claims = sign_in(&issuer, "ana@example.com", true).await;
+
+        let user = find_or_create_user(&pool, &issuer.provider(), &claims, None).await.unwrap();
+        assert_eq!(user.id, user_id);
+
+        // La siguiente vez se encuentra por la identidad vinculada
+        let user = find_or_create_user(&pool, &issuer.provider(), &claims, None).await.unwrap();
+        assert_eq!(user.id, user_id);
+        let identities: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM user_identities WHERE user_id = $1")
+            .bind(user_id)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(identities, 1);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn does_not_link_an_account_with_an_unverified_email(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "ana@example.com").await;
+        sqlx::query("UPDATE users SET email_verified_at = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        let issuer = MockIssuer::start();
+        let claims = sign_in(&issuer, "ana@example.com", true).await;
+
+        let result = find_or_create_user(&pool, &issuer.provider(), &claims, None).await;
+        assert!(matches!(result, Err(HttpError::Conflict(_))));
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn requires_an_email_verified_by_the_provider(pool: DbPool) {
+        load_schema(&pool).await;
+        create_user(&pool, "ana@example.com").await;
+        let issuer = MockIssuer::start();
+        let claims = sign_in(&issuer, "ana@example.com", false).await;
+
+        let result = find_or_create_user(&pool, &issuer.provider(), &claims, None).await;
+        assert!(matches!(result, Err(HttpError::BadRequest(_))));
+
+        let users: i64 = sqlx::query_scalar("SELECT COUNT(*) FROM users")
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(users, 1);
+    }
+}
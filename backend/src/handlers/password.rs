@@ -0,0 +1,198 @@
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use log::{error, info};
+use validator::Validate;
+
+use crate::{
+    models::auth::{ForgotPassword, ResetPassword, ResetTokenCheck},
+    services::{
+        login_guard::LoginGuard,
+        mailer::{Email, Mailer},
+    },
+    utils::{audit, error::HttpError, jwt::parse_duration, refresh_token, secure_token},
+    AppState,
+};
+
+pub async fn forgot_password(
+    body: web::Json<ForgotPassword>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    body.0.validate()?;
+
+    // El enlace va a la dirección guardada, aunque se haya escrito con otras mayúsculas
+    let user: Option<(i32, String, Option<String>)> = sqlx::query_as(
+        r#"
+        SELECT id, email, name FROM users
+        WHERE LOWER(email) = LOWER($1) AND is_active = true
+        ORDER BY email = $1 DESC, id
+        LIMIT 1
+        "#
+    )
+    .bind(&body.email)
+    .fetch_optional(&data.pool)
+    .await?;
+
+    if let Some((user_id, to, name)) = user {
+        let token = secure_token::generate();
+        let expires_in = parse_duration(&data.config.password_reset_expires_in);
+        let expires_at = Utc::now() + Duration::seconds(expires_in);
+
+        let mut tx = data.pool.begin().await?;
+
+        // Solo vale el último enlace enviado
+        sqlx::query!(
+            "UPDATE password_reset_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        sqlx::query!(
+            "INSERT INTO password_reset_tokens (user_id, token_hash, expires_at) VALUES ($1, $2, $3)",
+            user_id,
+            secure_token::hash(&token),
+            expires_at
+        )
+        .execute(&mut *tx)
+        .await?;
+
+        tx.commit().await?;
+
+        let link = format!(
+            "{}/reset-password?token={}",
+            data.config.frontend_url.trim_end_matches('/'),
+            token
+        );
+        let email = Email {
+            to,
+            subject: "Restablece tu contraseña".to_string(),
+            body: format!(
+                "Hola {},\n\nHemos recibido una solicitud para restablecer tu contraseña. \
+                 Abre este enlace para elegir una nueva; caduca en {} minutos:\n\n{}\n\n\
+                 Si no has sido tú, ignora este correo.",
+                name.unwrap_or_default(),
+                expires_in / 60,
+                link
+            ),
+        };
+
+        // El envío va en segundo plano para que la respuesta tarde lo mismo exista o no la cuenta
+        let mailer = mailer.into_inner();
+        actix_web::rt::spawn(async move {
+            if let Err(e) = mailer.send(email).await {
+                error!("Failed to send password reset email to user {}: {}", user_id, e);
+            }
+        });
+
+        info!("Password reset requested for user {}", user_id);
+    }
+
+    // La respuesta no revela si el email está registrado
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "status": "success",
+        "message": "If the email is registered, a password reset link has been sent"
+    })))
+}
+
+pub async fn verify_reset_token(
+    body: web::Json<ResetTokenCheck>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let valid = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM password_reset_tokens
+            WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        ) as "valid!"
+        "#,
+        secure_token::hash(&body.token)
+    )
+    .fetch_one(&data.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({ "valid": valid })))
+}
+
+pub async fn reset_password(
+    body: web::Json<ResetPassword>,
+    data: web::Data<AppState>,
+    guard: web::Data<LoginGuard>,
+) -> Result<HttpResponse, HttpError> {
+    body.0.validate()?;
+
+    let mut tx = data.pool.begin().await?;
+
+    // Se marca como usado en la misma sentencia para que dos peticiones no lo canjeen a la vez
+    let user_id = sqlx::query_scalar!(
+        r#"
+        UPDATE password_reset_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id
+        "#,
+        secure_token::hash(&body.token)
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| HttpError::bad_request("Invalid or expired reset token"))?;
+
+    let hashed_password = bcrypt::hash(&body.new_password, 12)?;
+
+    let email = sqlx::query_scalar!(
+        "UPDATE users SET password_hash = $1, has_password = true, updated_at = NOW() WHERE id = $2 RETURNING email",
+        hashed_password,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    // Quien tuviera la contraseña anterior pierde el acceso
+    refresh_token::revoke_all(&data.pool, user_id).await?;
+
+    // Restablecer la contraseña es la vía para desbloquear la cuenta sin esperar
+    if guard.unlock(&email) {
+        info!("Account of user {} unlocked by password reset", user_id);
+        if let Err(e) = audit::record(&data.pool, Some(user_id), "login_account_unlocked", None, serde_json::json!({
+            "reason": "password_reset"
+        }))
+        .await
+        {
+            error!("Failed to write audit log: {}", e);
+        }
+    }
+
+    info!("Password reset completed for user {}", user_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Password has been reset"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::DbPool;
+    use crate::test_utils::{self, create_user, load_schema, RecordingMailer};
+
+    #[sqlx::test(migrations = false)]
+    async fn reset_link_ignores_email_case(pool: DbPool) {
+        load_schema(&pool).await;
+        create_user(&pool, "Ana@Example.com").await;
+        let data = test_utils::app_state(&pool);
+
+        // El handler envía el correo con actix_web::rt::spawn, que necesita un LocalSet
+        tokio::task::LocalSet::new()
+            .run_until(async {
+                let (mailer, mailer_data) = RecordingMailer::new();
+                let body = web::Json(ForgotPassword { email: "ana@EXAMPLE.com".to_string() });
+                forgot_password(body, data, mailer_data).await.unwrap();
+
+                let email = mailer.next().await.expect("reset email");
+                assert_eq!(email.to, "Ana@Example.com");
+            })
+            .await;
+    }
+}
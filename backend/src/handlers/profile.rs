@@ -0,0 +1,185 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use log::info;
+use validator::Validate;
+
+use crate::{
+    db::DbPool,
+    handlers::verification,
+    models::auth::{ChangePassword, TokenClaims, UpdateUser, User, UserProfile},
+    services::mailer::Mailer,
+    utils::{error::HttpError, refresh_token},
+    AppState,
+};
+
+// Nuevo email pendiente de confirmar: el del último enlace sin usar ni caducar, si no es el actual
+async fn pending_email(pool: &DbPool, user_id: i32) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT t.email FROM email_verification_tokens t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.user_id = $1 AND t.used_at IS NULL AND t.expires_at > NOW() AND t.email <> u.email
+        ORDER BY t.created_at DESC
+        LIMIT 1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+async fn load_profile(pool: &DbPool, user_id: i32) -> Result<UserProfile, HttpError> {
+    let user = sqlx::query!(
+        r#"
+        SELECT id, email, name, bio, date_of_birth, avatar, created_at, updated_at, email_verified_at
+        FROM users WHERE id = $1
+        "#,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .ok_or_else(|| HttpError::not_found("User not found"))?;
+
+    Ok(UserProfile {
+        id: user.id,
+        email: user.email,
+        name: user.name,
+        bio: user.bio,
+        date_of_birth: user.date_of_birth,
+        avatar: user.avatar,
+        created_at: user.created_at,
+        updated_at: user.updated_at,
+        email_verified: user.email_verified_at.is_some(),
+        pending_email: pending_email(pool, user_id).await?,
+    })
+}
+
+pub async fn get_profile(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let profile = load_profile(&data.pool, user.id).await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+pub async fn update_profile(
+    user: web::ReqData<User>,
+    body: web::Json<UpdateUser>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    body.0.validate()?;
+    let body = body.into_inner();
+
+    if let Some(date_of_birth) = body.date_of_birth {
+        if date_of_birth > Utc::now().date_naive() {
+            return Err(HttpError::validation("Date of birth cannot be in the future"));
+        }
+    }
+
+    let name = body.name.as_deref().map(str::trim);
+    if name.is_some_and(|name| name.chars().count() < 2) {
+        return Err(HttpError::validation("Name must be at least 2 characters long"));
+    }
+
+    // Un email igual al actual no es un cambio. Se guarda tal como se escribe: los emails
+    // se comparan siempre sin distinguir mayúsculas
+    let new_email = body
+        .email
+        .as_deref()
+        .map(|email| email.trim().to_string())
+        .filter(|email| !email.eq_ignore_ascii_case(&user.email));
+
+    if let Some(email) = &new_email {
+        let taken = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1)) as "taken!""#,
+            email
+        )
+        .fetch_one(&data.pool)
+        .await?;
+
+        if taken {
+            return Err(HttpError::conflict("Email already registered"));
+        }
+    }
+
+    let mut tx = data.pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE users SET
+            name = COALESCE($1, name),
+            bio = COALESCE($2, bio),
+            date_of_birth = COALESCE($3, date_of_birth),
+            avatar = COALESCE($4, avatar),
+            updated_at = NOW()
+        WHERE id = $5
+        "#,
+        name,
+        body.bio,
+        body.date_of_birth,
+        body.avatar,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    // El email no cambia hasta que se confirma desde la nueva dirección
+    let token = match &new_email {
+        Some(email) => Some(verification::create_token(&mut tx, &data.config, user.id, email).await?),
+        None => None,
+    };
+
+    tx.commit().await?;
+
+    if let (Some(email), Some(token)) = (new_email, token) {
+        verification::send_link(
+            mailer.into_inner(),
+            &data.config,
+            user.id,
+            user.name.as_deref(),
+            email,
+            &token,
+        );
+        info!("Email change requested for user {}", user.id);
+    }
+
+    let profile = load_profile(&data.pool, user.id).await?;
+    Ok(HttpResponse::Ok().json(profile))
+}
+
+pub async fn change_password(
+    user: web::ReqData<User>,
+    claims: web::ReqData<TokenClaims>,
+    body: web::Json<ChangePassword>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    body.validate().map_err(HttpError::validation)?;
+
+    if !bcrypt::verify(&body.current_password, &user.password_hash)? {
+        return Err(HttpError::bad_request("Current password is incorrect"));
+    }
+
+    let hashed_password = bcrypt::hash(&body.new_password, 12)?;
+
+    sqlx::query!(
+        "UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2",
+        hashed_password,
+        user.id
+    )
+    .execute(&data.pool)
+    .await?;
+
+    // La sesión desde la que se cambia sigue abierta; las demás se cierran
+    match uuid::Uuid::parse_str(&claims.sid) {
+        Ok(session_id) => refresh_token::revoke_others(&data.pool, user.id, session_id).await?,
+        Err(_) => refresh_token::revoke_all(&data.pool, user.id).await?,
+    }
+
+    info!("Password changed for user {}", user.id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Password has been changed"
+    })))
+}
@@ -0,0 +1,300 @@
+use actix_web::{web, HttpResponse};
+use chrono::Utc;
+use log::info;
+use sqlx::PgConnection;
+
+use crate::{
+    db::DbPool,
+    models::auth::{TotpCode, TotpDisable, User},
+    utils::{audit, error::HttpError, secure_token, totp},
+    AppState,
+};
+
+// Códigos de recuperación que se generan cada vez
+const RECOVERY_CODE_COUNT: usize = 10;
+
+struct TotpState {
+    secret: Option<String>,
+    enabled: bool,
+}
+
+async fn totp_state(pool: &DbPool, user_id: i32) -> Result<TotpState, sqlx::Error> {
+    let row = sqlx::query!(
+        "SELECT totp_secret, totp_enabled_at FROM users WHERE id = $1",
+        user_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    Ok(TotpState {
+        secret: row.totp_secret,
+        enabled: row.totp_enabled_at.is_some(),
+    })
+}
+
+/// Indica si la cuenta tiene la verificación en dos pasos activa.
+pub(crate) async fn is_enabled(pool: &DbPool, user_id: i32) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"SELECT totp_enabled_at IS NOT NULL as "enabled!" FROM users WHERE id = $1"#,
+        user_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+// Acepta el código TOTP solo si su paso es posterior al último usado, y lo guarda
+async fn consume_totp(pool: &DbPool, user_id: i32, secret: &str, code: &str) -> Result<bool, sqlx::Error> {
+    let Some(step) = totp::verify(secret, code, Utc::now().timestamp()) else {
+        return Ok(false);
+    };
+
+    let updated = sqlx::query!(
+        r#"
+        UPDATE users SET totp_last_step = $1
+        WHERE id = $2 AND (totp_last_step IS NULL OR totp_last_step < $1)
+        "#,
+        step,
+        user_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(updated > 0)
+}
+
+async fn consume_recovery_code(pool: &DbPool, user_id: i32, code: &str) -> Result<bool, sqlx::Error> {
+    let used = sqlx::query!(
+        r#"
+        UPDATE totp_recovery_codes SET used_at = NOW()
+        WHERE user_id = $1 AND code_hash = $2 AND used_at IS NULL
+        "#,
+        user_id,
+        secure_token::hash(&totp::normalize_recovery_code(code))
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    if used > 0 {
+        info!("Recovery code used by user {}", user_id);
+    }
+    Ok(used > 0)
+}
+
+/// Comprueba el segundo factor de una cuenta con 2FA activa: un código TOTP o, si no
+/// coincide, un código de recuperación. Los dos son de un solo uso.
+pub(crate) async fn verify_second_factor(pool: &DbPool, user_id: i32, code: &str) -> Result<bool, sqlx::Error> {
+    let state = totp_state(pool, user_id).await?;
+    let Some(secret) = state.secret.filter(|_| state.enabled) else {
+        return Ok(false);
+    };
+
+    if consume_totp(pool, user_id, &secret, code).await? {
+        return Ok(true);
+    }
+    consume_recovery_code(pool, user_id, code).await
+}
+
+// Sustituye los códigos de recuperación del usuario y devuelve los nuevos en claro
+async fn replace_recovery_codes(conn: &mut PgConnection, user_id: i32) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query!("DELETE FROM totp_recovery_codes WHERE user_id = $1", user_id)
+        .execute(&mut *conn)
+        .await?;
+
+    let codes = totp::generate_recovery_codes(RECOVERY_CODE_COUNT);
+    let hashes: Vec<String> = codes
+        .iter()
+        .map(|code| secure_token::hash(&totp::normalize_recovery_code(code)))
+        .collect();
+
+    sqlx::query!(
+        "INSERT INTO totp_recovery_codes (user_id, code_hash) SELECT $1, UNNEST($2::text[])",
+        user_id,
+        &hashes
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(codes)
+}
+
+async fn log_event(pool: &DbPool, user_id: i32, event: &str) {
+    if let Err(e) = audit::record(pool, Some(user_id), event, None, serde_json::json!({})).await {
+        log::error!("Failed to write audit log: {}", e);
+    }
+}
+
+pub async fn status(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let enabled = is_enabled(&data.pool, user.id).await?;
+    let recovery_codes_left = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM totp_recovery_codes WHERE user_id = $1 AND used_at IS NULL"#,
+        user.id
+    )
+    .fetch_one(&data.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "enabled": enabled,
+        "recovery_codes_left": if enabled { recovery_codes_left } else { 0 }
+    })))
+}
+
+/// Empieza el alta: genera un secreto que no se activa hasta confirmarlo con un código.
+pub async fn setup(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    if is_enabled(&data.pool, user.id).await? {
+        return Err(HttpError::conflict("Two-factor authentication is already enabled"));
+    }
+
+    let secret = totp::generate_secret();
+
+    sqlx::query!(
+        "UPDATE users SET totp_secret = $1, totp_last_step = NULL WHERE id = $2",
+        secret,
+        user.id
+    )
+    .execute(&data.pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "secret": secret,
+        "provisioning_uri": totp::provisioning_uri(&data.config.totp_issuer, &user.email, &secret)
+    })))
+}
+
+/// Activa la 2FA con el primer código de la app y devuelve los códigos de recuperación.
+/// Es la única vez que se muestran.
+pub async fn confirm(
+    user: web::ReqData<User>,
+    body: web::Json<TotpCode>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let state = totp_state(&data.pool, user.id).await?;
+    if state.enabled {
+        return Err(HttpError::conflict("Two-factor authentication is already enabled"));
+    }
+    let secret = state
+        .secret
+        .ok_or_else(|| HttpError::bad_request("Start the two-factor setup first"))?;
+
+    if !consume_totp(&data.pool, user.id, &secret, &body.code).await? {
+        return Err(HttpError::bad_request("Invalid verification code"));
+    }
+
+    let mut tx = data.pool.begin().await?;
+    sqlx::query!(
+        "UPDATE users SET totp_enabled_at = NOW(), updated_at = NOW() WHERE id = $1",
+        user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+    let recovery_codes = replace_recovery_codes(&mut tx, user.id).await?;
+    tx.commit().await?;
+
+    log_event(&data.pool, user.id, "totp_enabled").await;
+    info!("Two-factor authentication enabled for user {}", user.id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "recovery_codes": recovery_codes
+    })))
+}
+
+/// Genera códigos de recuperación nuevos; los anteriores dejan de valer.
+pub async fn regenerate_recovery_codes(
+    user: web::ReqData<User>,
+    body: web::Json<TotpCode>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    if !is_enabled(&data.pool, user.id).await? {
+        return Err(HttpError::bad_request("Two-factor authentication is not enabled"));
+    }
+
+    let state = totp_state(&data.pool, user.id).await?;
+    let secret = state.secret.unwrap_or_default();
+    if !consume_totp(&data.pool, user.id, &secret, &body.code).await? {
+        return Err(HttpError::bad_request("Invalid verification code"));
+    }
+
+    let mut tx = data.pool.begin().await?;
+    let recovery_codes = replace_recovery_codes(&mut tx, user.id).await?;
+    tx.commit().await?;
+
+    log_event(&data.pool, user.id, "totp_recovery_codes_regenerated").await;
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "recovery_codes": recovery_codes
+    })))
+}
+
+/// Desactiva la 2FA. Pide la contraseña y un código para que no baste con una sesión robada.
+pub async fn disable(
+    user: web::ReqData<User>,
+    body: web::Json<TotpDisable>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    if !is_enabled(&data.pool, user.id).await? {
+        return Err(HttpError::bad_request("Two-factor authentication is not enabled"));
+    }
+
+    if !bcrypt::verify(&body.password, &user.password_hash)? {
+        return Err(HttpError::bad_request("Password is incorrect"));
+    }
+    if !verify_second_factor(&data.pool, user.id, &body.code).await? {
+        return Err(HttpError::bad_request("Invalid verification code"));
+    }
+
+    let mut tx = data.pool.begin().await?;
+    sqlx::query!(
+        r#"
+        UPDATE users SET totp_secret = NULL, totp_enabled_at = NULL, totp_last_step = NULL, updated_at = NOW()
+        WHERE id = $1
+        "#,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!("DELETE FROM totp_recovery_codes WHERE user_id = $1", user.id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    log_event(&data.pool, user.id, "totp_disabled").await;
+    info!("Two-factor authentication disabled for user {}", user.id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Two-factor authentication has been disabled"
+    })))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_user, load_schema};
+
+    #[sqlx::test(migrations = false)]
+    async fn totp_code_cannot_be_reused(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "totp@example.com").await;
+        let secret = totp::generate_secret();
+        let now = Utc::now().timestamp();
+
+        let code = totp::code_for(&secret, now);
+        assert!(consume_totp(&pool, user_id, &secret, &code).await.unwrap());
+        assert!(!consume_totp(&pool, user_id, &secret, &code).await.unwrap());
+
+        // Tampoco vale un código anterior al último usado, aunque siga dentro del margen
+        let previous = totp::code_for(&secret, now - 30);
+        if previous != code {
+            assert!(!consume_totp(&pool, user_id, &secret, &previous).await.unwrap());
+        }
+    }
+}
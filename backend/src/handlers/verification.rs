@@ -0,0 +1,170 @@
+use std::sync::Arc;
+
+use actix_web::{web, HttpResponse};
+use chrono::{Duration, Utc};
+use log::{error, info};
+use sqlx::PgConnection;
+
+use crate::{
+    config::Config,
+    models::auth::{User, VerifyEmail},
+    services::mailer::{Email, Mailer},
+    utils::{error::HttpError, jwt::parse_duration, secure_token},
+    AppState,
+};
+
+/// Crea un enlace de verificación para `email`. Los enlaces anteriores del usuario dejan de valer.
+pub(crate) async fn create_token(
+    conn: &mut PgConnection,
+    config: &Config,
+    user_id: i32,
+    email: &str,
+) -> Result<String, sqlx::Error> {
+    let token = secure_token::generate();
+    let expires_at = Utc::now() + Duration::seconds(parse_duration(&config.email_verification_expires_in));
+
+    sqlx::query!(
+        "UPDATE email_verification_tokens SET used_at = NOW() WHERE user_id = $1 AND used_at IS NULL",
+        user_id
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO email_verification_tokens (user_id, email, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        "#,
+        user_id,
+        email,
+        secure_token::hash(&token),
+        expires_at
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(token)
+}
+
+/// Envía en segundo plano el correo con el enlace de verificación.
+pub(crate) fn send_link(
+    mailer: Arc<dyn Mailer>,
+    config: &Config,
+    user_id: i32,
+    name: Option<&str>,
+    email: String,
+    token: &str,
+) {
+    let link = format!(
+        "{}/verify-email?token={}",
+        config.frontend_url.trim_end_matches('/'),
+        token
+    );
+    let message = Email {
+        to: email,
+        subject: "Confirma tu email".to_string(),
+        body: format!(
+            "Hola {},\n\nAbre este enlace para confirmar tu dirección de email; \
+             caduca en {} horas:\n\n{}\n\n\
+             Si no has sido tú, ignora este correo.",
+            name.unwrap_or_default(),
+            parse_duration(&config.email_verification_expires_in) / 3600,
+            link
+        ),
+    };
+
+    actix_web::rt::spawn(async move {
+        if let Err(e) = mailer.send(message).await {
+            error!("Failed to send email verification to user {}: {}", user_id, e);
+        }
+    });
+}
+
+pub async fn verify_email(
+    body: web::Json<VerifyEmail>,
+    data: web::Data<AppState>,
+) -> Result<HttpResponse, HttpError> {
+    let mut tx = data.pool.begin().await?;
+
+    let verification = sqlx::query!(
+        r#"
+        UPDATE email_verification_tokens SET used_at = NOW()
+        WHERE token_hash = $1 AND used_at IS NULL AND expires_at > NOW()
+        RETURNING user_id, email
+        "#,
+        secure_token::hash(&body.token)
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| HttpError::bad_request("Invalid or expired verification token"))?;
+
+    // Sirve tanto para la cuenta nueva como para un cambio de email.
+    // Si otra cuenta ha ocupado la dirección entretanto, la restricción UNIQUE devuelve 409
+    sqlx::query!(
+        r#"
+        UPDATE users SET email = $1, email_verified_at = NOW(), updated_at = NOW()
+        WHERE id = $2
+        "#,
+        verification.email,
+        verification.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    info!("Email verified for user {}", verification.user_id);
+
+    Ok(HttpResponse::Ok().json(serde_json::json!({
+        "status": "success",
+        "message": "Email has been verified",
+        "email": verification.email
+    })))
+}
+
+pub async fn resend_verification(
+    user: web::ReqData<User>,
+    data: web::Data<AppState>,
+    mailer: web::Data<dyn Mailer>,
+) -> Result<HttpResponse, HttpError> {
+    if user.is_email_verified() {
+        return Err(HttpError::conflict("Email is already verified"));
+    }
+
+    // Un correo como mucho cada EMAIL_VERIFICATION_RESEND_INTERVAL
+    let last_sent = sqlx::query_scalar!(
+        "SELECT MAX(created_at) FROM email_verification_tokens WHERE user_id = $1",
+        user.id
+    )
+    .fetch_one(&data.pool)
+    .await?;
+
+    let interval = Duration::seconds(parse_duration(&data.config.email_verification_resend_interval));
+    if let Some(last_sent) = last_sent {
+        let wait = (last_sent + interval - Utc::now()).num_seconds();
+        if wait > 0 {
+            return Err(HttpError::too_many_requests(format!(
+                "Please wait {} seconds before requesting another verification email",
+                wait
+            )));
+        }
+    }
+
+    let mut conn = data.pool.acquire().await?;
+    let token = create_token(&mut conn, &data.config, user.id, &user.email).await?;
+    send_link(
+        mailer.into_inner(),
+        &data.config,
+        user.id,
+        user.name.as_deref(),
+        user.email.clone(),
+        &token,
+    );
+
+    info!("Verification email resent for user {}", user.id);
+
+    Ok(HttpResponse::Accepted().json(serde_json::json!({
+        "status": "success",
+        "message": "Verification email sent"
+    })))
+}
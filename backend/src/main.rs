@@ -6,13 +6,23 @@ use actix_web_httpauth::middleware::HttpAuthentication;
 use dotenv::dotenv;
 use log::info;
 use sqlx::PgPool;
+use std::sync::Arc;
 use crate::config::Config;
+use crate::services::account_deletion;
+use crate::services::chat::ChatHub;
+use crate::services::login_guard::{InMemoryAttemptStore, LoginGuard, LoginPolicy};
+use crate::services::mailer;
+use crate::services::oidc::OidcClient;
+use crate::services::presence::PresenceTracker;
+use crate::utils::derived_key;
+use crate::utils::jwt::JwtKeys;
 
 // Application state
 #[derive(Debug, Clone)]
 struct AppState {
     pool: PgPool,
     config: Config,
+    jwt_keys: Arc<JwtKeys>,
 }
 
 mod config;
@@ -22,7 +32,9 @@ mod routes;
 mod utils;
 mod middleware;
 mod db;
-// use routes::configure;
+mod services;
+#[cfg(test)]
+mod test_utils;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -40,6 +52,15 @@ async fn main() -> std::io::Result<()> {
         }
     };
     
+    // Claves de firma de los JWT
+    let jwt_keys = match JwtKeys::from_config(&config) {
+        Ok(keys) => Arc::new(keys),
+        Err(e) => {
+            log::error!("Failed to load JWT keys: {}", e);
+            std::process::exit(1);
+        }
+    };
+
     // Create web::Data from the pool
     let pool_data = web::Data::new(pool);
 
@@ -47,19 +68,11 @@ async fn main() -> std::io::Result<()> {
     let host = config.host.clone();
     let port = config.port;
 
-    // Secret key for session middleware
-    let secret_key = {
-        // Generate a fixed-size key for cookie signing
-        let mut key = [0u8; 64]; // Using 64 bytes to be safe
-        let secret = config.jwt_secret.as_bytes();
-        
-        // Copy the secret bytes into the key, repeating if necessary
-        for i in 0..64 {
-            key[i] = secret[i % secret.len()];
-        }
-        
-        actix_web::cookie::Key::derive_from(&key)
-    };
+    // Clave de las cookies de sesión, distinta de la que firma los JWT
+    let secret_key = actix_web::cookie::Key::derive_from(&derived_key::derive(
+        &config.jwt_secret,
+        derived_key::SESSION_COOKIE,
+    ));
 
     log::info!("Starting server at http://{}:{}", host, port);
 
@@ -70,8 +83,37 @@ async fn main() -> std::io::Result<()> {
     let app_data = web::Data::new(AppState {
         pool: pool_data.get_ref().clone(),
         config: config.clone(),
+        jwt_keys,
     });
 
+    // Salas del chat de grupos, compartidas por todos los workers
+    let chat_hub = web::Data::new(ChatHub::default());
+
+    // Presencia de los miembros de los grupos
+    let presence = PresenceTracker::new(pool_data.get_ref().clone(), chat_hub.get_ref().clone());
+    if let Err(e) = presence.reset().await {
+        log::error!("Failed to reset presence: {}", e);
+    }
+    presence.clone().spawn_sweeper();
+    let presence = web::Data::new(presence);
+
+    // Envío de correos (recuperación de contraseña, verificación de email)
+    let mailer = web::Data::from(mailer::from_config(&config));
+
+    // Protección del login contra fuerza bruta; los contadores viven en memoria
+    let login_guard = LoginGuard::new(
+        LoginPolicy::from_config(&config),
+        Arc::new(InMemoryAttemptStore::default()),
+    );
+    login_guard.clone().spawn_sweeper();
+    let login_guard = web::Data::new(login_guard);
+
+    // Inicio de sesión con proveedores OpenID Connect
+    let oidc = web::Data::new(OidcClient::from_config(&config));
+
+    // Borrado de las cuentas cuyo periodo de gracia ha terminado
+    account_deletion::spawn_purger(pool_data.get_ref().clone());
+
     info!("Starting HTTP server at http://{}:{}", config.host, config.port);
     
     HttpServer::new(move || {
@@ -104,7 +146,9 @@ async fn main() -> std::io::Result<()> {
                 .build()
             )
             // Shared application state
-            .app_data(app_data.clone());
+            .app_data(app_data.clone())
+            // Claves públicas para que otros servicios validen los tokens
+            .route("/.well-known/jwks.json", web::get().to(handlers::auth::jwks));
         
         // Configure routes
         
@@ -112,16 +156,48 @@ async fn main() -> std::io::Result<()> {
         app.service(
             web::scope("/api")
                 .app_data(web::Data::new(pool_data.get_ref().clone()))
+                .app_data(chat_hub.clone())
+                .app_data(presence.clone())
+                .app_data(mailer.clone())
+                .app_data(login_guard.clone())
+                .app_data(oidc.clone())
                 // Rutas públicas de autenticación (no requieren token)
                 .service(
                     web::scope("/auth")
                         .route("/login", web::post().to(handlers::auth::login))
                         .route("/register", web::post().to(handlers::auth::register))
+                        .route("/login/2fa", web::post().to(handlers::auth::login_two_factor))
+                        .route("/refresh", web::post().to(handlers::auth::refresh))
+                        .route("/account/restore", web::post().to(handlers::account::restore_account))
+                        .route("/account/restore/request", web::post().to(handlers::account::request_restore))
+                        .route("/account/restore/confirm", web::post().to(handlers::account::confirm_restore))
+                        .route("/account/delete/confirm", web::post().to(handlers::account::confirm_deletion))
+                        .route("/forgot-password", web::post().to(handlers::password::forgot_password))
+                        .route("/reset-password", web::post().to(handlers::password::reset_password))
+                        .route("/reset-password/verify", web::post().to(handlers::password::verify_reset_token))
+                        .route("/verify-email", web::post().to(handlers::verification::verify_email))
+                        .route("/oidc/providers", web::get().to(handlers::oidc::providers))
+                        .route("/oidc/{provider}/authorize", web::get().to(handlers::oidc::authorize))
+                        .route("/oidc/{provider}/callback", web::post().to(handlers::oidc::callback))
                         // Rutas protegidas de autenticación
                         .service(
                             web::scope("")
                                 .wrap(auth.clone())
                                 .route("/logout", web::post().to(handlers::auth::logout))
+                                .route("/logout-all", web::post().to(handlers::auth::logout_all))
+                                .route("/sessions", web::get().to(handlers::auth::get_sessions))
+                                .route("/sessions/{id}", web::delete().to(handlers::auth::revoke_session))
+                                .route("/profile", web::get().to(handlers::profile::get_profile))
+                                .route("/profile", web::put().to(handlers::profile::update_profile))
+                                .route("/change-password", web::post().to(handlers::profile::change_password))
+                                .route("/verify-email/resend", web::post().to(handlers::verification::resend_verification))
+                                .route("/2fa", web::get().to(handlers::two_factor::status))
+                                .route("/2fa/setup", web::post().to(handlers::two_factor::setup))
+                                .route("/2fa/confirm", web::post().to(handlers::two_factor::confirm))
+                                .route("/2fa/recovery-codes", web::post().to(handlers::two_factor::regenerate_recovery_codes))
+                                .route("/2fa/disable", web::post().to(handlers::two_factor::disable))
+                                .route("/account/delete", web::post().to(handlers::account::request_deletion))
+                                .route("/account/export", web::get().to(handlers::account::export_data))
                         )
                 )
                 // Rutas protegidas de posts (todas requieren autenticación)
@@ -136,6 +212,33 @@ async fn main() -> std::io::Result<()> {
                         .wrap(auth.clone())
                         .configure(routes::comments::configure)
                 )
+                // Rutas protegidas de seguimiento del estado de ánimo
+                .service(
+                    web::scope("/mood")
+                        .wrap(auth.clone())
+                        .configure(routes::mood::configure)
+                )
+                // Heartbeat de presencia para clientes sin WebSocket
+                .service(
+                    web::scope("/presence")
+                        .wrap(auth.clone())
+                        .configure(routes::presence::configure)
+                )
+                // Búsqueda de texto completo en posts, comentarios y grupos
+                .service(
+                    web::scope("/search")
+                        .wrap(auth.clone())
+                        .configure(routes::search::configure)
+                )
+                // WebSocket del chat de grupos; el handler valida el token por su cuenta
+                .route("/ws/groups/{id}", web::get().to(routes::messages::websocket))
+                // Rutas protegidas de grupos de apoyo
+                .service(
+                    web::scope("/groups")
+                        .wrap(auth.clone())
+                        .configure(routes::groups::configure)
+                        .configure(routes::messages::configure)
+                )
         )
     })
     .bind((host, port))?
@@ -1,69 +1,94 @@
 use actix_web::{dev::ServiceRequest, Error, web, HttpMessage};
 use actix_web_httpauth::extractors::bearer::BearerAuth;
 use futures_util::future::Future;
-use crate::utils::{jwt::decode_token, error::HttpError};
-use crate::models::auth::User;
+use crate::utils::{jwt::JwtKeys, error::HttpError, revocation};
+use crate::models::auth::{TokenClaims, User};
+use crate::config::Config;
 use crate::db::DbPool;
+use crate::AppState;
 
 pub async fn validator(
     req: ServiceRequest,
     credentials: BearerAuth,
 ) -> Result<ServiceRequest, (Error, ServiceRequest)> {
-    let token = credentials.token();
-    
-    match decode_token(token) {
-        Ok(claims) => {
-            // Extraer el user_id del token
-            let user_id = claims.sub.parse::<i32>().unwrap_or(0);
-            
-            // Obtener el pool de conexiones de la aplicación
-            if let Some(pool) = req.app_data::<web::Data<DbPool>>() {
-                // Buscar el usuario en la base de datos
-                // Usamos query! en lugar de query_as! para tener más control sobre el mapeo de campos
-                match sqlx::query!("SELECT * FROM users WHERE id = $1", user_id)
-                .fetch_optional(pool.get_ref())
-                .await {
-                    Ok(Some(record)) => {
-                        // Crear un objeto User a partir de los resultados de la consulta
-                        let user = User {
-                            id: record.id,
-                            email: record.email.clone(),
-                            password_hash: record.password_hash.clone(),
-                            name: record.name.clone(),
-                            bio: record.bio.clone(),
-                            date_of_birth: record.date_of_birth,
-                            avatar: record.avatar.clone(),
-                            last_login: record.last_login,
-                            created_at: record.created_at,
-                            updated_at: record.updated_at,
-                            is_active: record.is_active.unwrap_or(true)
-                        };
-                        
-                        // Añadir el usuario al contexto de la solicitud como ReqData
-                        req.extensions_mut().insert(user);
-                        Ok(req)
-                    },
-                    Ok(None) => {
-                        log::error!("User not found: {}", user_id);
-                        let error = HttpError::unauthorized("User not found");
-                        Err((error.into(), req))
-                    },
-                    Err(e) => {
-                        log::error!("Database error: {}", e);
-                        let error = HttpError::InternalServerError;
-                        Err((error.into(), req))
-                    }
-                }
-            } else {
-                log::error!("Database pool not found in app_data");
-                let error = HttpError::InternalServerError;
-                Err((error.into(), req))
-            }
+    // Obtener el pool de conexiones y las claves de la aplicación
+    let data = match req.app_data::<web::Data<AppState>>() {
+        Some(data) => data.clone(),
+        None => {
+            log::error!("Application state not found in app_data");
+            let error = HttpError::InternalServerError;
+            return Err((error.into(), req));
+        }
+    };
+
+    match authenticate(&data.pool, &data.jwt_keys, credentials.token()).await {
+        Ok((user, claims)) => {
+            // Añadir el usuario y los claims al contexto de la solicitud como ReqData
+            req.extensions_mut().insert(user);
+            req.extensions_mut().insert(claims);
+            Ok(req)
         },
+        Err(error) => Err((error.into(), req)),
+    }
+}
+
+/// Valida el JWT, comprueba que no se haya revocado y carga el usuario al que pertenece.
+///
+/// La usa el middleware y también las conexiones WebSocket, que no pasan por él
+/// porque los navegadores no permiten enviar la cabecera Authorization al abrirlas.
+pub async fn authenticate(pool: &DbPool, keys: &JwtKeys, token: &str) -> Result<(User, TokenClaims), HttpError> {
+    let claims = keys.decode_token(token).map_err(|e| {
+        log::error!("JWT validation error: {}", e);
+        HttpError::unauthorized("Invalid or expired token")
+    })?;
+
+    match revocation::is_revoked(pool, &claims).await {
+        Ok(false) => {},
+        Ok(true) => return Err(HttpError::unauthorized("Token has been revoked")),
         Err(e) => {
-            log::error!("JWT validation error: {}", e);
-            let error = HttpError::unauthorized("Invalid or expired token");
-            Err((error.into(), req))
+            log::error!("Database error: {}", e);
+            return Err(HttpError::InternalServerError);
+        }
+    }
+
+    // Extraer el user_id del token
+    let user_id = claims.sub.parse::<i32>().unwrap_or(0);
+
+    // Buscar el usuario en la base de datos
+    // Usamos query! en lugar de query_as! para tener más control sobre el mapeo de campos
+    match sqlx::query!("SELECT * FROM users WHERE id = $1", user_id)
+        .fetch_optional(pool)
+        .await
+    {
+        // Cuenta desactivada, por ejemplo pendiente de eliminación
+        Ok(Some(record)) if record.is_active == Some(false) => {
+            Err(HttpError::unauthorized("Account is disabled"))
+        },
+        Ok(Some(record)) => {
+            // Crear un objeto User a partir de los resultados de la consulta
+            let user = User {
+                id: record.id,
+                email: record.email,
+                password_hash: record.password_hash,
+                name: record.name,
+                bio: record.bio,
+                date_of_birth: record.date_of_birth,
+                avatar: record.avatar,
+                last_login: record.last_login,
+                created_at: record.created_at,
+                updated_at: record.updated_at,
+                is_active: record.is_active.unwrap_or(true),
+                email_verified_at: record.email_verified_at,
+            };
+            Ok((user, claims))
+        },
+        Ok(None) => {
+            log::error!("User not found: {}", user_id);
+            Err(HttpError::unauthorized("User not found"))
+        },
+        Err(e) => {
+            log::error!("Database error: {}", e);
+            Err(HttpError::InternalServerError)
         }
     }
 }
@@ -86,4 +111,22 @@ pub fn skip_authentication(path: &str) -> bool {
     ];
     
     public_paths.iter().any(|p| path.starts_with(p))
-}
\ No newline at end of file
+}
+/// Con `REQUIRE_VERIFIED_EMAIL` activo, solo las cuentas con el email verificado pueden publicar.
+pub fn require_verified_email(config: &Config, user: &User) -> Result<(), HttpError> {
+    if config.require_verified_email && !user.is_email_verified() {
+        return Err(HttpError::forbidden("Please verify your email address before posting"));
+    }
+    Ok(())
+}
+
+/// Solo las cuentas de `ADMIN_EMAILS`, y con el email verificado para que nadie pueda
+/// hacerse administrador registrándose con una de esas direcciones.
+pub fn require_admin(config: &Config, user: &User) -> Result<(), HttpError> {
+    let is_admin = user.is_email_verified()
+        && config.admin_emails.iter().any(|email| email.eq_ignore_ascii_case(&user.email));
+    if !is_admin {
+        return Err(HttpError::forbidden("Only administrators can do this"));
+    }
+    Ok(())
+}
@@ -1,3 +1,4 @@
 pub mod auth;
 
-pub use auth::validator_wrapper as validator;
\ No newline at end of file
+pub use auth::validator_wrapper as validator;
+pub use auth::{authenticate, require_admin, require_verified_email};
@@ -16,6 +16,13 @@ pub struct User {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub is_active: bool,
+    pub email_verified_at: Option<DateTime<Utc>>,
+}
+
+impl User {
+    pub fn is_email_verified(&self) -> bool {
+        self.email_verified_at.is_some()
+    }
 }
 
 
@@ -38,11 +45,38 @@ pub struct RegisterUser {
     pub name: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Validate)]
 pub struct UpdateUser {
-    pub username: Option<String>,
+    /// El cambio de email no se aplica hasta confirmar la nueva dirección.
+    #[validate(email)]
     pub email: Option<String>,
+    #[validate(length(min = 2, max = 100))]
+    pub name: Option<String>,
+    #[validate(length(max = 1000))]
+    pub bio: Option<String>,
+    pub date_of_birth: Option<chrono::NaiveDate>,
+    #[validate(length(max = 2048))]
+    pub avatar: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub id: i32,
+    pub email: String,
     pub name: Option<String>,
+    pub bio: Option<String>,
+    pub date_of_birth: Option<chrono::NaiveDate>,
+    pub avatar: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub email_verified: bool,
+    /// Email a la espera de confirmación, si lo hay.
+    pub pending_email: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct VerifyEmail {
+    pub token: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,9 +94,142 @@ impl ChangePassword {
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ForgotPassword {
+    #[validate(email)]
+    pub email: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct ResetPassword {
+    pub token: String,
+    #[validate(length(min = 6))]
+    pub new_password: String,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
+pub struct ResetTokenCheck {
+    pub token: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RefreshTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TokenClaims {
     pub sub: String,
+    /// Audiencia de los access tokens (`JWT_AUDIENCE`).
+    pub aud: String,
+    pub iat: usize,
+    pub exp: usize,
+    /// Identificador único del token, usado para revocarlo.
+    pub jti: String,
+    /// Sesión (familia de refresh tokens) a la que pertenece el token.
+    pub sid: String,
+}
+
+/// Claims del token intermedio que se entrega tras la contraseña cuando la cuenta tiene 2FA.
+///
+/// No sirve como access token: su `typ` es `mfa+jwt` y su `aud` es `mfa`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MfaClaims {
+    pub sub: String,
+    pub aud: String,
     pub iat: usize,
     pub exp: usize,
 }
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LoginTwoFactor {
+    pub mfa_token: String,
+    /// Código de la app de autenticación o uno de los códigos de recuperación.
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpCode {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TotpDisable {
+    pub password: String,
+    pub code: String,
+}
+
+/// Qué pasa con los posts y comentarios de una cuenta eliminada.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountDeletionMode {
+    /// Se borran junto con la cuenta.
+    #[default]
+    Delete,
+    /// Se conservan sin autor para no romper las conversaciones.
+    Anonymize,
+}
+
+impl AccountDeletionMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AccountDeletionMode::Delete => "delete",
+            AccountDeletionMode::Anonymize => "anonymize",
+        }
+    }
+
+    pub fn from_db(value: Option<&str>) -> Self {
+        match value {
+            Some("anonymize") => AccountDeletionMode::Anonymize,
+            _ => AccountDeletionMode::Delete,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeleteAccount {
+    /// Obligatoria salvo en las cuentas sin contraseña propia, que confirman por email.
+    pub password: Option<String>,
+    /// Código de 2FA, obligatorio si la cuenta la tiene activa.
+    pub code: Option<String>,
+    #[serde(default)]
+    pub content: AccountDeletionMode,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RestoreAccount {
+    #[validate(email)]
+    pub email: String,
+    pub password: String,
+    pub code: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Validate)]
+pub struct RestoreAccountRequest {
+    #[validate(email)]
+    pub email: String,
+}
+
+/// Enlace recibido por email para confirmar la eliminación o la restauración de la cuenta.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AccountConfirmation {
+    pub token: String,
+    pub code: Option<String>,
+}
+
+/// Sesión abierta de un usuario, una por cada inicio de sesión.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub id: uuid::Uuid,
+    pub started_at: DateTime<Utc>,
+    pub last_refreshed_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    pub current: bool,
+}
+
+/// Parámetros con los que el proveedor OpenID Connect devuelve al usuario a la aplicación.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OidcCallback {
+    pub code: String,
+    pub state: String,
+}
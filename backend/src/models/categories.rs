@@ -19,3 +19,24 @@ pub struct Tag {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
+
+/// Categoría con el número de posts publicados en ella.
+#[derive(Debug, Serialize)]
+pub struct CategoryWithCount {
+    #[serde(flatten)]
+    pub category: Category,
+    pub posts_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryCreate {
+    pub name: String,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CategoryUpdate {
+    pub name: Option<String>,
+    /// Una cadena vacía borra la descripción.
+    pub description: Option<String>,
+}
@@ -4,6 +4,8 @@ use chrono::{DateTime, Utc};
 
 use std::default::Default;
 
+use crate::models::presence::PresenceStatus;
+
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct Group {
     pub id: i32,
@@ -16,6 +18,7 @@ pub struct Group {
     pub image_url: Option<String>,
     pub color: Option<String>,
     pub members_count: i32,
+    pub visibility: GroupVisibility,
 }
 
 impl Default for Group {
@@ -31,6 +34,7 @@ impl Default for Group {
             image_url: None,
             color: None,
             members_count: 0,
+            visibility: GroupVisibility::Public,
         }
     }
 }
@@ -43,6 +47,8 @@ pub struct GroupCreate {
     pub category: String,
     pub image_url: Option<String>,
     pub color: Option<String>,
+    #[serde(default)]
+    pub visibility: GroupVisibility,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,15 +59,16 @@ pub struct GroupUpdate {
     pub category: Option<String>,
     pub image_url: Option<String>,
     pub color: Option<String>,
+    pub visibility: Option<GroupVisibility>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
 pub struct GroupMember {
     pub group_id: i32,
     pub user_id: i32,
-    pub role: String,
+    pub role: GroupRole,
     pub join_date: DateTime<Utc>,
-    pub status: String,
+    pub status: PresenceStatus,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -73,6 +80,7 @@ pub struct GroupPost {
     pub created_at: DateTime<Utc>,
     pub likes_count: i32,
     pub comments_count: i32,
+    pub is_pinned: bool,
 }
 
 impl Default for GroupPost {
@@ -85,12 +93,15 @@ impl Default for GroupPost {
             created_at: Utc::now(),
             likes_count: 0,
             comments_count: 0,
+            is_pinned: false,
         }
     }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct GroupPostCreate {
+    // El grupo se toma de la ruta; se conserva por compatibilidad con clientes existentes
+    #[serde(default)]
     pub group_id: i32,
     pub content: String,
 }
@@ -110,3 +121,176 @@ pub struct User {
     pub name: Option<String>,
     pub avatar: Option<String>,
 }
+
+/// Rol de un usuario dentro de un grupo, guardado en `group_members.role`.
+///
+/// El orden de las variantes es el rango: un rol solo puede gestionar a los de rango inferior.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupRole {
+    Banned,
+    Member,
+    Moderator,
+    Owner,
+}
+
+/// Acciones sobre un grupo que dependen del rol.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GroupPermission {
+    Post,
+    EditGroup,
+    DeleteGroup,
+    DeleteAnyPost,
+    PinPosts,
+    PromoteMembers,
+    BanUsers,
+    TransferOwnership,
+    ReviewJoinRequests,
+    ManageInvites,
+}
+
+impl GroupRole {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GroupRole::Owner => "owner",
+            GroupRole::Moderator => "moderator",
+            GroupRole::Member => "member",
+            GroupRole::Banned => "banned",
+        }
+    }
+
+    /// Convierte el valor de la base de datos; los valores desconocidos se tratan como `member`.
+    pub fn from_db(role: Option<&str>) -> Self {
+        match role {
+            Some("owner") => GroupRole::Owner,
+            Some("moderator") => GroupRole::Moderator,
+            Some("banned") => GroupRole::Banned,
+            _ => GroupRole::Member,
+        }
+    }
+
+    /// Matriz de permisos de los grupos.
+    pub fn can(self, permission: GroupPermission) -> bool {
+        use GroupPermission::*;
+
+        match self {
+            GroupRole::Owner => true,
+            GroupRole::Moderator => matches!(
+                permission,
+                Post | EditGroup | DeleteAnyPost | PinPosts | BanUsers | ReviewJoinRequests | ManageInvites
+            ),
+            GroupRole::Member => permission == Post,
+            GroupRole::Banned => false,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupMemberRoleUpdate {
+    pub role: GroupRole,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupOwnershipTransfer {
+    pub user_id: i32,
+}
+
+/// Quién puede ver un grupo y cómo se entra en él.
+///
+/// Los grupos públicos admiten a cualquiera, los privados requieren que un moderador
+/// apruebe la solicitud y en los de solo invitación únicamente se entra con un enlace.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GroupVisibility {
+    #[default]
+    Public,
+    Private,
+    InviteOnly,
+}
+
+impl GroupVisibility {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            GroupVisibility::Public => "public",
+            GroupVisibility::Private => "private",
+            GroupVisibility::InviteOnly => "invite_only",
+        }
+    }
+
+    pub fn from_db(visibility: &str) -> Self {
+        match visibility {
+            "private" => GroupVisibility::Private,
+            "invite_only" => GroupVisibility::InviteOnly,
+            _ => GroupVisibility::Public,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JoinRequestStatus {
+    Pending,
+    Approved,
+    Rejected,
+}
+
+impl JoinRequestStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            JoinRequestStatus::Pending => "pending",
+            JoinRequestStatus::Approved => "approved",
+            JoinRequestStatus::Rejected => "rejected",
+        }
+    }
+
+    pub fn from_db(status: &str) -> Self {
+        match status {
+            "approved" => JoinRequestStatus::Approved,
+            "rejected" => JoinRequestStatus::Rejected,
+            _ => JoinRequestStatus::Pending,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupJoinRequest {
+    pub id: i32,
+    pub group_id: i32,
+    pub user_id: i32,
+    pub message: Option<String>,
+    pub status: JoinRequestStatus,
+    pub created_at: DateTime<Utc>,
+    pub reviewed_by: Option<i32>,
+    pub reviewed_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GroupJoinRequestCreate {
+    pub message: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupJoinRequestReview {
+    pub status: JoinRequestStatus,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupInvite {
+    pub id: i32,
+    pub group_id: i32,
+    pub code: String,
+    pub created_by: Option<i32>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub max_uses: Option<i32>,
+    pub uses: i32,
+    pub revoked: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GroupInviteCreate {
+    /// Horas hasta que caduca el enlace; sin valor no caduca.
+    pub expires_in_hours: Option<i64>,
+    /// Número máximo de usos; sin valor es ilimitado.
+    pub max_uses: Option<i32>,
+}
@@ -0,0 +1,42 @@
+use serde::{Deserialize, Serialize};
+use chrono::{DateTime, Utc};
+
+use crate::models::presence::PresenceStatus;
+
+/// Mensaje del chat de un grupo (tabla `messages`).
+///
+/// `is_read` es un único indicador por mensaje: pasa a true cuando cualquier otro
+/// miembro del grupo confirma haberlo leído.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupMessage {
+    pub id: i32,
+    pub group_id: i32,
+    pub user_id: i32,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    pub is_read: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MessageReadReceipt {
+    /// Se marcan como leídos todos los mensajes del grupo hasta este id, incluido.
+    pub up_to: i32,
+}
+
+/// Eventos que envía el cliente por el WebSocket.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatClientEvent {
+    Message { content: String },
+    Read { up_to: i32 },
+}
+
+/// Eventos que el servidor difunde a los miembros conectados a un grupo.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ChatServerEvent {
+    Message { message: GroupMessage },
+    Read { user_id: i32, up_to: i32 },
+    Presence { user_id: i32, status: PresenceStatus },
+    Error { message: String },
+}
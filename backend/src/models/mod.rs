@@ -4,5 +4,7 @@ pub mod comments;
 pub mod groups;
 pub mod mood;
 pub mod categories;
+pub mod messages;
+pub mod presence;
 
 pub use auth::{User, LoginUser, RegisterUser};
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 
 use std::default::Default;
 
@@ -19,7 +19,6 @@ pub struct MoodRecord {
     pub anxiety_level_id: Option<i32>,
     pub record_date: DateTime<Utc>,
     pub notes: Option<String>,
-    pub triggers: Option<String>,
     pub mood_score: i32,
 }
 
@@ -31,7 +30,6 @@ impl Default for MoodRecord {
             anxiety_level_id: None,
             record_date: Utc::now(),
             notes: None,
-            triggers: None,
             mood_score: 0,
         }
     }
@@ -41,7 +39,8 @@ impl Default for MoodRecord {
 pub struct MoodRecordCreate {
     pub anxiety_level_id: Option<i32>,
     pub notes: Option<String>,
-    pub triggers: Option<String>,
+    #[serde(default)]
+    pub triggers: Vec<TriggerRef>,
     pub mood_score: i32,
 }
 
@@ -49,6 +48,30 @@ pub struct MoodRecordCreate {
 pub struct MoodRecordWithRelations {
     pub record: MoodRecord,
     pub anxiety_level: Option<AnxietyLevel>,
+    pub triggers: Vec<Trigger>,
+}
+
+/// Desencadenante de ansiedad. Los del sistema no tienen `user_id`.
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct Trigger {
+    pub id: i32,
+    pub user_id: Option<i32>,
+    pub name: String,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TriggerCreate {
+    pub name: String,
+}
+
+/// Referencia a un desencadenante existente por ID o, si es un texto, por nombre
+/// (se crea como desencadenante del usuario si no existe).
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum TriggerRef {
+    Id(i32),
+    Name(String),
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
@@ -90,3 +113,61 @@ impl Default for UserPreferences {
         }
     }
 }
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct MoodTrendPoint {
+    pub period_start: NaiveDate,
+    pub average_score: f64,
+    pub min_score: i32,
+    pub max_score: i32,
+    pub records_count: i64,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct MoodMovingAverage {
+    pub date: NaiveDate,
+    pub daily_average: f64,
+    pub moving_average_7d: f64,
+    pub moving_average_30d: f64,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct AnxietyLevelCount {
+    pub anxiety_level_id: Option<i32>,
+    pub name: Option<String>,
+    pub color_code: Option<String>,
+    pub records_count: i64,
+}
+
+#[derive(Debug, FromRow, Serialize, Deserialize)]
+pub struct TriggerCount {
+    pub trigger_id: i32,
+    pub name: String,
+    pub is_system: bool,
+    pub records_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoodAnalytics {
+    pub daily: Vec<MoodTrendPoint>,
+    pub weekly: Vec<MoodTrendPoint>,
+    pub monthly: Vec<MoodTrendPoint>,
+    pub moving_averages: Vec<MoodMovingAverage>,
+    pub anxiety_levels: Vec<AnxietyLevelCount>,
+    pub triggers: Vec<TriggerCount>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoodImportRowError {
+    pub line: usize,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MoodImportReport {
+    pub dry_run: bool,
+    pub total_rows: usize,
+    pub valid_rows: usize,
+    pub imported: usize,
+    pub errors: Vec<MoodImportRowError>,
+}
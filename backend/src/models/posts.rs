@@ -17,6 +17,9 @@ pub struct Post {
     pub updated_at: Option<DateTime<Utc>>,
     pub likes_count: i32,
     pub comments_count: i32,
+    #[sqlx(default)]
+    #[serde(default)]
+    pub tags: Vec<String>,
 }
 
 impl Default for Post {
@@ -31,6 +34,7 @@ impl Default for Post {
             updated_at: None,
             likes_count: 0,
             comments_count: 0,
+            tags: Vec::new(),
         }
     }
 }
@@ -41,6 +45,7 @@ pub struct PostCreate {
     pub post_title: String,
     pub content: String,
     pub category: String,
+    #[serde(default)]
     pub tags: Vec<String>,
 }
 
@@ -60,6 +65,8 @@ pub struct PostUpdate {
     pub title: Option<String>,
     pub content: Option<String>,
     pub category: Option<String>,
+    /// Si se envía, sustituye todas las etiquetas del post.
+    pub tags: Option<Vec<String>>,
 }
 
 #[derive(Debug, FromRow, Serialize, Deserialize)]
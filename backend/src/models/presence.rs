@@ -0,0 +1,48 @@
+use serde::{Deserialize, Serialize};
+
+/// Disponibilidad de un usuario, guardada en `group_members.status`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PresenceStatus {
+    Online,
+    Away,
+    #[default]
+    Offline,
+}
+
+impl PresenceStatus {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            PresenceStatus::Online => "online",
+            PresenceStatus::Away => "away",
+            PresenceStatus::Offline => "offline",
+        }
+    }
+
+    pub fn from_db(status: Option<&str>) -> Self {
+        match status {
+            Some("online") => PresenceStatus::Online,
+            Some("away") => PresenceStatus::Away,
+            _ => PresenceStatus::Offline,
+        }
+    }
+}
+
+fn default_active() -> bool {
+    true
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PresenceHeartbeat {
+    /// false cuando la aplicación está en segundo plano: mantiene la sesión pero cuenta como inactividad.
+    #[serde(default = "default_active")]
+    pub active: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct OnlineMember {
+    pub user_id: i32,
+    pub name: Option<String>,
+    pub avatar: Option<String>,
+    pub status: PresenceStatus,
+}
@@ -1,11 +0,0 @@
-use actix_web::web;
-use crate::handlers::auth::{login, logout, register};
-
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/auth")
-            .route("/login", web::post().to(login))
-            .route("/register", web::post().to(register))
-            .route("/logout", web::post().to(logout))
-    );
-}
\ No newline at end of file
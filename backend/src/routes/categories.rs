@@ -0,0 +1,203 @@
+use actix_web::{web, HttpResponse};
+use crate::models::categories::{Category, CategoryCreate, CategoryUpdate, CategoryWithCount};
+use crate::models::User;
+use crate::db::DbPool;
+use crate::middleware::require_admin;
+use crate::utils::error::HttpError;
+use crate::AppState;
+
+use sqlx::PgExecutor;
+
+// Igual que categories.name
+const MAX_NAME_LENGTH: usize = 50;
+
+// Se registran dentro de /api/posts, antes de las rutas "{id}" de los posts
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("categories", web::get().to(get_categories))
+       .route("categories", web::post().to(create_category))
+       .route("categories/{id}", web::put().to(update_category))
+       .route("categories/{id}", web::delete().to(delete_category));
+}
+
+/// Nombre de la categoría tal y como está guardada, sin distinguir mayúsculas.
+pub(crate) async fn resolve_category(
+    executor: impl PgExecutor<'_>,
+    name: &str,
+) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT name FROM categories WHERE LOWER(name) = LOWER($1)",
+        name.trim()
+    )
+    .fetch_optional(executor)
+    .await
+}
+
+fn validate_name(name: &str) -> Result<String, HttpError> {
+    let name = name.trim();
+    let length = name.chars().count();
+    if length == 0 || length > MAX_NAME_LENGTH {
+        return Err(HttpError::validation(format!(
+            "Category name must be between 1 and {} characters",
+            MAX_NAME_LENGTH
+        )));
+    }
+    Ok(name.to_string())
+}
+
+async fn ensure_name_available(pool: &DbPool, name: &str, except_id: Option<i32>) -> Result<(), HttpError> {
+    let taken = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS (
+            SELECT 1 FROM categories WHERE LOWER(name) = LOWER($1) AND id IS DISTINCT FROM $2
+        ) as "exists!"
+        "#,
+        name,
+        except_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if taken {
+        return Err(HttpError::conflict("A category with this name already exists"));
+    }
+    Ok(())
+}
+
+/// Categorías con el número de posts visibles en cada una.
+pub async fn get_categories(pool: web::Data<DbPool>) -> Result<HttpResponse, HttpError> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            c.id, c.name, c.description, c.created_at, c.updated_at,
+            (SELECT COUNT(*)
+             FROM posts p
+             LEFT JOIN users u ON p.user_id = u.id
+             WHERE p.category = c.name AND (p.user_id IS NULL OR u.is_active = true)) as "posts_count!"
+        FROM categories c
+        ORDER BY c.name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let categories: Vec<CategoryWithCount> = rows
+        .into_iter()
+        .map(|row| CategoryWithCount {
+            category: Category {
+                id: row.id,
+                name: row.name,
+                description: row.description,
+                created_at: row.created_at,
+                updated_at: row.updated_at,
+            },
+            posts_count: row.posts_count,
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(categories))
+}
+
+async fn create_category(
+    pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    category: web::Json<CategoryCreate>,
+) -> Result<HttpResponse, HttpError> {
+    require_admin(&data.config, &user)?;
+
+    let name = validate_name(&category.name)?;
+    ensure_name_available(pool.get_ref(), &name, None).await?;
+    let description = category.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
+    let created = sqlx::query_as!(
+        Category,
+        r#"
+        INSERT INTO categories (name, description)
+        VALUES ($1, $2)
+        RETURNING id, name, description, created_at, updated_at
+        "#,
+        name,
+        description
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    log::info!("Category {} created by user {}", created.name, user.id);
+    Ok(HttpResponse::Created().json(created))
+}
+
+/// Al renombrar una categoría, la clave foránea actualiza también sus posts.
+async fn update_category(
+    pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    category: web::Json<CategoryUpdate>,
+) -> Result<HttpResponse, HttpError> {
+    require_admin(&data.config, &user)?;
+
+    let name = category.name.as_deref().map(validate_name).transpose()?;
+    if let Some(name) = &name {
+        ensure_name_available(pool.get_ref(), name, Some(*id)).await?;
+    }
+    let description = category.description.as_deref().map(str::trim);
+
+    let updated = sqlx::query_as!(
+        Category,
+        r#"
+        UPDATE categories
+        SET name = COALESCE($1, name),
+            description = CASE WHEN $2::text IS NULL THEN description ELSE NULLIF($2, '') END,
+            updated_at = NOW()
+        WHERE id = $3
+        RETURNING id, name, description, created_at, updated_at
+        "#,
+        name,
+        description,
+        *id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| HttpError::not_found("Category not found"))?;
+
+    log::info!("Category {} updated by user {}", updated.id, user.id);
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+/// Solo se pueden borrar las categorías sin posts; antes hay que moverlos a otra.
+async fn delete_category(
+    pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    require_admin(&data.config, &user)?;
+
+    let posts_count = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(p.id) as "count!"
+        FROM categories c
+        LEFT JOIN posts p ON p.category = c.name
+        WHERE c.id = $1
+        GROUP BY c.id
+        "#,
+        *id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| HttpError::not_found("Category not found"))?;
+
+    if posts_count > 0 {
+        return Err(HttpError::conflict(format!(
+            "This category has {} posts, move them to another category first",
+            posts_count
+        )));
+    }
+
+    sqlx::query!("DELETE FROM categories WHERE id = $1", *id)
+        .execute(pool.get_ref())
+        .await?;
+
+    log::info!("Category {} deleted by user {}", *id, user.id);
+    Ok(HttpResponse::NoContent().finish())
+}
@@ -1,7 +1,12 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use crate::models::comments::*;
 use crate::models::auth::User;
 use crate::db::DbPool;
+use crate::middleware::require_verified_email;
+use crate::AppState;
+use crate::utils::cursor::{self, Cursor, CursorPage};
+use crate::utils::datetime::naive_to_utc;
+use crate::utils::pagination::Pagination;
 
 use serde::Deserialize;
 
@@ -16,8 +21,10 @@ pub fn configure(cfg: &mut web::ServiceConfig) {
 
 async fn get_comments(
     pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
     user: web::ReqData<User>,
     query: web::Query<GetCommentsQuery>,
+    pagination: web::Query<Pagination>,
 ) -> impl Responder {
     // Validar que se proporciona un post_id
     let post_id = match query.post_id {
@@ -26,6 +33,38 @@ async fn get_comments(
             "error": "Debe proporcionar un post_id"
         }))
     };
+
+    // Los cursores de un hilo no sirven para otro
+    let scope = format!("comments:{}", post_id);
+    let cursor = match query.cursor.as_deref().map(|c| Cursor::decode(c, &data.config.jwt_secret, &scope)) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(e)) => return e.error_response(),
+        None => None,
+    };
+    // Sin `limit` ni `cursor` se devuelve el hilo completo, como antes de paginar
+    let limit = match (pagination.limit, &cursor) {
+        (None, None) => None,
+        _ => Some(pagination.resolve(50, 100).1),
+    };
+
+    let total = match sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM comments c
+        LEFT JOIN users u ON c.user_id = u.id
+        WHERE c.post_id = $1 AND (c.user_id IS NULL OR u.is_active = true)
+        "#,
+        post_id
+    )
+    .fetch_one(pool.get_ref())
+    .await {
+        Ok(total) => total,
+        Err(e) => {
+            log::error!("Error al contar comentarios: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(serde_json::json!({ "error": "Error al obtener comentarios" }));
+        }
+    };
     
     // Consultar comentarios con información del autor y si el usuario actual ha dado like
     match sqlx::query!(
@@ -33,22 +72,46 @@ async fn get_comments(
         SELECT 
             c.id, c.content, c.created_at,
             c.likes_count,
-            u.id as user_id, u.name, u.avatar,
+            u.id as "user_id?", u.name, u.avatar,
             CASE WHEN cl.user_id IS NOT NULL THEN true ELSE false END as is_liked
         FROM comments c
-        JOIN users u ON c.user_id = u.id
+        LEFT JOIN users u ON c.user_id = u.id
         LEFT JOIN comment_likes cl ON c.id = cl.comment_id AND cl.user_id = $1
         WHERE c.post_id = $2
-        ORDER BY c.created_at DESC
+          AND (c.user_id IS NULL OR u.is_active = true)
+          AND ($3::timestamp IS NULL
+               OR (NOT $5::bool AND (c.created_at, c.id) < ($3, $4::int))
+               OR ($5::bool AND (c.created_at, c.id) > ($3, $4::int)))
+        ORDER BY
+            CASE WHEN $5::bool THEN c.created_at END ASC,
+            CASE WHEN $5::bool THEN c.id END ASC,
+            c.created_at DESC, c.id DESC
+        LIMIT $6
         "#,
         user.id,
-        post_id
+        post_id,
+        cursor.map(|c| c.created_at.naive_utc()),
+        cursor.map(|c| c.id),
+        Cursor::is_prev(cursor.as_ref()),
+        limit.map(|limit| limit as i64 + 1)
     )
     .fetch_all(pool.get_ref())
     .await {
         Ok(records) => {
+            let page = match limit {
+                Some(limit) => cursor::paginate(
+                    records,
+                    limit as usize,
+                    cursor.as_ref(),
+                    &data.config.jwt_secret,
+                    &scope,
+                    |record| (naive_to_utc(record.created_at.unwrap_or_default()), record.id, false),
+                ),
+                None => CursorPage { items: records, next_cursor: None, prev_cursor: None },
+            };
+
             // Mapear los resultados al formato que espera el frontend
-            let comments: Vec<serde_json::Value> = records.into_iter().map(|record| {
+            let comments: Vec<serde_json::Value> = page.items.into_iter().map(|record| {
                 // Formatear la fecha correctamente
                 let date_str = record.created_at
                     .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
@@ -70,7 +133,9 @@ async fn get_comments(
 
             HttpResponse::Ok().json(serde_json::json!({
                 "comments": comments,
-                "total": comments.len()
+                "total": total,
+                "next_cursor": page.next_cursor,
+                "prev_cursor": page.prev_cursor
             }))
         },
         Err(e) => {
@@ -83,9 +148,14 @@ async fn get_comments(
 
 async fn create_comment(
     pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
     user: web::ReqData<User>,
     comment: web::Json<CommentCreate>,
 ) -> impl Responder {
+    if let Err(e) = require_verified_email(&data.config, &user) {
+        return e.error_response();
+    }
+
     // Validar que el contenido no esté vacío
     if comment.content.trim().is_empty() {
         return HttpResponse::BadRequest().json(serde_json::json!({
@@ -274,6 +344,5 @@ async fn like_comment(
 #[derive(Deserialize)]
 pub struct GetCommentsQuery {
     pub post_id: Option<i32>,
-    pub page: Option<i32>,
-    pub limit: Option<i32>,
+    pub cursor: Option<String>,
 }
@@ -1,106 +1,1273 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse};
 use crate::models::groups::*;
+use crate::models::presence::{OnlineMember, PresenceStatus};
 use crate::models::auth::User;
 use crate::db::DbPool;
+use crate::middleware::require_verified_email;
+use crate::utils::cursor::{self, Cursor};
+use crate::utils::datetime::{naive_opt_to_utc, naive_to_utc, now_utc};
+use crate::utils::error::HttpError;
+use crate::utils::pagination::{self, Pagination};
+use crate::AppState;
 
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
 use serde::Deserialize;
+use serde_json::json;
+use sqlx::{FromRow, Postgres, Transaction};
+use uuid::Uuid;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api/groups")
-            .service(web::resource("/")
-                .route(web::get().to(get_groups))
-                .route(web::post().to(create_group))
-            )
-            .service(web::resource("/{id}")
-                .route(web::get().to(get_group))
-                .route(web::put().to(update_group))
-                .route(web::delete().to(delete_group))
-            )
-            .service(web::resource("/{id}/join").route(web::post().to(join_group)))
-            .service(web::resource("/{id}/leave").route(web::post().to(leave_group)))
-            .service(web::resource("/{id}/posts").route(web::post().to(create_group_post)))
-    );
+    cfg.service(web::resource("/invites/{code}/accept").route(web::post().to(accept_invite)))
+        .service(web::resource("")
+            .route(web::get().to(get_groups))
+            .route(web::post().to(create_group))
+        )
+        .service(web::resource("/{id}")
+            .route(web::get().to(get_group))
+            .route(web::put().to(update_group))
+            .route(web::delete().to(delete_group))
+        )
+        .service(web::resource("/{id}/join").route(web::post().to(join_group)))
+        .service(web::resource("/{id}/leave").route(web::post().to(leave_group)))
+        .service(web::resource("/{id}/online").route(web::get().to(get_online_members)))
+        .service(web::resource("/{id}/posts")
+            .route(web::get().to(get_group_posts))
+            .route(web::post().to(create_group_post))
+        )
+        .service(web::resource("/{id}/posts/{post_id}").route(web::delete().to(delete_group_post)))
+        .service(web::resource("/{id}/posts/{post_id}/pin").route(web::post().to(pin_group_post)))
+        .service(web::resource("/{id}/members/{user_id}").route(web::put().to(update_member_role)))
+        .service(web::resource("/{id}/transfer").route(web::post().to(transfer_ownership)))
+        .service(web::resource("/{id}/requests").route(web::get().to(get_join_requests)))
+        .service(web::resource("/{id}/requests/{request_id}").route(web::put().to(review_join_request)))
+        .service(web::resource("/{id}/invites")
+            .route(web::get().to(get_invites))
+            .route(web::post().to(create_invite))
+        )
+        .service(web::resource("/{id}/invites/{invite_id}").route(web::delete().to(revoke_invite)));
+}
+
+// Número de publicaciones recientes que se incluyen en el detalle de un grupo
+const GROUP_DETAIL_POSTS: i64 = 20;
+
+#[derive(FromRow)]
+struct GroupRow {
+    id: i32,
+    name: String,
+    description: Option<String>,
+    long_description: Option<String>,
+    category: Option<String>,
+    created_at: Option<NaiveDateTime>,
+    creator_id: Option<i32>,
+    image_url: Option<String>,
+    color: Option<String>,
+    members_count: Option<i32>,
+    visibility: String,
+}
+
+impl From<GroupRow> for Group {
+    fn from(row: GroupRow) -> Self {
+        Group {
+            id: row.id,
+            name: row.name,
+            description: row.description,
+            long_description: row.long_description,
+            category: row.category.unwrap_or_default(),
+            created_at: naive_opt_to_utc(row.created_at).unwrap_or_else(now_utc),
+            creator_id: row.creator_id.unwrap_or_default(),
+            image_url: row.image_url,
+            color: row.color,
+            members_count: row.members_count.unwrap_or_default(),
+            visibility: GroupVisibility::from_db(&row.visibility),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct GroupMemberRow {
+    group_id: i32,
+    user_id: i32,
+    role: Option<String>,
+    join_date: Option<NaiveDateTime>,
+    status: Option<String>,
+}
+
+impl From<GroupMemberRow> for GroupMember {
+    fn from(row: GroupMemberRow) -> Self {
+        GroupMember {
+            group_id: row.group_id,
+            user_id: row.user_id,
+            role: GroupRole::from_db(row.role.as_deref()),
+            join_date: naive_opt_to_utc(row.join_date).unwrap_or_else(now_utc),
+            status: PresenceStatus::from_db(row.status.as_deref()),
+        }
+    }
+}
+
+#[derive(FromRow)]
+struct GroupPostRow {
+    id: i32,
+    group_id: Option<i32>,
+    user_id: Option<i32>,
+    content: String,
+    created_at: Option<NaiveDateTime>,
+    likes_count: Option<i32>,
+    comments_count: Option<i32>,
+    is_pinned: bool,
+}
+
+impl From<GroupPostRow> for GroupPost {
+    fn from(row: GroupPostRow) -> Self {
+        GroupPost {
+            id: row.id,
+            group_id: row.group_id.unwrap_or_default(),
+            user_id: row.user_id.unwrap_or_default(),
+            content: row.content,
+            created_at: naive_opt_to_utc(row.created_at).unwrap_or_else(now_utc),
+            likes_count: row.likes_count.unwrap_or_default(),
+            comments_count: row.comments_count.unwrap_or_default(),
+            is_pinned: row.is_pinned,
+        }
+    }
+}
+
+async fn fetch_group_row(pool: &DbPool, id: i32) -> Result<Option<GroupRow>, sqlx::Error> {
+    sqlx::query_as!(
+        GroupRow,
+        r#"
+        SELECT id, name, description, long_description, category, created_at,
+               creator_id, image_url, color, members_count, visibility
+        FROM groups
+        WHERE id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await
+}
+
+// Grupo con su creador, sus miembros y las publicaciones más recientes
+async fn fetch_group_with_relations(
+    pool: &DbPool,
+    id: i32,
+) -> Result<Option<GroupWithRelations>, sqlx::Error> {
+    let group: Group = match fetch_group_row(pool, id).await? {
+        Some(row) => row.into(),
+        None => return Ok(None),
+    };
+
+    // El creador puede haberse borrado (ON DELETE SET NULL)
+    let creator = sqlx::query!(
+        "SELECT id, name, avatar FROM users WHERE id = $1",
+        group.creator_id
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|record| crate::models::groups::User {
+        id: record.id,
+        // La tabla users no tiene nombre de usuario; no se expone el email
+        username: record.name.clone().unwrap_or_default(),
+        name: record.name,
+        avatar: record.avatar,
+    })
+    .unwrap_or_else(|| crate::models::groups::User {
+        id: 0,
+        username: String::new(),
+        name: None,
+        avatar: None,
+    });
+
+    let members = sqlx::query_as!(
+        GroupMemberRow,
+        r#"
+        SELECT group_id as "group_id!", user_id as "user_id!", role, join_date, status
+        FROM group_members
+        WHERE group_id = $1 AND role <> 'banned'
+        ORDER BY join_date
+        "#,
+        id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(GroupMember::from)
+    .collect();
+
+    let posts = sqlx::query_as!(
+        GroupPostRow,
+        r#"
+        SELECT id, group_id, user_id, content, created_at, likes_count, comments_count, is_pinned
+        FROM group_posts
+        WHERE group_id = $1
+        ORDER BY is_pinned DESC, created_at DESC, id DESC
+        LIMIT $2
+        "#,
+        id,
+        GROUP_DETAIL_POSTS
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(GroupPost::from)
+    .collect();
+
+    Ok(Some(GroupWithRelations {
+        group,
+        creator,
+        members,
+        posts,
+    }))
+}
+
+pub(crate) async fn fetch_member_role(
+    pool: &DbPool,
+    group_id: i32,
+    user_id: i32,
+) -> Result<Option<GroupRole>, sqlx::Error> {
+    let role = sqlx::query_scalar!(
+        "SELECT role FROM group_members WHERE group_id = $1 AND user_id = $2",
+        group_id,
+        user_id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(role.map(|role| GroupRole::from_db(role.as_deref())))
+}
+
+async fn ensure_group_exists(pool: &DbPool, id: i32) -> Result<(), HttpError> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM groups WHERE id = $1) as "exists!""#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !exists {
+        return Err(HttpError::not_found("Group not found"));
+    }
+    Ok(())
+}
+
+async fn fetch_group_visibility(pool: &DbPool, id: i32) -> Result<GroupVisibility, HttpError> {
+    let visibility = sqlx::query_scalar!("SELECT visibility FROM groups WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Group not found"))?;
+
+    Ok(GroupVisibility::from_db(&visibility))
+}
+
+// Solo los grupos públicos muestran miembros y publicaciones a quien no pertenece a ellos
+fn can_view_content(visibility: GroupVisibility, role: Option<GroupRole>) -> bool {
+    match role {
+        Some(GroupRole::Banned) => false,
+        Some(_) => true,
+        None => visibility == GroupVisibility::Public,
+    }
+}
+
+/// Comprueba que el grupo exista y que el rol del usuario en él tenga el permiso.
+/// Devuelve el rol para las comprobaciones de rango que dependen del destinatario.
+async fn require_permission(
+    pool: &DbPool,
+    group_id: i32,
+    user_id: i32,
+    permission: GroupPermission,
+) -> Result<GroupRole, HttpError> {
+    ensure_group_exists(pool, group_id).await?;
+
+    match fetch_member_role(pool, group_id, user_id).await? {
+        Some(role) if role.can(permission) => Ok(role),
+        Some(GroupRole::Banned) => Err(HttpError::forbidden("You are banned from this group")),
+        Some(_) => Err(HttpError::forbidden("Your role in this group does not allow this action")),
+        None => Err(HttpError::forbidden("Only group members can do this")),
+    }
+}
+
+// Recalcula members_count a partir de group_members en la misma transacción.
+// Los usuarios expulsados conservan su fila para no poder volver a unirse, pero no cuentan.
+pub(crate) async fn sync_members_count(
+    tx: &mut Transaction<'_, Postgres>,
+    group_id: i32,
+) -> Result<i32, sqlx::Error> {
+    let count = sqlx::query_scalar!(
+        r#"
+        UPDATE groups
+        SET members_count = (
+            SELECT COUNT(*) FROM group_members WHERE group_id = $1 AND role <> 'banned'
+        )
+        WHERE id = $1
+        RETURNING members_count as "members_count!"
+        "#,
+        group_id
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok(count)
+}
+
+// Añade al usuario como miembro. Devuelve false si ya tenía fila en el grupo.
+async fn add_member(
+    tx: &mut Transaction<'_, Postgres>,
+    group_id: i32,
+    user_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let inserted = sqlx::query!(
+        r#"
+        INSERT INTO group_members (group_id, user_id, role)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (group_id, user_id) DO NOTHING
+        "#,
+        group_id,
+        user_id,
+        GroupRole::Member.as_str()
+    )
+    .execute(&mut **tx)
+    .await?
+    .rows_affected();
+
+    if inserted == 0 {
+        return Ok(false);
+    }
+
+    sync_members_count(tx, group_id).await?;
+    Ok(true)
+}
+
+fn validate_group_fields(name: Option<&str>, category: Option<&str>) -> Result<(), HttpError> {
+    if let Some(name) = name {
+        let length = name.trim().chars().count();
+        if length == 0 || length > 100 {
+            return Err(HttpError::validation("Group name must be between 1 and 100 characters"));
+        }
+    }
+
+    if let Some(category) = category {
+        let length = category.trim().chars().count();
+        if length == 0 || length > 50 {
+            return Err(HttpError::validation("Group category must be between 1 and 50 characters"));
+        }
+    }
+
+    Ok(())
 }
 
 async fn get_groups(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _query: web::Query<GetGroupsQuery>,
-) -> impl Responder {
-    // Implementar la lógica para obtener grupos
-    HttpResponse::Ok().json(Vec::<Group>::new())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    query: web::Query<GetGroupsQuery>,
+    pagination: web::Query<Pagination>,
+) -> Result<HttpResponse, HttpError> {
+    let (page, limit) = pagination.resolve(20, 100);
+    let offset = pagination::offset(page, limit);
+    let category = query.category.as_deref().map(str::trim).filter(|c| !c.is_empty());
+
+    // Los grupos de solo invitación no aparecen en el listado para quien no es miembro
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM groups g
+        LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $1
+        WHERE ($2::text IS NULL OR LOWER(g.category) = LOWER($2))
+          AND (g.visibility <> 'invite_only' OR gm.role IN ('owner', 'moderator', 'member'))
+        "#,
+        user.id,
+        category
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let rows = sqlx::query!(
+        r#"
+        SELECT
+            g.id, g.name, g.description, g.long_description, g.category, g.created_at,
+            g.creator_id, g.image_url, g.color, g.members_count, g.visibility,
+            gm.role as "member_role?"
+        FROM groups g
+        LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $1
+        WHERE ($2::text IS NULL OR LOWER(g.category) = LOWER($2))
+          AND (g.visibility <> 'invite_only' OR gm.role IN ('owner', 'moderator', 'member'))
+        ORDER BY g.members_count DESC NULLS LAST, g.created_at DESC, g.id DESC
+        LIMIT $3 OFFSET $4
+        "#,
+        user.id,
+        category,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let groups: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            let role = row.member_role.as_deref().map(|role| GroupRole::from_db(Some(role)));
+            let is_member = matches!(role, Some(role) if role != GroupRole::Banned);
+            let group = Group::from(GroupRow {
+                id: row.id,
+                name: row.name,
+                description: row.description,
+                long_description: row.long_description,
+                category: row.category,
+                created_at: row.created_at,
+                creator_id: row.creator_id,
+                image_url: row.image_url,
+                color: row.color,
+                members_count: row.members_count,
+                visibility: row.visibility,
+            });
+            json!({
+                "group": group,
+                "is_member": is_member,
+                "role": role,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "groups": groups,
+        "total": total,
+        "page": page,
+        "per_page": limit
+    })))
 }
 
 async fn create_group(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _group: web::Json<GroupCreate>,
-) -> impl Responder {
-    // Implementar la lógica para crear un grupo
-    HttpResponse::Created().json(Group::default())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    group: web::Json<GroupCreate>,
+) -> Result<HttpResponse, HttpError> {
+    validate_group_fields(Some(&group.name), Some(&group.category))?;
+
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO groups (name, description, long_description, category, creator_id, image_url, color, visibility)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+        group.name.trim(),
+        group.description,
+        group.long_description,
+        group.category.trim(),
+        user.id,
+        group.image_url,
+        group.color,
+        group.visibility.as_str()
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    // El creador entra como propietario
+    sqlx::query!(
+        "INSERT INTO group_members (group_id, user_id, role) VALUES ($1, $2, $3)",
+        id,
+        user.id,
+        GroupRole::Owner.as_str()
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sync_members_count(&mut tx, id).await?;
+    tx.commit().await?;
+
+    let created = fetch_group_with_relations(pool.get_ref(), id)
+        .await?
+        .ok_or(HttpError::InternalServerError)?;
+
+    Ok(HttpResponse::Created().json(created))
 }
 
 async fn get_group(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-) -> impl Responder {
-    // Implementar la lógica para obtener un grupo por ID
-    HttpResponse::Ok().json(Group::default())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    let mut group = fetch_group_with_relations(pool.get_ref(), *id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Group not found"))?;
+
+    let role = fetch_member_role(pool.get_ref(), *id, user.id).await?;
+    if !can_view_content(group.group.visibility, role) {
+        // Un grupo de solo invitación no existe para quien no pertenece a él
+        if group.group.visibility == GroupVisibility::InviteOnly && role.is_none() {
+            return Err(HttpError::not_found("Group not found"));
+        }
+        group.members.clear();
+        group.posts.clear();
+    }
+
+    Ok(HttpResponse::Ok().json(group))
 }
 
 async fn update_group(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-    _group: web::Json<GroupUpdate>,
-) -> impl Responder {
-    // Implementar la lógica para actualizar un grupo
-    HttpResponse::Ok().json(Group::default())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    group: web::Json<GroupUpdate>,
+) -> Result<HttpResponse, HttpError> {
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::EditGroup).await?;
+    validate_group_fields(group.name.as_deref(), group.category.as_deref())?;
+
+    // Los campos ausentes conservan su valor actual
+    sqlx::query!(
+        r#"
+        UPDATE groups
+        SET name = COALESCE($1, name),
+            description = COALESCE($2, description),
+            long_description = COALESCE($3, long_description),
+            category = COALESCE($4, category),
+            image_url = COALESCE($5, image_url),
+            color = COALESCE($6, color),
+            visibility = COALESCE($7, visibility)
+        WHERE id = $8
+        "#,
+        group.name.as_deref().map(str::trim),
+        group.description,
+        group.long_description,
+        group.category.as_deref().map(str::trim),
+        group.image_url,
+        group.color,
+        group.visibility.map(GroupVisibility::as_str),
+        *id
+    )
+    .execute(pool.get_ref())
+    .await?;
+
+    let updated = fetch_group_with_relations(pool.get_ref(), *id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Group not found"))?;
+
+    Ok(HttpResponse::Ok().json(updated))
 }
 
 async fn delete_group(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-) -> impl Responder {
-    // Implementar la lógica para eliminar un grupo
-    HttpResponse::NoContent().finish()
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::DeleteGroup).await?;
+
+    sqlx::query!("DELETE FROM groups WHERE id = $1", *id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
 }
 
 async fn join_group(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-) -> impl Responder {
-    // Implementar la lógica para unirse a un grupo
-    HttpResponse::NoContent().finish()
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    request: Option<web::Json<GroupJoinRequestCreate>>,
+) -> Result<HttpResponse, HttpError> {
+    let visibility = fetch_group_visibility(pool.get_ref(), *id).await?;
+
+    match fetch_member_role(pool.get_ref(), *id, user.id).await? {
+        Some(GroupRole::Banned) => return Err(HttpError::forbidden("You are banned from this group")),
+        Some(_) => return Err(HttpError::conflict("Already a member of this group")),
+        None => {}
+    }
+
+    match visibility {
+        GroupVisibility::Public => {
+            let mut tx = pool.begin().await?;
+            if !add_member(&mut tx, *id, user.id).await? {
+                return Err(HttpError::conflict("Already a member of this group"));
+            }
+            tx.commit().await?;
+
+            Ok(HttpResponse::NoContent().finish())
+        }
+        GroupVisibility::Private => {
+            let message = request
+                .and_then(|request| request.into_inner().message)
+                .map(|message| message.trim().to_string())
+                .filter(|message| !message.is_empty());
+
+            // El índice único parcial impide dos solicitudes pendientes; se traduce en 409
+            let created = sqlx::query_as!(
+                GroupJoinRequestRow,
+                r#"
+                INSERT INTO group_join_requests (group_id, user_id, message)
+                VALUES ($1, $2, $3)
+                RETURNING id, group_id, user_id, message, status, created_at, reviewed_by, reviewed_at
+                "#,
+                *id,
+                user.id,
+                message
+            )
+            .fetch_one(pool.get_ref())
+            .await
+            .map_err(|e| match HttpError::from(e) {
+                HttpError::Conflict(_) => HttpError::conflict("You already have a pending request for this group"),
+                e => e,
+            })?;
+
+            Ok(HttpResponse::Accepted().json(GroupJoinRequest::from(created)))
+        }
+        GroupVisibility::InviteOnly => {
+            Err(HttpError::forbidden("This group can only be joined with an invite link"))
+        }
+    }
 }
 
 async fn leave_group(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-) -> impl Responder {
-    // Implementar la lógica para abandonar un grupo
-    HttpResponse::NoContent().finish()
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    ensure_group_exists(pool.get_ref(), *id).await?;
+
+    match fetch_member_role(pool.get_ref(), *id, user.id).await? {
+        Some(GroupRole::Owner) => {
+            return Err(HttpError::conflict("Transfer ownership before leaving the group"));
+        }
+        // Salir borraría la expulsión y permitiría volver a unirse
+        Some(GroupRole::Banned) => return Err(HttpError::forbidden("You are banned from this group")),
+        Some(_) => {}
+        None => return Err(HttpError::not_found("Not a member of this group")),
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "DELETE FROM group_members WHERE group_id = $1 AND user_id = $2",
+        *id,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sync_members_count(&mut tx, *id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Miembros disponibles ahora mismo: primero los conectados y después los ausentes
+async fn get_online_members(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    let visibility = fetch_group_visibility(pool.get_ref(), *id).await?;
+    let role = fetch_member_role(pool.get_ref(), *id, user.id).await?;
+    if !can_view_content(visibility, role) {
+        return Err(HttpError::forbidden("Only group members can see who is online in this group"));
+    }
+
+    let members: Vec<OnlineMember> = sqlx::query!(
+        r#"
+        SELECT gm.user_id as "user_id!", u.name, u.avatar, gm.status
+        FROM group_members gm
+        JOIN users u ON u.id = gm.user_id
+        WHERE gm.group_id = $1 AND gm.role <> 'banned' AND gm.status IN ('online', 'away')
+        ORDER BY gm.status = 'online' DESC, u.name
+        "#,
+        *id
+    )
+    .fetch_all(pool.get_ref())
+    .await?
+    .into_iter()
+    .map(|row| OnlineMember {
+        user_id: row.user_id,
+        name: row.name,
+        avatar: row.avatar,
+        status: PresenceStatus::from_db(row.status.as_deref()),
+    })
+    .collect();
+
+    Ok(HttpResponse::Ok().json(members))
+}
+
+async fn get_group_posts(
+    pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    query: web::Query<GetGroupPostsQuery>,
+    pagination: web::Query<Pagination>,
+) -> Result<HttpResponse, HttpError> {
+    let visibility = fetch_group_visibility(pool.get_ref(), *id).await?;
+    let role = fetch_member_role(pool.get_ref(), *id, user.id).await?;
+    if !can_view_content(visibility, role) {
+        return Err(HttpError::forbidden("Only group members can see the posts of this group"));
+    }
+
+    let (page, limit) = pagination.resolve(20, 100);
+    let scope = format!("group_posts:{}", *id);
+    let cursor = query
+        .cursor
+        .as_deref()
+        .map(|c| Cursor::decode(c, &data.config.jwt_secret, &scope))
+        .transpose()?;
+    let offset = if cursor.is_some() { 0 } else { pagination::offset(page, limit) };
+
+    let total = sqlx::query_scalar!(
+        r#"SELECT COUNT(*) as "count!" FROM group_posts WHERE group_id = $1"#,
+        *id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    // Las fijadas van primero, así que también forman parte de la posición
+    let rows = sqlx::query_as!(
+        GroupPostRow,
+        r#"
+        SELECT id, group_id, user_id, content, created_at, likes_count, comments_count, is_pinned
+        FROM group_posts
+        WHERE group_id = $1
+          AND ($2::timestamp IS NULL
+               OR (NOT $5::bool AND (is_pinned, created_at, id) < ($4::bool, $2, $3::int))
+               OR ($5::bool AND (is_pinned, created_at, id) > ($4::bool, $2, $3::int)))
+        ORDER BY
+            CASE WHEN $5::bool THEN is_pinned END ASC,
+            CASE WHEN $5::bool THEN created_at END ASC,
+            CASE WHEN $5::bool THEN id END ASC,
+            is_pinned DESC, created_at DESC, id DESC
+        LIMIT $6 OFFSET $7
+        "#,
+        *id,
+        cursor.map(|c| c.created_at.naive_utc()),
+        cursor.map(|c| c.id),
+        cursor.map(|c| c.pinned),
+        Cursor::is_prev(cursor.as_ref()),
+        limit as i64 + 1,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let page_of = cursor::paginate(
+        rows,
+        limit as usize,
+        cursor.as_ref(),
+        &data.config.jwt_secret,
+        &scope,
+        |row| (naive_to_utc(row.created_at.unwrap_or_default()), row.id, row.is_pinned),
+    );
+    let posts: Vec<GroupPost> = page_of.items.into_iter().map(GroupPost::from).collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "posts": posts,
+        "total": total,
+        "page": if cursor.is_some() { None } else { Some(page) },
+        "per_page": limit,
+        "next_cursor": page_of.next_cursor,
+        "prev_cursor": page_of.prev_cursor
+    })))
 }
 
 async fn create_group_post(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-    _post: web::Json<GroupPostCreate>,
-) -> impl Responder {
-    // Implementar la lógica para crear un post en un grupo
-    HttpResponse::Created().json(GroupPost::default())
+    pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    post: web::Json<GroupPostCreate>,
+) -> Result<HttpResponse, HttpError> {
+    require_verified_email(&data.config, &user)?;
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::Post).await?;
+
+    if post.content.trim().is_empty() {
+        return Err(HttpError::validation("Post content cannot be empty"));
+    }
+
+    let created: GroupPost = sqlx::query_as!(
+        GroupPostRow,
+        r#"
+        INSERT INTO group_posts (group_id, user_id, content)
+        VALUES ($1, $2, $3)
+        RETURNING id, group_id, user_id, content, created_at, likes_count, comments_count, is_pinned
+        "#,
+        *id,
+        user.id,
+        post.content
+    )
+    .fetch_one(pool.get_ref())
+    .await?
+    .into();
+
+    Ok(HttpResponse::Created().json(created))
+}
+
+async fn delete_group_post(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, HttpError> {
+    let (group_id, post_id) = path.into_inner();
+    ensure_group_exists(pool.get_ref(), group_id).await?;
+
+    let author_id = sqlx::query_scalar!(
+        "SELECT user_id FROM group_posts WHERE id = $1 AND group_id = $2",
+        post_id,
+        group_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| HttpError::not_found("Post not found"))?;
+
+    // Cada autor puede borrar sus publicaciones mientras siga siendo miembro (los expulsados
+    // o que ya han salido no); las ajenas requieren moderación
+    let permission = if author_id == Some(user.id) {
+        GroupPermission::Post
+    } else {
+        GroupPermission::DeleteAnyPost
+    };
+    require_permission(pool.get_ref(), group_id, user.id, permission).await?;
+
+    sqlx::query!("DELETE FROM group_posts WHERE id = $1", post_id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn pin_group_post(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, HttpError> {
+    let (group_id, post_id) = path.into_inner();
+    require_permission(pool.get_ref(), group_id, user.id, GroupPermission::PinPosts).await?;
+
+    // Alterna el estado, igual que los likes de los posts
+    let pinned = sqlx::query_scalar!(
+        r#"
+        UPDATE group_posts SET is_pinned = NOT is_pinned
+        WHERE id = $1 AND group_id = $2
+        RETURNING is_pinned
+        "#,
+        post_id,
+        group_id
+    )
+    .fetch_optional(pool.get_ref())
+    .await?
+    .ok_or_else(|| HttpError::not_found("Post not found"))?;
+
+    Ok(HttpResponse::Ok().json(json!({ "pinned": pinned })))
+}
+
+async fn update_member_role(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    path: web::Path<(i32, i32)>,
+    update: web::Json<GroupMemberRoleUpdate>,
+) -> Result<HttpResponse, HttpError> {
+    let (group_id, member_id) = path.into_inner();
+    let new_role = update.role;
+
+    if new_role == GroupRole::Owner {
+        return Err(HttpError::bad_request("Use the transfer endpoint to change the group owner"));
+    }
+
+    if member_id == user.id {
+        return Err(HttpError::bad_request("You cannot change your own role"));
+    }
+
+    ensure_group_exists(pool.get_ref(), group_id).await?;
+
+    let current_role = fetch_member_role(pool.get_ref(), group_id, member_id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("User is not a member of this group"))?;
+
+    // Expulsar o readmitir requiere BanUsers; nombrar o retirar moderadores, PromoteMembers
+    let permission = if new_role == GroupRole::Banned || current_role == GroupRole::Banned {
+        GroupPermission::BanUsers
+    } else {
+        GroupPermission::PromoteMembers
+    };
+    let actor_role = require_permission(pool.get_ref(), group_id, user.id, permission).await?;
+
+    if current_role >= actor_role || new_role >= actor_role {
+        return Err(HttpError::forbidden("You can only manage members with a lower role than yours"));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE group_members SET role = $1 WHERE group_id = $2 AND user_id = $3",
+        new_role.as_str(),
+        group_id,
+        member_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let members_count = sync_members_count(&mut tx, group_id).await?;
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(json!({
+        "group_id": group_id,
+        "user_id": member_id,
+        "role": new_role,
+        "members_count": members_count
+    })))
+}
+
+async fn transfer_ownership(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    transfer: web::Json<GroupOwnershipTransfer>,
+) -> Result<HttpResponse, HttpError> {
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::TransferOwnership).await?;
+
+    if transfer.user_id == user.id {
+        return Err(HttpError::bad_request("You already own this group"));
+    }
+
+    match fetch_member_role(pool.get_ref(), *id, transfer.user_id).await? {
+        Some(GroupRole::Banned) => {
+            return Err(HttpError::conflict("Ownership cannot be transferred to a banned user"));
+        }
+        Some(_) => {}
+        None => return Err(HttpError::not_found("User is not a member of this group")),
+    }
+
+    // El propietario anterior queda como moderador
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        "UPDATE group_members SET role = $1 WHERE group_id = $2 AND user_id = $3",
+        GroupRole::Moderator.as_str(),
+        *id,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query!(
+        "UPDATE group_members SET role = $1 WHERE group_id = $2 AND user_id = $3",
+        GroupRole::Owner.as_str(),
+        *id,
+        transfer.user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let group = fetch_group_with_relations(pool.get_ref(), *id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Group not found"))?;
+
+    Ok(HttpResponse::Ok().json(group))
+}
+
+#[derive(FromRow)]
+struct GroupJoinRequestRow {
+    id: i32,
+    group_id: i32,
+    user_id: i32,
+    message: Option<String>,
+    status: String,
+    created_at: DateTime<Utc>,
+    reviewed_by: Option<i32>,
+    reviewed_at: Option<DateTime<Utc>>,
+}
+
+impl From<GroupJoinRequestRow> for GroupJoinRequest {
+    fn from(row: GroupJoinRequestRow) -> Self {
+        GroupJoinRequest {
+            id: row.id,
+            group_id: row.group_id,
+            user_id: row.user_id,
+            message: row.message,
+            status: JoinRequestStatus::from_db(&row.status),
+            created_at: row.created_at,
+            reviewed_by: row.reviewed_by,
+            reviewed_at: row.reviewed_at,
+        }
+    }
+}
+
+async fn get_join_requests(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::ReviewJoinRequests).await?;
+
+    let requests: Vec<GroupJoinRequest> = sqlx::query_as!(
+        GroupJoinRequestRow,
+        r#"
+        SELECT id, group_id, user_id, message, status, created_at, reviewed_by, reviewed_at
+        FROM group_join_requests
+        WHERE group_id = $1 AND status = 'pending'
+        ORDER BY created_at, id
+        "#,
+        *id
+    )
+    .fetch_all(pool.get_ref())
+    .await?
+    .into_iter()
+    .map(GroupJoinRequest::from)
+    .collect();
+
+    Ok(HttpResponse::Ok().json(requests))
+}
+
+async fn review_join_request(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    path: web::Path<(i32, i32)>,
+    review: web::Json<GroupJoinRequestReview>,
+) -> Result<HttpResponse, HttpError> {
+    let (group_id, request_id) = path.into_inner();
+
+    if review.status == JoinRequestStatus::Pending {
+        return Err(HttpError::bad_request("A request can only be approved or rejected"));
+    }
+
+    require_permission(pool.get_ref(), group_id, user.id, GroupPermission::ReviewJoinRequests).await?;
+
+    let mut tx = pool.begin().await?;
+
+    // Solo se revisan solicitudes pendientes; la fila queda bloqueada hasta el commit
+    let reviewed = sqlx::query_as!(
+        GroupJoinRequestRow,
+        r#"
+        UPDATE group_join_requests
+        SET status = $1, reviewed_by = $2, reviewed_at = NOW()
+        WHERE id = $3 AND group_id = $4 AND status = 'pending'
+        RETURNING id, group_id, user_id, message, status, created_at, reviewed_by, reviewed_at
+        "#,
+        review.status.as_str(),
+        user.id,
+        request_id,
+        group_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| HttpError::not_found("Pending join request not found"))?;
+
+    if review.status == JoinRequestStatus::Approved {
+        // Si el usuario ya entró por otra vía (p. ej. una invitación) no hay nada que añadir
+        add_member(&mut tx, group_id, reviewed.user_id).await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(HttpResponse::Ok().json(GroupJoinRequest::from(reviewed)))
+}
+
+// Límites de los enlaces de invitación
+const MAX_INVITE_HOURS: i64 = 24 * 365;
+const MAX_INVITE_USES: i32 = 10_000;
+
+#[derive(FromRow)]
+struct GroupInviteRow {
+    id: i32,
+    group_id: i32,
+    code: String,
+    created_by: Option<i32>,
+    expires_at: Option<DateTime<Utc>>,
+    max_uses: Option<i32>,
+    uses: i32,
+    revoked: bool,
+    created_at: DateTime<Utc>,
+}
+
+impl From<GroupInviteRow> for GroupInvite {
+    fn from(row: GroupInviteRow) -> Self {
+        GroupInvite {
+            id: row.id,
+            group_id: row.group_id,
+            code: row.code,
+            created_by: row.created_by,
+            expires_at: row.expires_at,
+            max_uses: row.max_uses,
+            uses: row.uses,
+            revoked: row.revoked,
+            created_at: row.created_at,
+        }
+    }
+}
+
+async fn get_invites(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::ManageInvites).await?;
+
+    let invites: Vec<GroupInvite> = sqlx::query_as!(
+        GroupInviteRow,
+        r#"
+        SELECT id, group_id, code, created_by, expires_at, max_uses, uses, revoked, created_at
+        FROM group_invites
+        WHERE group_id = $1
+        ORDER BY created_at DESC, id DESC
+        "#,
+        *id
+    )
+    .fetch_all(pool.get_ref())
+    .await?
+    .into_iter()
+    .map(GroupInvite::from)
+    .collect();
+
+    Ok(HttpResponse::Ok().json(invites))
+}
+
+async fn create_invite(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    invite: web::Json<GroupInviteCreate>,
+) -> Result<HttpResponse, HttpError> {
+    require_permission(pool.get_ref(), *id, user.id, GroupPermission::ManageInvites).await?;
+
+    if let Some(hours) = invite.expires_in_hours {
+        if !(1..=MAX_INVITE_HOURS).contains(&hours) {
+            return Err(HttpError::validation(format!(
+                "expires_in_hours must be between 1 and {}",
+                MAX_INVITE_HOURS
+            )));
+        }
+    }
+
+    if let Some(max_uses) = invite.max_uses {
+        if !(1..=MAX_INVITE_USES).contains(&max_uses) {
+            return Err(HttpError::validation(format!(
+                "max_uses must be between 1 and {}",
+                MAX_INVITE_USES
+            )));
+        }
+    }
+
+    let expires_at = invite.expires_in_hours.map(|hours| now_utc() + Duration::hours(hours));
+    let code = Uuid::new_v4().simple().to_string();
+
+    let created: GroupInvite = sqlx::query_as!(
+        GroupInviteRow,
+        r#"
+        INSERT INTO group_invites (group_id, code, created_by, expires_at, max_uses)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id, group_id, code, created_by, expires_at, max_uses, uses, revoked, created_at
+        "#,
+        *id,
+        code,
+        user.id,
+        expires_at,
+        invite.max_uses
+    )
+    .fetch_one(pool.get_ref())
+    .await?
+    .into();
+
+    Ok(HttpResponse::Created().json(created))
+}
+
+async fn revoke_invite(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    path: web::Path<(i32, i32)>,
+) -> Result<HttpResponse, HttpError> {
+    let (group_id, invite_id) = path.into_inner();
+    require_permission(pool.get_ref(), group_id, user.id, GroupPermission::ManageInvites).await?;
+
+    let revoked = sqlx::query!(
+        "UPDATE group_invites SET revoked = TRUE WHERE id = $1 AND group_id = $2",
+        invite_id,
+        group_id
+    )
+    .execute(pool.get_ref())
+    .await?
+    .rows_affected();
+
+    if revoked == 0 {
+        return Err(HttpError::not_found("Invite not found"));
+    }
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn accept_invite(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    code: web::Path<String>,
+) -> Result<HttpResponse, HttpError> {
+    let mut tx = pool.begin().await?;
+
+    // FOR UPDATE serializa los usos concurrentes del mismo enlace
+    let invite = sqlx::query_as!(
+        GroupInviteRow,
+        r#"
+        SELECT id, group_id, code, created_by, expires_at, max_uses, uses, revoked, created_at
+        FROM group_invites
+        WHERE code = $1
+        FOR UPDATE
+        "#,
+        code.as_str()
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| HttpError::not_found("Invite not found"))?;
+
+    if invite.revoked || invite.expires_at.is_some_and(|expires_at| expires_at <= now_utc()) {
+        return Err(HttpError::forbidden("This invite link has expired"));
+    }
+
+    if invite.max_uses.is_some_and(|max_uses| invite.uses >= max_uses) {
+        return Err(HttpError::forbidden("This invite link has reached its maximum number of uses"));
+    }
+
+    let role = sqlx::query_scalar!(
+        "SELECT role FROM group_members WHERE group_id = $1 AND user_id = $2",
+        invite.group_id,
+        user.id
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .map(|role| GroupRole::from_db(role.as_deref()));
+
+    match role {
+        Some(GroupRole::Banned) => return Err(HttpError::forbidden("You are banned from this group")),
+        Some(_) => return Err(HttpError::conflict("Already a member of this group")),
+        None => {}
+    }
+
+    add_member(&mut tx, invite.group_id, user.id).await?;
+
+    sqlx::query!("UPDATE group_invites SET uses = uses + 1 WHERE id = $1", invite.id)
+        .execute(&mut *tx)
+        .await?;
+
+    // La invitación resuelve cualquier solicitud que siguiera pendiente
+    sqlx::query!(
+        r#"
+        UPDATE group_join_requests
+        SET status = 'approved', reviewed_by = $1, reviewed_at = NOW()
+        WHERE group_id = $2 AND user_id = $3 AND status = 'pending'
+        "#,
+        invite.created_by,
+        invite.group_id,
+        user.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    let group = fetch_group_with_relations(pool.get_ref(), invite.group_id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Group not found"))?;
+
+    Ok(HttpResponse::Ok().json(group))
 }
 
 #[derive(Deserialize)]
 pub struct GetGroupsQuery {
     pub category: Option<String>,
-    pub search: Option<String>,
-    pub page: Option<i32>,
-    pub limit: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct GetGroupPostsQuery {
+    pub cursor: Option<String>,
 }
@@ -0,0 +1,458 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web_httpauth::extractors::bearer::BearerAuth;
+use actix_ws::{AggregatedMessage, AggregatedMessageStream, CloseCode, CloseReason, Session};
+use crate::models::groups::GroupRole;
+use crate::models::messages::*;
+use crate::models::auth::{TokenClaims, User};
+use crate::db::DbPool;
+use crate::middleware::{authenticate, require_verified_email};
+use crate::routes::groups::fetch_member_role;
+use crate::services::chat::{ChatHub, TICKET_TTL};
+use crate::services::presence::PresenceTracker;
+use crate::utils::datetime::{naive_opt_to_utc, now_utc};
+use crate::utils::error::HttpError;
+use crate::utils::revocation;
+use crate::AppState;
+
+use chrono::NaiveDateTime;
+use std::time::Duration;
+use futures_util::StreamExt;
+use serde::Deserialize;
+use serde_json::json;
+use sqlx::FromRow;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::time::{interval, Instant};
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/{id}/messages").route(web::get().to(get_messages)))
+        .service(web::resource("/{id}/messages/read").route(web::post().to(mark_messages_read)))
+        .service(web::resource("/{id}/chat/ticket").route(web::post().to(create_chat_ticket)));
+}
+
+// Longitud máxima de un mensaje de chat
+const MAX_MESSAGE_LENGTH: usize = 2000;
+// Tamaño máximo de una trama WebSocket entrante
+const MAX_FRAME_SIZE: usize = 16 * 1024;
+// Cada cuánto se envía un ping y cuánto silencio se tolera antes de cerrar la conexión
+const PING_INTERVAL: Duration = Duration::from_secs(30);
+const CLIENT_TIMEOUT: Duration = Duration::from_secs(75);
+// Antigüedad máxima de la última comprobación de acceso antes de entregar un evento
+const ACCESS_RECHECK_INTERVAL: Duration = Duration::from_secs(5);
+
+#[derive(FromRow)]
+struct GroupMessageRow {
+    id: i32,
+    group_id: Option<i32>,
+    user_id: Option<i32>,
+    content: String,
+    timestamp: Option<NaiveDateTime>,
+    is_read: Option<bool>,
+}
+
+impl From<GroupMessageRow> for GroupMessage {
+    fn from(row: GroupMessageRow) -> Self {
+        GroupMessage {
+            id: row.id,
+            group_id: row.group_id.unwrap_or_default(),
+            user_id: row.user_id.unwrap_or_default(),
+            content: row.content,
+            timestamp: naive_opt_to_utc(row.timestamp).unwrap_or_else(now_utc),
+            is_read: row.is_read.unwrap_or_default(),
+        }
+    }
+}
+
+// El chat es solo para miembros, sea cual sea la visibilidad del grupo
+async fn ensure_chat_member(pool: &DbPool, group_id: i32, user_id: i32) -> Result<(), HttpError> {
+    let exists = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM groups WHERE id = $1) as "exists!""#,
+        group_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if !exists {
+        return Err(HttpError::not_found("Group not found"));
+    }
+
+    match fetch_member_role(pool, group_id, user_id).await? {
+        Some(GroupRole::Banned) => Err(HttpError::forbidden("You are banned from this group")),
+        Some(_) => Ok(()),
+        None => Err(HttpError::forbidden("Only group members can use the group chat")),
+    }
+}
+
+fn validate_message(content: &str) -> Result<&str, String> {
+    let content = content.trim();
+    if content.is_empty() {
+        return Err("Message content cannot be empty".to_string());
+    }
+    if content.chars().count() > MAX_MESSAGE_LENGTH {
+        return Err(format!("Messages cannot be longer than {} characters", MAX_MESSAGE_LENGTH));
+    }
+    Ok(content)
+}
+
+// Guarda el mensaje solo si el autor sigue siendo miembro; devuelve None si lo han expulsado
+async fn save_message(
+    pool: &DbPool,
+    group_id: i32,
+    user_id: i32,
+    content: &str,
+) -> Result<Option<GroupMessage>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        GroupMessageRow,
+        r#"
+        INSERT INTO messages (group_id, user_id, content)
+        SELECT $1, $2, $3
+        WHERE EXISTS (
+            SELECT 1 FROM group_members
+            WHERE group_id = $1 AND user_id = $2 AND role <> 'banned'
+        )
+        RETURNING id, group_id, user_id, content, timestamp, is_read
+        "#,
+        group_id,
+        user_id,
+        content
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    Ok(row.map(GroupMessage::from))
+}
+
+// Marca como leídos los mensajes de otros miembros hasta `up_to` y avisa a los conectados
+async fn mark_read(
+    pool: &DbPool,
+    hub: &ChatHub,
+    group_id: i32,
+    user_id: i32,
+    up_to: i32,
+) -> Result<u64, sqlx::Error> {
+    let updated = sqlx::query!(
+        r#"
+        UPDATE messages SET is_read = TRUE
+        WHERE group_id = $1 AND id <= $2 AND user_id IS DISTINCT FROM $3 AND is_read IS NOT TRUE
+        "#,
+        group_id,
+        up_to,
+        user_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    hub.publish(group_id, ChatServerEvent::Read { user_id, up_to });
+    Ok(updated)
+}
+
+async fn get_messages(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    query: web::Query<GetMessagesQuery>,
+) -> Result<HttpResponse, HttpError> {
+    ensure_chat_member(pool.get_ref(), *id, user.id).await?;
+
+    let limit = query.limit.unwrap_or(50).clamp(1, 100) as i64;
+
+    // Se pide una fila de más para saber si quedan mensajes anteriores
+    let mut messages: Vec<GroupMessage> = sqlx::query_as!(
+        GroupMessageRow,
+        r#"
+        SELECT id, group_id, user_id, content, timestamp, is_read
+        FROM messages
+        WHERE group_id = $1 AND ($2::int IS NULL OR id < $2)
+        ORDER BY id DESC
+        LIMIT $3
+        "#,
+        *id,
+        query.before,
+        limit + 1
+    )
+    .fetch_all(pool.get_ref())
+    .await?
+    .into_iter()
+    .map(GroupMessage::from)
+    .collect();
+
+    let has_more = messages.len() as i64 > limit;
+    messages.truncate(limit as usize);
+    messages.reverse();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "messages": messages,
+        "has_more": has_more,
+        "next_before": if has_more { messages.first().map(|m| m.id) } else { None }
+    })))
+}
+
+async fn mark_messages_read(
+    pool: web::Data<DbPool>,
+    hub: web::Data<ChatHub>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    receipt: web::Json<MessageReadReceipt>,
+) -> Result<HttpResponse, HttpError> {
+    ensure_chat_member(pool.get_ref(), *id, user.id).await?;
+
+    let updated = mark_read(pool.get_ref(), hub.get_ref(), *id, user.id, receipt.up_to).await?;
+
+    Ok(HttpResponse::Ok().json(json!({ "updated": updated })))
+}
+
+/// Crea un ticket de un solo uso para abrir el WebSocket del chat.
+///
+/// Los navegadores no pueden enviar la cabecera Authorization al abrir un WebSocket y un
+/// JWT en la URL quedaría en los logs de acceso, así que se pasa este ticket en su lugar.
+async fn create_chat_ticket(
+    pool: web::Data<DbPool>,
+    hub: web::Data<ChatHub>,
+    user: web::ReqData<User>,
+    credentials: BearerAuth,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    ensure_chat_member(pool.get_ref(), *id, user.id).await?;
+
+    let ticket = hub.issue_ticket(credentials.token(), *id);
+
+    Ok(HttpResponse::Created().json(json!({
+        "ticket": ticket,
+        "expires_in": TICKET_TTL.as_secs()
+    })))
+}
+
+/// Abre el WebSocket del chat de un grupo.
+///
+/// Se autentica con la cabecera Authorization o con un ticket de `POST /groups/{id}/chat/ticket`
+/// en el parámetro `ticket`.
+pub async fn websocket(
+    req: HttpRequest,
+    body: web::Payload,
+    data: web::Data<AppState>,
+    hub: web::Data<ChatHub>,
+    presence: web::Data<PresenceTracker>,
+    id: web::Path<i32>,
+    query: web::Query<WebSocketQuery>,
+) -> Result<HttpResponse, HttpError> {
+    let header_token = req
+        .headers()
+        .get(actix_web::http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::to_string);
+    let token = match (header_token, query.ticket.as_deref()) {
+        (Some(token), _) => token,
+        (None, Some(ticket)) => hub
+            .redeem_ticket(ticket, *id)
+            .ok_or_else(|| HttpError::unauthorized("Invalid or expired chat ticket"))?,
+        (None, None) => return Err(HttpError::unauthorized("Missing authentication token")),
+    };
+
+    let (user, claims) = authenticate(&data.pool, &data.jwt_keys, &token).await?;
+    ensure_chat_member(&data.pool, *id, user.id).await?;
+    // Sin el email verificado se puede leer el chat pero no escribir en él
+    let can_post = require_verified_email(&data.config, &user).is_ok();
+
+    let (response, session, stream) = actix_ws::handle(&req, body)
+        .map_err(|e| HttpError::bad_request(e.to_string()))?;
+
+    let conn = ChatConnection {
+        pool: data.pool.clone(),
+        hub: hub.get_ref().clone(),
+        presence: presence.get_ref().clone(),
+        group_id: *id,
+        user_id: user.id,
+        claims,
+        can_post,
+    };
+    actix_web::rt::spawn(chat_session(
+        session,
+        stream.max_frame_size(MAX_FRAME_SIZE).aggregate_continuations(),
+        conn,
+    ));
+
+    Ok(response)
+}
+
+// Estado de una conexión WebSocket abierta
+struct ChatConnection {
+    pool: DbPool,
+    hub: ChatHub,
+    presence: PresenceTracker,
+    group_id: i32,
+    user_id: i32,
+    claims: TokenClaims,
+    can_post: bool,
+}
+
+impl ChatConnection {
+    // Comprueba que la sesión sigue siendo válida y el usuario sigue en el grupo.
+    // Ante un error de la base de datos se cierra la conexión por precaución.
+    async fn check_access(&self) -> Result<(), &'static str> {
+        if self.claims.exp as i64 <= now_utc().timestamp() {
+            return Err("Session expired");
+        }
+
+        match revocation::is_revoked(&self.pool, &self.claims).await {
+            Ok(false) => {}
+            Ok(true) => return Err("Session revoked"),
+            Err(e) => {
+                log::error!("Failed to check token revocation: {}", e);
+                return Err("Access could not be verified");
+            }
+        }
+
+        match fetch_member_role(&self.pool, self.group_id, self.user_id).await {
+            Ok(Some(role)) if role != GroupRole::Banned => Ok(()),
+            Ok(_) => Err("You are no longer a member of this group"),
+            Err(e) => {
+                log::error!("Failed to check group membership: {}", e);
+                Err("Access could not be verified")
+            }
+        }
+    }
+}
+
+async fn chat_session(mut session: Session, mut stream: AggregatedMessageStream, conn: ChatConnection) {
+    let mut events = conn.hub.subscribe(conn.group_id);
+    let mut ping = interval(PING_INTERVAL);
+    let mut last_heard = Instant::now();
+    let mut last_checked = Instant::now();
+    // Motivo del cierre si se le retira el acceso; se cierra con el código de política
+    let mut denied = None;
+    conn.presence.connect(conn.user_id).await;
+
+    loop {
+        tokio::select! {
+            message = stream.next() => {
+                last_heard = Instant::now();
+                let text = match message {
+                    Some(Ok(AggregatedMessage::Text(text))) => text,
+                    Some(Ok(AggregatedMessage::Ping(bytes))) => {
+                        if session.pong(&bytes).await.is_err() {
+                            break;
+                        }
+                        continue;
+                    }
+                    Some(Ok(AggregatedMessage::Pong(_))) => {
+                        conn.presence.activity(conn.user_id, false).await;
+                        continue;
+                    }
+                    Some(Ok(AggregatedMessage::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => continue,
+                };
+
+                // Escribir o leer en el chat cuenta como actividad del usuario
+                conn.presence.activity(conn.user_id, true).await;
+                let keep_open = handle_client_event(&mut session, &conn, &text).await;
+                if !keep_open {
+                    break;
+                }
+            }
+            _ = ping.tick() => {
+                // Conexión caída sin cierre: el cliente no responde a los pings
+                if last_heard.elapsed() > CLIENT_TIMEOUT {
+                    break;
+                }
+                if let Err(reason) = conn.check_access().await {
+                    denied = Some(reason);
+                    break;
+                }
+                last_checked = Instant::now();
+                if session.ping(b"").await.is_err() {
+                    break;
+                }
+            }
+            event = events.recv() => {
+                let event = match event {
+                    Ok(event) => event,
+                    // La conexión va retrasada: el cliente recupera lo perdido con el historial
+                    Err(RecvError::Lagged(_)) => continue,
+                    Err(RecvError::Closed) => break,
+                };
+                // No se entrega nada a quien ya no tiene acceso
+                if last_checked.elapsed() > ACCESS_RECHECK_INTERVAL {
+                    if let Err(reason) = conn.check_access().await {
+                        denied = Some(reason);
+                        break;
+                    }
+                    last_checked = Instant::now();
+                }
+                let Ok(payload) = serde_json::to_string(&event) else { continue };
+                if session.text(payload).await.is_err() {
+                    break;
+                }
+            }
+        }
+    }
+
+    drop(events);
+    conn.hub.leave(conn.group_id);
+    conn.presence.disconnect(conn.user_id).await;
+    let reason = denied.map(|reason| CloseReason {
+        code: CloseCode::Policy,
+        description: Some(reason.to_string()),
+    });
+    let _ = session.close(reason).await;
+}
+
+// Procesa un evento del cliente. Devuelve false si hay que cerrar la conexión.
+async fn handle_client_event(session: &mut Session, conn: &ChatConnection, text: &str) -> bool {
+    let event = match serde_json::from_str::<ChatClientEvent>(text) {
+        Ok(event) => event,
+        Err(e) => return send_error(session, format!("Invalid event: {}", e)).await,
+    };
+
+    match event {
+        ChatClientEvent::Message { .. } if !conn.can_post => {
+            send_error(session, "Please verify your email address before posting".to_string()).await
+        }
+        ChatClientEvent::Message { content } => {
+            let content = match validate_message(&content) {
+                Ok(content) => content,
+                Err(e) => return send_error(session, e).await,
+            };
+
+            match save_message(&conn.pool, conn.group_id, conn.user_id, content).await {
+                Ok(Some(message)) => {
+                    conn.hub.publish(conn.group_id, ChatServerEvent::Message { message });
+                    true
+                }
+                Ok(None) => {
+                    send_error(session, "You are no longer a member of this group".to_string()).await;
+                    false
+                }
+                Err(e) => {
+                    log::error!("Failed to save chat message: {}", e);
+                    send_error(session, "The message could not be sent".to_string()).await
+                }
+            }
+        }
+        ChatClientEvent::Read { up_to } => {
+            if let Err(e) = mark_read(&conn.pool, &conn.hub, conn.group_id, conn.user_id, up_to).await {
+                log::error!("Failed to update read receipts: {}", e);
+                return send_error(session, "Read receipt could not be saved".to_string()).await;
+            }
+            true
+        }
+    }
+}
+
+async fn send_error(session: &mut Session, message: String) -> bool {
+    let payload = serde_json::to_string(&ChatServerEvent::Error { message }).unwrap_or_default();
+    session.text(payload).await.is_ok()
+}
+
+#[derive(Deserialize)]
+pub struct GetMessagesQuery {
+    /// Devuelve los mensajes con id menor que este; sin valor, los más recientes.
+    pub before: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+#[derive(Deserialize)]
+pub struct WebSocketQuery {
+    /// Ticket de un solo uso de `POST /groups/{id}/chat/ticket`.
+    pub ticket: Option<String>,
+}
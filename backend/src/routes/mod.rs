@@ -1,15 +1,8 @@
-pub mod auth;
 pub mod posts;
+pub mod categories;
 pub mod comments;
 pub mod groups;
 pub mod mood;
-
-use actix_web::web;
-
-pub fn configure(cfg: &mut web::ServiceConfig) {
-    auth::configure(cfg);
-    posts::configure(cfg);
-    comments::configure(cfg);
-    groups::configure(cfg);
-    mood::configure(cfg);
-}
\ No newline at end of file
+pub mod messages;
+pub mod presence;
+pub mod search;
@@ -1,81 +1,1286 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{http::header, web, HttpResponse, Responder};
 use crate::models::mood::*;
 use crate::models::auth::User;
 use crate::db::DbPool;
+use crate::utils::csv;
+use crate::utils::datetime::{naive_opt_to_utc, now_utc};
+use crate::utils::error::HttpError;
+use crate::utils::pagination::{self, Pagination};
 
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime};
+use futures_util::{stream, TryStreamExt};
+use log::error;
 use serde::Deserialize;
+use serde_json::json;
+use sqlx::{FromRow, Postgres, Transaction};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use uuid::Uuid;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
-    cfg.service(
-        web::scope("/api/mood")
-            .service(web::resource("/levels")
-                .route(web::get().to(get_anxiety_levels))
-            )
-            .service(web::resource("/records")
-                .route(web::get().to(get_mood_records))
-                .route(web::post().to(create_mood_record))
-            )
-            .service(web::resource("/records/{id}")
-                .route(web::get().to(get_mood_record))
-                .route(web::put().to(update_mood_record))
-                .route(web::delete().to(delete_mood_record))
-            )
-            .service(web::resource("/stats").route(web::get().to(get_user_stats)))
-            .service(web::resource("/preferences").route(web::get().to(get_user_preferences)))
-    );
+    cfg.service(web::resource("/levels")
+            .route(web::get().to(get_anxiety_levels))
+        )
+        .service(web::resource("/records")
+            .route(web::get().to(get_mood_records))
+            .route(web::post().to(create_mood_record))
+        )
+        .service(web::resource("/records/{id}")
+            .route(web::get().to(get_mood_record))
+            .route(web::put().to(update_mood_record))
+            .route(web::delete().to(delete_mood_record))
+        )
+        .service(web::resource("/triggers")
+            .route(web::get().to(get_triggers))
+            .route(web::post().to(create_trigger))
+        )
+        .service(web::resource("/triggers/{id}")
+            .route(web::put().to(update_trigger))
+            .route(web::delete().to(delete_trigger))
+        )
+        .service(web::resource("/export").route(web::get().to(export_mood_records)))
+        .service(web::resource("/import")
+            .app_data(web::PayloadConfig::new(MAX_IMPORT_BYTES))
+            .route(web::post().to(import_mood_records))
+        )
+        .service(web::resource("/analytics").route(web::get().to(get_mood_analytics)))
+        .service(web::resource("/stats").route(web::get().to(get_user_stats)))
+        .service(web::resource("/preferences").route(web::get().to(get_user_preferences)));
+}
+
+// Fila plana de mood_records unida con anxiety_levels
+#[derive(FromRow)]
+struct MoodRecordRow {
+    id: i32,
+    user_id: Option<i32>,
+    anxiety_level_id: Option<i32>,
+    record_date: Option<NaiveDateTime>,
+    notes: Option<String>,
+    mood_score: Option<i32>,
+    level_name: Option<String>,
+    level_description: Option<String>,
+    level_color_code: Option<String>,
+}
+
+impl MoodRecordRow {
+    fn into_relations(self, triggers: Vec<Trigger>) -> MoodRecordWithRelations {
+        let row = self;
+
+        // El nivel de ansiedad solo existe si el LEFT JOIN encontró una fila
+        let anxiety_level = match (row.anxiety_level_id, row.level_name) {
+            (Some(id), Some(name)) => Some(AnxietyLevel {
+                id,
+                name,
+                description: row.level_description,
+                color_code: row.level_color_code,
+            }),
+            _ => None,
+        };
+
+        MoodRecordWithRelations {
+            record: MoodRecord {
+                id: row.id,
+                user_id: row.user_id.unwrap_or_default(),
+                anxiety_level_id: row.anxiety_level_id,
+                record_date: naive_opt_to_utc(row.record_date).unwrap_or_else(now_utc),
+                notes: row.notes,
+                mood_score: row.mood_score.unwrap_or_default(),
+            },
+            anxiety_level,
+            triggers,
+        }
+    }
+}
+
+async fn fetch_mood_record(
+    pool: &DbPool,
+    id: i32,
+) -> Result<Option<MoodRecordWithRelations>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        MoodRecordRow,
+        r#"
+        SELECT
+            m.id, m.user_id, m.anxiety_level_id, m.record_date,
+            m.notes, m.mood_score,
+            al.name as "level_name?", al.description as "level_description?",
+            al.color_code as "level_color_code?"
+        FROM mood_records m
+        LEFT JOIN anxiety_levels al ON m.anxiety_level_id = al.id
+        WHERE m.id = $1
+        "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    match row {
+        Some(row) => {
+            let mut triggers = fetch_record_triggers(pool, &[row.id]).await?;
+            let triggers = triggers.remove(&row.id).unwrap_or_default();
+            Ok(Some(row.into_relations(triggers)))
+        }
+        None => Ok(None),
+    }
+}
+
+// Carga en una sola consulta los desencadenantes de varios registros
+async fn fetch_record_triggers(
+    pool: &DbPool,
+    record_ids: &[i32],
+) -> Result<HashMap<i32, Vec<Trigger>>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT mt.mood_record_id as "mood_record_id!", t.id, t.user_id, t.name, t.created_at
+        FROM mood_record_triggers mt
+        JOIN triggers t ON t.id = mt.trigger_id
+        WHERE mt.mood_record_id = ANY($1)
+        ORDER BY t.name
+        "#,
+        record_ids
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut triggers: HashMap<i32, Vec<Trigger>> = HashMap::new();
+    for row in rows {
+        triggers.entry(row.mood_record_id).or_default().push(Trigger {
+            id: row.id,
+            user_id: row.user_id,
+            name: row.name,
+            created_at: row.created_at,
+        });
+    }
+
+    Ok(triggers)
+}
+
+/// Normaliza el nombre de un desencadenante: recorta y colapsa espacios.
+fn normalize_trigger_name(name: &str) -> Result<String, HttpError> {
+    let name = name.split_whitespace().collect::<Vec<_>>().join(" ");
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err(HttpError::validation("Trigger name must be between 1 and 100 characters"));
+    }
+    Ok(name)
+}
+
+/// Convierte la lista de IDs o nombres en IDs de desencadenantes visibles para el
+/// usuario, creando como propios los nombres que todavía no existan.
+async fn resolve_trigger_ids(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    refs: &[TriggerRef],
+) -> Result<Vec<i32>, HttpError> {
+    let mut ids = Vec::with_capacity(refs.len());
+
+    for trigger in refs {
+        let id = match trigger {
+            TriggerRef::Id(id) => {
+                let visible = sqlx::query_scalar!(
+                    r#"
+                    SELECT EXISTS(
+                        SELECT 1 FROM triggers WHERE id = $1 AND (user_id = $2 OR user_id IS NULL)
+                    ) as "exists!"
+                    "#,
+                    id,
+                    user_id
+                )
+                .fetch_one(&mut **tx)
+                .await?;
+
+                if !visible {
+                    return Err(HttpError::validation(format!("Trigger {} does not exist", id)));
+                }
+                *id
+            }
+            TriggerRef::Name(name) => {
+                let name = normalize_trigger_name(name)?;
+                let existing = sqlx::query_scalar!(
+                    r#"
+                    SELECT id FROM triggers
+                    WHERE (user_id = $1 OR user_id IS NULL) AND LOWER(name) = LOWER($2)
+                    ORDER BY user_id NULLS LAST
+                    LIMIT 1
+                    "#,
+                    user_id,
+                    name
+                )
+                .fetch_optional(&mut **tx)
+                .await?;
+
+                match existing {
+                    Some(id) => id,
+                    None => sqlx::query_scalar!(
+                        r#"
+                        INSERT INTO triggers (user_id, name)
+                        VALUES ($1, $2)
+                        ON CONFLICT (COALESCE(user_id, 0), LOWER(name))
+                        DO UPDATE SET name = triggers.name
+                        RETURNING id
+                        "#,
+                        user_id,
+                        name
+                    )
+                    .fetch_one(&mut **tx)
+                    .await?,
+                }
+            }
+        };
+
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+
+    Ok(ids)
+}
+
+/// Igual que `resolve_trigger_ids` para una lista de nombres ya normalizados, pero
+/// con dos consultas en total: devuelve el ID de cada nombre indexado en minúsculas.
+async fn resolve_trigger_names(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    names: &[String],
+) -> Result<HashMap<String, i32>, sqlx::Error> {
+    // Un nombre por clave; se conserva la primera forma en que aparece
+    let mut distinct: HashMap<String, &str> = HashMap::new();
+    for name in names {
+        distinct.entry(name.to_lowercase()).or_insert(name);
+    }
+    let keys: Vec<String> = distinct.keys().cloned().collect();
+
+    let mut ids: HashMap<String, i32> = sqlx::query!(
+        r#"
+        SELECT DISTINCT ON (LOWER(name)) LOWER(name) as "key!", id
+        FROM triggers
+        WHERE (user_id = $1 OR user_id IS NULL) AND LOWER(name) = ANY($2)
+        ORDER BY LOWER(name), user_id NULLS LAST
+        "#,
+        user_id,
+        &keys
+    )
+    .fetch_all(&mut **tx)
+    .await?
+    .into_iter()
+    .map(|row| (row.key, row.id))
+    .collect();
+
+    let missing: Vec<String> = distinct
+        .into_iter()
+        .filter(|(key, _)| !ids.contains_key(key))
+        .map(|(_, name)| name.to_string())
+        .collect();
+
+    if !missing.is_empty() {
+        let created = sqlx::query!(
+            r#"
+            INSERT INTO triggers (user_id, name)
+            SELECT $1, UNNEST($2::text[])
+            ON CONFLICT (COALESCE(user_id, 0), LOWER(name))
+            DO UPDATE SET name = triggers.name
+            RETURNING id, LOWER(name) as "key!"
+            "#,
+            user_id,
+            &missing
+        )
+        .fetch_all(&mut **tx)
+        .await?;
+
+        ids.extend(created.into_iter().map(|row| (row.key, row.id)));
+    }
+
+    Ok(ids)
+}
+
+// Sustituye los desencadenantes enlazados a un registro
+async fn set_record_triggers(
+    tx: &mut Transaction<'_, Postgres>,
+    record_id: i32,
+    trigger_ids: &[i32],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM mood_record_triggers WHERE mood_record_id = $1", record_id)
+        .execute(&mut **tx)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO mood_record_triggers (mood_record_id, trigger_id)
+        SELECT $1, UNNEST($2::int[])
+        ON CONFLICT DO NOTHING
+        "#,
+        record_id,
+        trigger_ids
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Comprueba que el registro exista y pertenezca al usuario autenticado
+async fn check_record_owner(pool: &DbPool, id: i32, user_id: i32) -> Result<(), HttpError> {
+    match sqlx::query_scalar!("SELECT user_id FROM mood_records WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?
+    {
+        Some(Some(owner_id)) if owner_id == user_id => Ok(()),
+        Some(_) => Err(HttpError::forbidden("Not authorized to access this mood record")),
+        None => Err(HttpError::not_found("Mood record not found")),
+    }
+}
+
+async fn validate_mood_record(pool: &DbPool, record: &MoodRecordCreate) -> Result<(), HttpError> {
+    // Debe coincidir con CHECK (mood_score BETWEEN 1 AND 10)
+    if !(1..=10).contains(&record.mood_score) {
+        return Err(HttpError::validation("mood_score must be between 1 and 10"));
+    }
+
+    if let Some(level_id) = record.anxiety_level_id {
+        let exists = sqlx::query_scalar!(
+            r#"SELECT EXISTS(SELECT 1 FROM anxiety_levels WHERE id = $1) as "exists!""#,
+            level_id
+        )
+        .fetch_one(pool)
+        .await?;
+
+        if !exists {
+            return Err(HttpError::validation("anxiety_level_id does not exist"));
+        }
+    }
+
+    Ok(())
 }
 
 async fn get_anxiety_levels(
-    _pool: web::Data<DbPool>,
-) -> impl Responder {
-    // Implementar la lógica para obtener niveles de ansiedad
-    HttpResponse::Ok().json(Vec::<AnxietyLevel>::new())
+    pool: web::Data<DbPool>,
+) -> Result<HttpResponse, HttpError> {
+    let levels = sqlx::query_as!(
+        AnxietyLevel,
+        "SELECT id, name, description, color_code FROM anxiety_levels ORDER BY id"
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(levels))
 }
 
 async fn get_mood_records(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _query: web::Query<GetMoodRecordsQuery>,
-) -> impl Responder {
-    // Implementar la lógica para obtener registros de estado de ánimo
-    HttpResponse::Ok().json(Vec::<AnxietyLevel>::new())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    query: web::Query<GetMoodRecordsQuery>,
+    pagination: web::Query<Pagination>,
+) -> Result<HttpResponse, HttpError> {
+    let (start, end) = query.date_range()?;
+    let (page, limit) = pagination.resolve(20, 100);
+    let offset = pagination::offset(page, limit);
+
+    let total = sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM mood_records m
+        WHERE m.user_id = $1
+          AND ($2::timestamp IS NULL OR m.record_date >= $2)
+          AND ($3::timestamp IS NULL OR m.record_date < $3)
+        "#,
+        user.id,
+        start,
+        end
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    let rows = sqlx::query_as!(
+        MoodRecordRow,
+        r#"
+        SELECT
+            m.id, m.user_id, m.anxiety_level_id, m.record_date,
+            m.notes, m.mood_score,
+            al.name as "level_name?", al.description as "level_description?",
+            al.color_code as "level_color_code?"
+        FROM mood_records m
+        LEFT JOIN anxiety_levels al ON m.anxiety_level_id = al.id
+        WHERE m.user_id = $1
+          AND ($2::timestamp IS NULL OR m.record_date >= $2)
+          AND ($3::timestamp IS NULL OR m.record_date < $3)
+        ORDER BY m.record_date DESC, m.id DESC
+        LIMIT $4 OFFSET $5
+        "#,
+        user.id,
+        start,
+        end,
+        limit as i64,
+        offset
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let record_ids: Vec<i32> = rows.iter().map(|row| row.id).collect();
+    let mut triggers = fetch_record_triggers(pool.get_ref(), &record_ids).await?;
+
+    let records: Vec<MoodRecordWithRelations> = rows
+        .into_iter()
+        .map(|row| {
+            let record_triggers = triggers.remove(&row.id).unwrap_or_default();
+            row.into_relations(record_triggers)
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "records": records,
+        "total": total,
+        "page": page,
+        "per_page": limit
+    })))
 }
 
 async fn create_mood_record(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _record: web::Json<MoodRecordCreate>,
-) -> impl Responder {
-    // Implementar la lógica para crear un registro de estado de ánimo
-    HttpResponse::Created().json(MoodRecord::default())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    record: web::Json<MoodRecordCreate>,
+) -> Result<HttpResponse, HttpError> {
+    validate_mood_record(pool.get_ref(), &record).await?;
+
+    let mut tx = pool.begin().await?;
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO mood_records (user_id, anxiety_level_id, notes, mood_score)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        user.id,
+        record.anxiety_level_id,
+        record.notes,
+        record.mood_score
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let trigger_ids = resolve_trigger_ids(&mut tx, user.id, &record.triggers).await?;
+    set_record_triggers(&mut tx, id, &trigger_ids).await?;
+
+    tx.commit().await?;
+
+    let created = fetch_mood_record(pool.get_ref(), id)
+        .await?
+        .ok_or(HttpError::InternalServerError)?;
+
+    Ok(HttpResponse::Created().json(created))
 }
 
 async fn get_mood_record(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-) -> impl Responder {
-    // Implementar la lógica para obtener un registro de estado de ánimo por ID
-    HttpResponse::Ok().json(MoodRecord::default())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    check_record_owner(pool.get_ref(), *id, user.id).await?;
+
+    let record = fetch_mood_record(pool.get_ref(), *id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Mood record not found"))?;
+
+    Ok(HttpResponse::Ok().json(record))
 }
 
 async fn update_mood_record(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-    _record: web::Json<MoodRecordCreate>,
-) -> impl Responder {
-    // Implementar la lógica para actualizar un registro de estado de ánimo
-    HttpResponse::Ok().json(MoodRecord::default())
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    record: web::Json<MoodRecordCreate>,
+) -> Result<HttpResponse, HttpError> {
+    check_record_owner(pool.get_ref(), *id, user.id).await?;
+    validate_mood_record(pool.get_ref(), &record).await?;
+
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE mood_records
+        SET anxiety_level_id = $1, notes = $2, mood_score = $3
+        WHERE id = $4
+        "#,
+        record.anxiety_level_id,
+        record.notes,
+        record.mood_score,
+        *id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let trigger_ids = resolve_trigger_ids(&mut tx, user.id, &record.triggers).await?;
+    set_record_triggers(&mut tx, *id, &trigger_ids).await?;
+
+    tx.commit().await?;
+
+    let updated = fetch_mood_record(pool.get_ref(), *id)
+        .await?
+        .ok_or_else(|| HttpError::not_found("Mood record not found"))?;
+
+    Ok(HttpResponse::Ok().json(updated))
 }
 
 async fn delete_mood_record(
-    _pool: web::Data<DbPool>,
-    _user: web::ReqData<User>,
-    _id: web::Path<i32>,
-) -> impl Responder {
-    // Implementar la lógica para eliminar un registro de estado de ánimo
-    HttpResponse::NoContent().finish()
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    check_record_owner(pool.get_ref(), *id, user.id).await?;
+
+    sqlx::query!("DELETE FROM mood_records WHERE id = $1", *id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+async fn get_mood_analytics(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    query: web::Query<GetMoodRecordsQuery>,
+) -> Result<HttpResponse, HttpError> {
+    let (start, end) = query.date_range()?;
+    let pool = pool.get_ref();
+
+    let daily = fetch_mood_trend(pool, user.id, start, end, "day").await?;
+    let weekly = fetch_mood_trend(pool, user.id, start, end, "week").await?;
+    let monthly = fetch_mood_trend(pool, user.id, start, end, "month").await?;
+
+    // Las medias móviles ponderan cada registro, no cada día, y la ventana
+    // abarca días naturales aunque no haya registros en todos ellos
+    let moving_averages = sqlx::query_as!(
+        MoodMovingAverage,
+        r#"
+        WITH daily AS (
+            SELECT record_date::date AS day,
+                   SUM(mood_score)::float8 AS score_sum,
+                   COUNT(mood_score)::float8 AS score_count
+            FROM mood_records
+            WHERE user_id = $1
+              AND mood_score IS NOT NULL
+              AND ($2::timestamp IS NULL OR record_date >= $2)
+              AND ($3::timestamp IS NULL OR record_date < $3)
+            GROUP BY 1
+        )
+        SELECT
+            day as "date!",
+            score_sum / score_count as "daily_average!",
+            SUM(score_sum) OVER w7 / SUM(score_count) OVER w7 as "moving_average_7d!",
+            SUM(score_sum) OVER w30 / SUM(score_count) OVER w30 as "moving_average_30d!"
+        FROM daily
+        WINDOW
+            w7 AS (ORDER BY day RANGE BETWEEN INTERVAL '6 days' PRECEDING AND CURRENT ROW),
+            w30 AS (ORDER BY day RANGE BETWEEN INTERVAL '29 days' PRECEDING AND CURRENT ROW)
+        ORDER BY day
+        "#,
+        user.id,
+        start,
+        end
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let anxiety_levels = sqlx::query_as!(
+        AnxietyLevelCount,
+        r#"
+        SELECT
+            m.anxiety_level_id, al.name as "name?", al.color_code as "color_code?",
+            COUNT(*) as "records_count!"
+        FROM mood_records m
+        LEFT JOIN anxiety_levels al ON m.anxiety_level_id = al.id
+        WHERE m.user_id = $1
+          AND ($2::timestamp IS NULL OR m.record_date >= $2)
+          AND ($3::timestamp IS NULL OR m.record_date < $3)
+        GROUP BY m.anxiety_level_id, al.name, al.color_code
+        ORDER BY COUNT(*) DESC
+        "#,
+        user.id,
+        start,
+        end
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let triggers = sqlx::query_as!(
+        TriggerCount,
+        r#"
+        SELECT
+            t.id as trigger_id, t.name, t.user_id IS NULL as "is_system!",
+            COUNT(*) as "records_count!"
+        FROM mood_records m
+        JOIN mood_record_triggers mt ON mt.mood_record_id = m.id
+        JOIN triggers t ON t.id = mt.trigger_id
+        WHERE m.user_id = $1
+          AND ($2::timestamp IS NULL OR m.record_date >= $2)
+          AND ($3::timestamp IS NULL OR m.record_date < $3)
+        GROUP BY t.id, t.name, t.user_id
+        ORDER BY COUNT(*) DESC, t.name
+        LIMIT $4
+        "#,
+        user.id,
+        start,
+        end,
+        TOP_TRIGGERS
+    )
+    .fetch_all(pool)
+    .await?;
+
+    Ok(HttpResponse::Ok().json(MoodAnalytics {
+        daily,
+        weekly,
+        monthly,
+        moving_averages,
+        anxiety_levels,
+        triggers,
+    }))
+}
+
+// `unit` es cualquier campo válido para date_trunc: "day", "week" o "month"
+async fn fetch_mood_trend(
+    pool: &DbPool,
+    user_id: i32,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    unit: &str,
+) -> Result<Vec<MoodTrendPoint>, sqlx::Error> {
+    sqlx::query_as!(
+        MoodTrendPoint,
+        r#"
+        SELECT
+            date_trunc($4, record_date)::date as "period_start!",
+            AVG(mood_score)::float8 as "average_score!",
+            MIN(mood_score) as "min_score!",
+            MAX(mood_score) as "max_score!",
+            COUNT(*) as "records_count!"
+        FROM mood_records
+        WHERE user_id = $1
+          AND mood_score IS NOT NULL
+          AND ($2::timestamp IS NULL OR record_date >= $2)
+          AND ($3::timestamp IS NULL OR record_date < $3)
+        GROUP BY 1
+        ORDER BY 1
+        "#,
+        user_id,
+        start,
+        end,
+        unit
+    )
+    .fetch_all(pool)
+    .await
+}
+
+const TOP_TRIGGERS: i64 = 10;
+
+async fn get_triggers(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+) -> Result<HttpResponse, HttpError> {
+    let triggers = sqlx::query_as!(
+        Trigger,
+        r#"
+        SELECT id, user_id, name, created_at
+        FROM triggers
+        WHERE user_id = $1 OR user_id IS NULL
+        ORDER BY user_id NULLS FIRST, name
+        "#,
+        user.id
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(triggers))
+}
+
+// Solo los desencadenantes propios se pueden modificar; los del sistema son de solo lectura
+async fn check_trigger_owner(pool: &DbPool, id: i32, user_id: i32) -> Result<(), HttpError> {
+    match sqlx::query_scalar!("SELECT user_id FROM triggers WHERE id = $1", id)
+        .fetch_optional(pool)
+        .await?
+    {
+        Some(Some(owner_id)) if owner_id == user_id => Ok(()),
+        Some(None) => Err(HttpError::forbidden("System triggers cannot be modified")),
+        Some(Some(_)) | None => Err(HttpError::not_found("Trigger not found")),
+    }
+}
+
+// Un desencadenante propio no puede repetir el nombre de otro propio ni de uno del sistema
+async fn check_trigger_name_available(
+    pool: &DbPool,
+    user_id: i32,
+    name: &str,
+    exclude_id: Option<i32>,
+) -> Result<(), HttpError> {
+    let exists = sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM triggers
+            WHERE (user_id = $1 OR user_id IS NULL)
+              AND LOWER(name) = LOWER($2)
+              AND ($3::int IS NULL OR id <> $3)
+        ) as "exists!"
+        "#,
+        user_id,
+        name,
+        exclude_id
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if exists {
+        return Err(HttpError::conflict("A trigger with this name already exists"));
+    }
+    Ok(())
+}
+
+async fn create_trigger(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    trigger: web::Json<TriggerCreate>,
+) -> Result<HttpResponse, HttpError> {
+    let name = normalize_trigger_name(&trigger.name)?;
+    check_trigger_name_available(pool.get_ref(), user.id, &name, None).await?;
+
+    let created = sqlx::query_as!(
+        Trigger,
+        r#"
+        INSERT INTO triggers (user_id, name)
+        VALUES ($1, $2)
+        RETURNING id, user_id, name, created_at
+        "#,
+        user.id,
+        name
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Created().json(created))
+}
+
+async fn update_trigger(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+    trigger: web::Json<TriggerCreate>,
+) -> Result<HttpResponse, HttpError> {
+    check_trigger_owner(pool.get_ref(), *id, user.id).await?;
+    let name = normalize_trigger_name(&trigger.name)?;
+    check_trigger_name_available(pool.get_ref(), user.id, &name, Some(*id)).await?;
+
+    let updated = sqlx::query_as!(
+        Trigger,
+        r#"
+        UPDATE triggers SET name = $1
+        WHERE id = $2
+        RETURNING id, user_id, name, created_at
+        "#,
+        name,
+        *id
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    Ok(HttpResponse::Ok().json(updated))
+}
+
+async fn delete_trigger(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    id: web::Path<i32>,
+) -> Result<HttpResponse, HttpError> {
+    check_trigger_owner(pool.get_ref(), *id, user.id).await?;
+
+    sqlx::query!("DELETE FROM triggers WHERE id = $1", *id)
+        .execute(pool.get_ref())
+        .await?;
+
+    Ok(HttpResponse::NoContent().finish())
+}
+
+// Fila de exportación: el registro con su nivel de ansiedad y los nombres de sus desencadenantes
+struct MoodExportRow {
+    id: i32,
+    record_date: Option<NaiveDateTime>,
+    mood_score: Option<i32>,
+    notes: Option<String>,
+    anxiety_level_id: Option<i32>,
+    anxiety_level: Option<String>,
+    triggers: Vec<String>,
+}
+
+async fn export_mood_records(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    range: web::Query<GetMoodRecordsQuery>,
+    export: web::Query<ExportMoodQuery>,
+) -> Result<HttpResponse, HttpError> {
+    let (start, end) = range.date_range()?;
+    let format = export.format;
+    let pool = pool.get_ref().clone();
+    let user = user.into_inner();
+
+    // La consulta se ejecuta en su propia tarea y envía los fragmentos por un canal
+    // acotado, de modo que el historial nunca se carga entero en memoria
+    let (tx, rx) = mpsc::channel::<Result<web::Bytes, actix_web::Error>>(EXPORT_CHANNEL_CAPACITY);
+    actix_web::rt::spawn(async move {
+        if let Err(e) = stream_mood_export(&pool, &user, start, end, format, &tx).await {
+            error!("Error al exportar registros de estado de ánimo: {}", e);
+            let _ = tx
+                .send(Err(actix_web::error::ErrorInternalServerError("Export failed")))
+                .await;
+        }
+    });
+
+    let body = stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|chunk| (chunk, rx))
+    });
+
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((
+            header::CONTENT_DISPOSITION,
+            format!("attachment; filename=\"{}\"", format.file_name()),
+        ))
+        .streaming(body))
+}
+
+const EXPORT_CHANNEL_CAPACITY: usize = 32;
+
+async fn stream_mood_export(
+    pool: &DbPool,
+    user: &User,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    format: ExportFormat,
+    tx: &mpsc::Sender<Result<web::Bytes, actix_web::Error>>,
+) -> Result<(), sqlx::Error> {
+    let mut rows = sqlx::query_as!(
+        MoodExportRow,
+        r#"
+        SELECT
+            m.id, m.record_date, m.mood_score, m.notes,
+            m.anxiety_level_id, al.name as "anxiety_level?",
+            COALESCE(
+                (SELECT ARRAY_AGG(t.name ORDER BY t.name)
+                 FROM mood_record_triggers mt
+                 JOIN triggers t ON t.id = mt.trigger_id
+                 WHERE mt.mood_record_id = m.id),
+                '{}'
+            ) as "triggers!"
+        FROM mood_records m
+        LEFT JOIN anxiety_levels al ON m.anxiety_level_id = al.id
+        WHERE m.user_id = $1
+          AND ($2::timestamp IS NULL OR m.record_date >= $2)
+          AND ($3::timestamp IS NULL OR m.record_date < $3)
+        ORDER BY m.record_date, m.id
+        "#,
+        user.id,
+        start,
+        end
+    )
+    .fetch(pool);
+
+    // Si el cliente se desconecta el canal se cierra y se deja de leer
+    let mut first = true;
+    if tx.send(Ok(format.header().into())).await.is_err() {
+        return Ok(());
+    }
+
+    while let Some(row) = rows.try_next().await? {
+        let chunk = format.row(user, &row, first);
+        first = false;
+        if tx.send(Ok(chunk.into())).await.is_err() {
+            return Ok(());
+        }
+    }
+
+    let _ = tx.send(Ok(format.footer().into())).await;
+    Ok(())
+}
+
+impl ExportFormat {
+    fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv; charset=utf-8",
+            ExportFormat::Json => "application/json",
+            ExportFormat::Fhir => "application/fhir+json",
+        }
+    }
+
+    fn file_name(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "mood-records.csv",
+            ExportFormat::Json => "mood-records.json",
+            ExportFormat::Fhir => "mood-records.fhir.json",
+        }
+    }
+
+    fn header(self) -> String {
+        match self {
+            ExportFormat::Csv => csv::write_record(&MOOD_CSV_COLUMNS),
+            ExportFormat::Json => "[".to_string(),
+            // El Bundle se abre a mano para ir añadiendo las entradas conforme llegan
+            ExportFormat::Fhir => format!(
+                r#"{{"resourceType":"Bundle","id":"{}","type":"collection","timestamp":"{}","entry":["#,
+                Uuid::new_v4(),
+                now_utc().to_rfc3339()
+            ),
+        }
+    }
+
+    fn row(self, user: &User, row: &MoodExportRow, first: bool) -> String {
+        let separator = if first { "" } else { "," };
+        let record_date = naive_opt_to_utc(row.record_date).map(|dt| dt.to_rfc3339());
+
+        match self {
+            ExportFormat::Csv => csv::write_record(&[
+                record_date.unwrap_or_default(),
+                row.mood_score.map(|score| score.to_string()).unwrap_or_default(),
+                row.anxiety_level.clone().unwrap_or_default(),
+                row.notes.clone().unwrap_or_default(),
+                row.triggers.join(";"),
+            ]),
+            ExportFormat::Json => format!(
+                "{}{}",
+                separator,
+                json!({
+                    "id": row.id,
+                    "record_date": record_date,
+                    "mood_score": row.mood_score,
+                    "anxiety_level_id": row.anxiety_level_id,
+                    "anxiety_level": row.anxiety_level,
+                    "notes": row.notes,
+                    "triggers": row.triggers,
+                })
+            ),
+            ExportFormat::Fhir => format!(
+                "{}{}",
+                separator,
+                json!({
+                    "fullUrl": format!("urn:uuid:{}", Uuid::new_v4()),
+                    "resource": fhir_observation(user, row, record_date),
+                })
+            ),
+        }
+    }
+
+    fn footer(self) -> String {
+        match self {
+            ExportFormat::Csv => String::new(),
+            ExportFormat::Json => "]".to_string(),
+            ExportFormat::Fhir => "]}".to_string(),
+        }
+    }
+}
+
+const MOOD_CSV_COLUMNS: [&str; 5] = ["date", "score", "anxiety_level", "notes", "triggers"];
+
+const FHIR_CODE_SYSTEM: &str = "urn:anxiety:mood";
+
+/// Observation de FHIR R4 para un registro: la puntuación va en `valueInteger` y el
+/// nivel de ansiedad y los desencadenantes como componentes.
+fn fhir_observation(user: &User, row: &MoodExportRow, record_date: Option<String>) -> serde_json::Value {
+    let mut components = Vec::new();
+
+    if let Some(level) = &row.anxiety_level {
+        components.push(json!({
+            "code": {
+                "coding": [{ "system": FHIR_CODE_SYSTEM, "code": "anxiety-level", "display": "Nivel de ansiedad" }],
+                "text": "Nivel de ansiedad"
+            },
+            "valueCodeableConcept": {
+                "coding": [{
+                    "system": format!("{}/anxiety-levels", FHIR_CODE_SYSTEM),
+                    "code": row.anxiety_level_id.map(|id| id.to_string()),
+                    "display": level
+                }],
+                "text": level
+            }
+        }));
+    }
+
+    for trigger in &row.triggers {
+        components.push(json!({
+            "code": {
+                "coding": [{ "system": FHIR_CODE_SYSTEM, "code": "trigger", "display": "Desencadenante" }],
+                "text": "Desencadenante"
+            },
+            "valueString": trigger
+        }));
+    }
+
+    let mut observation = json!({
+        "resourceType": "Observation",
+        "id": format!("mood-record-{}", row.id),
+        "status": "final",
+        "category": [{
+            "coding": [{
+                "system": "http://terminology.hl7.org/CodeSystem/observation-category",
+                "code": "survey",
+                "display": "Survey"
+            }]
+        }],
+        "code": {
+            "coding": [{ "system": FHIR_CODE_SYSTEM, "code": "mood-score", "display": "Puntuación del estado de ánimo" }],
+            "text": "Puntuación del estado de ánimo (1-10)"
+        },
+        "subject": {
+            "identifier": { "system": "urn:anxiety:user", "value": user.id.to_string() },
+            "display": user.name
+        },
+        "effectiveDateTime": record_date,
+        "valueInteger": row.mood_score,
+        "component": components,
+    });
+
+    if let Some(notes) = &row.notes {
+        observation["note"] = json!([{ "text": notes }]);
+    }
+
+    observation
+}
+
+// Fila del CSV ya validada y lista para insertar
+struct MoodImportRow {
+    record_date: NaiveDateTime,
+    mood_score: i32,
+    anxiety_level_id: Option<i32>,
+    notes: Option<String>,
+    triggers: Vec<String>,
+}
+
+async fn import_mood_records(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    query: web::Query<ImportMoodQuery>,
+    body: String,
+) -> Result<HttpResponse, HttpError> {
+    let records = csv::parse(&body, detect_delimiter(&body)).map_err(HttpError::bad_request)?;
+    let mut records = records.into_iter();
+    let header = records
+        .next()
+        .ok_or_else(|| HttpError::bad_request("The CSV file is empty"))?;
+    let columns = MoodImportColumns::from_header(&header.fields)?;
+
+    if records.len() > MAX_IMPORT_ROWS {
+        return Err(HttpError::bad_request(format!(
+            "The CSV file has more than {} rows",
+            MAX_IMPORT_ROWS
+        )));
+    }
+
+    let levels: HashMap<String, i32> = sqlx::query!("SELECT id, name FROM anxiety_levels")
+        .fetch_all(pool.get_ref())
+        .await?
+        .into_iter()
+        .map(|level| (level.name.to_lowercase(), level.id))
+        .collect();
+
+    let mut rows = Vec::new();
+    let mut errors = Vec::new();
+    let mut total_rows = 0;
+
+    for record in records {
+        total_rows += 1;
+        match columns.parse_row(&record.fields, &levels) {
+            Ok(row) => rows.push(row),
+            Err(row_errors) => errors.push(MoodImportRowError {
+                line: record.line,
+                errors: row_errors,
+            }),
+        }
+    }
+
+    let mut report = MoodImportReport {
+        dry_run: !query.commit,
+        total_rows,
+        valid_rows: rows.len(),
+        imported: 0,
+        errors,
+    };
+
+    if !query.commit {
+        return Ok(HttpResponse::Ok().json(report));
+    }
+
+    // La importación es todo o nada: con una sola fila inválida no se inserta nada
+    if !report.errors.is_empty() {
+        return Ok(HttpResponse::UnprocessableEntity().json(report));
+    }
+
+    let mut tx = pool.begin().await?;
+
+    let names: Vec<String> = rows.iter().flat_map(|row| row.triggers.iter().cloned()).collect();
+    let trigger_ids_by_name = resolve_trigger_names(&mut tx, user.id, &names).await?;
+
+    for row in &rows {
+        let id = sqlx::query_scalar!(
+            r#"
+            INSERT INTO mood_records (user_id, anxiety_level_id, record_date, notes, mood_score)
+            VALUES ($1, $2, $3, $4, $5)
+            RETURNING id
+            "#,
+            user.id,
+            row.anxiety_level_id,
+            row.record_date,
+            row.notes,
+            row.mood_score
+        )
+        .fetch_one(&mut *tx)
+        .await?;
+
+        let mut trigger_ids = Vec::with_capacity(row.triggers.len());
+        for name in &row.triggers {
+            let id = *trigger_ids_by_name
+                .get(&name.to_lowercase())
+                .ok_or(HttpError::InternalServerError)?;
+            if !trigger_ids.contains(&id) {
+                trigger_ids.push(id);
+            }
+        }
+        set_record_triggers(&mut tx, id, &trigger_ids).await?;
+    }
+
+    tx.commit().await?;
+    report.imported = rows.len();
+
+    Ok(HttpResponse::Created().json(report))
+}
+
+const MAX_IMPORT_BYTES: usize = 5 * 1024 * 1024;
+const MAX_IMPORT_ROWS: usize = 20_000;
+
+// Las hojas de cálculo en español suelen exportar con punto y coma
+fn detect_delimiter(body: &str) -> char {
+    let header = body.lines().next().unwrap_or_default();
+    [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|delimiter| header.matches(*delimiter).count())
+        .unwrap_or(',')
+}
+
+/// Posición de cada columna en el CSV, deducida de la cabecera. Se aceptan los
+/// nombres que usan otras aplicaciones de seguimiento además de los de la exportación.
+struct MoodImportColumns {
+    date: usize,
+    score: usize,
+    anxiety_level: Option<usize>,
+    notes: Option<usize>,
+    triggers: Option<usize>,
+}
+
+impl MoodImportColumns {
+    fn from_header(header: &[String]) -> Result<Self, HttpError> {
+        let names: Vec<String> = header
+            .iter()
+            .map(|name| name.trim().to_lowercase().replace([' ', '-'], "_"))
+            .collect();
+        let find = |aliases: &[&str]| names.iter().position(|name| aliases.contains(&name.as_str()));
+
+        let date = find(&["date", "record_date", "datetime", "timestamp", "fecha"])
+            .ok_or_else(|| HttpError::bad_request("The CSV header must include a 'date' column"))?;
+        let score = find(&["score", "mood_score", "mood", "rating", "puntuacion", "puntuación"])
+            .ok_or_else(|| HttpError::bad_request("The CSV header must include a 'score' column"))?;
+
+        Ok(MoodImportColumns {
+            date,
+            score,
+            anxiety_level: find(&["anxiety_level", "anxiety", "nivel_de_ansiedad", "nivel_ansiedad", "ansiedad"]),
+            notes: find(&["notes", "note", "comments", "comment", "notas", "nota"]),
+            triggers: find(&["triggers", "trigger", "tags", "activities", "desencadenantes"]),
+        })
+    }
+
+    fn parse_row(
+        &self,
+        fields: &[String],
+        levels: &HashMap<String, i32>,
+    ) -> Result<MoodImportRow, Vec<String>> {
+        let field = |index: Option<usize>| {
+            index
+                .and_then(|index| fields.get(index))
+                .map(|value| value.trim())
+                .unwrap_or_default()
+        };
+        let mut errors = Vec::new();
+
+        let date = field(Some(self.date));
+        let record_date = match parse_record_date(date) {
+            Some(record_date) if record_date > now_utc().naive_utc() => {
+                errors.push(format!("date '{}' is in the future", date));
+                None
+            }
+            Some(record_date) => Some(record_date),
+            None => {
+                errors.push(format!("invalid date '{}'", date));
+                None
+            }
+        };
+
+        // Debe coincidir con CHECK (mood_score BETWEEN 1 AND 10)
+        let score = field(Some(self.score));
+        let mood_score = match score.parse::<i32>() {
+            Ok(mood_score) if (1..=10).contains(&mood_score) => Some(mood_score),
+            Ok(_) => {
+                errors.push(format!("score {} must be between 1 and 10", score));
+                None
+            }
+            Err(_) => {
+                errors.push(format!("invalid score '{}'", score));
+                None
+            }
+        };
+
+        let level = field(self.anxiety_level);
+        let anxiety_level_id = if level.is_empty() {
+            None
+        } else {
+            match levels.get(&level.to_lowercase()) {
+                Some(id) => Some(*id),
+                None => {
+                    errors.push(format!("unknown anxiety level '{}'", level));
+                    None
+                }
+            }
+        };
+
+        let notes = field(self.notes);
+
+        let mut triggers = Vec::new();
+        for name in field(self.triggers).split([';', ',', '|']) {
+            if name.trim().is_empty() {
+                continue;
+            }
+            match normalize_trigger_name(name) {
+                Ok(name) => triggers.push(name),
+                Err(_) => errors.push(format!("trigger '{}' is longer than 100 characters", name.trim())),
+            }
+        }
+
+        match (record_date, mood_score) {
+            (Some(record_date), Some(mood_score)) if errors.is_empty() => Ok(MoodImportRow {
+                record_date,
+                mood_score,
+                anxiety_level_id,
+                notes: (!notes.is_empty()).then(|| notes.to_string()),
+                triggers,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+fn parse_record_date(value: &str) -> Option<NaiveDateTime> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Some(dt.naive_utc());
+    }
+
+    ["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(value, format).ok())
+        .or_else(|| {
+            ["%Y-%m-%d", "%d/%m/%Y"]
+                .iter()
+                .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+                .and_then(|date| date.and_hms_opt(0, 0, 0))
+        })
 }
 
 async fn get_user_stats(
@@ -98,6 +1303,71 @@ async fn get_user_preferences(
 pub struct GetMoodRecordsQuery {
     pub start_date: Option<String>,
     pub end_date: Option<String>,
-    pub page: Option<i32>,
-    pub limit: Option<i32>,
+}
+
+impl GetMoodRecordsQuery {
+    /// Devuelve el rango `[start, end)` listo para comparar con `record_date`.
+    ///
+    /// Acepta fechas `YYYY-MM-DD` o RFC 3339. Una `end_date` sin hora incluye el día completo.
+    pub fn date_range(&self) -> Result<(Option<NaiveDateTime>, Option<NaiveDateTime>), HttpError> {
+        let start = self.start_date
+            .as_deref()
+            .map(|s| parse_date_bound(s, false))
+            .transpose()?;
+        let end = self.end_date
+            .as_deref()
+            .map(|s| parse_date_bound(s, true))
+            .transpose()?;
+
+        if let (Some(start), Some(end)) = (start, end) {
+            if start >= end {
+                return Err(HttpError::validation("start_date must be before end_date"));
+            }
+        }
+
+        Ok((start, end))
+    }
+}
+
+fn parse_date_bound(value: &str, is_end: bool) -> Result<NaiveDateTime, HttpError> {
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.naive_utc());
+    }
+
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(date) => {
+            let start_of_day = date.and_hms_opt(0, 0, 0).ok_or(HttpError::InternalServerError)?;
+            if is_end {
+                Ok(start_of_day + Duration::days(1))
+            } else {
+                Ok(start_of_day)
+            }
+        }
+        Err(_) => Err(HttpError::validation(format!(
+            "Invalid date '{}', expected YYYY-MM-DD or RFC 3339",
+            value
+        ))),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExportMoodQuery {
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Json,
+    Fhir,
+}
+
+#[derive(Deserialize)]
+pub struct ImportMoodQuery {
+    /// Sin `commit=true` solo se valida el fichero (dry run)
+    #[serde(default)]
+    pub commit: bool,
 }
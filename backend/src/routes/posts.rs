@@ -1,24 +1,40 @@
-use actix_web::{web, HttpResponse, Responder};
+use actix_web::{web, HttpResponse, Responder, ResponseError};
 use crate::models::posts::{Post, PostCreate, PostUpdate};
-use crate::models::categories::{Category, Tag};
+use crate::models::categories::Tag;
 use crate::models::User;
 use crate::db::DbPool;
+use crate::middleware::require_verified_email;
+use crate::routes::categories::{self, resolve_category};
+use crate::AppState;
+use crate::utils::error::HttpError;
+use crate::utils::cursor::{self, Cursor};
 use crate::utils::datetime::{datetime_to_utc, datetime_opt_to_utc, now_utc};
+use crate::utils::pagination::{self, Pagination};
 use chrono::{DateTime, Utc};
 use log::error;
 use serde::Deserialize;
 use serde_json::json;
-use sqlx::Row;
+use sqlx::postgres::PgRow;
+use sqlx::{PgExecutor, Postgres, Row, Transaction};
+use std::collections::HashMap;
+
+// Los cursores del feed solo valen para el feed
+const POSTS_CURSOR_SCOPE: &str = "posts";
+
+const MAX_TAGS_PER_POST: usize = 10;
+// Igual que tags.name
+const MAX_TAG_LENGTH: usize = 50;
 
 pub fn configure(cfg: &mut web::ServiceConfig) {
     // Definimos rutas explícitamente para el endpoint de posts
     cfg.route("", web::get().to(get_posts))
        .route("", web::post().to(create_post))
+       // Antes de "{id}", que si no las captura
+       .configure(categories::configure)
+       .route("tags", web::get().to(get_tags))
        .route("{id}", web::get().to(get_post))
        .route("{id}", web::put().to(update_post))
        .route("{id}", web::delete().to(delete_post))
-       .route("categories", web::get().to(get_categories))
-       .route("tags", web::get().to(get_tags))
        .route("{id}/like", web::post().to(like_post))
        .route("{id}/save", web::post().to(save_post));
 }
@@ -33,14 +49,14 @@ pub async fn get_post(
         SELECT 
             p.id, p.title, p.content, p.category, p.created_at, p.updated_at,
             p.likes_count, p.comments_count,
-            u.id as user_id, u.name, u.avatar,
+            u.id as "user_id?", u.name, u.avatar,
             CASE WHEN pl.user_id IS NOT NULL THEN true ELSE false END as is_liked,
             CASE WHEN ps.user_id IS NOT NULL THEN true ELSE false END as is_saved
         FROM posts p
-        JOIN users u ON p.user_id = u.id
+        LEFT JOIN users u ON p.user_id = u.id
         LEFT JOIN post_likes pl ON p.id = pl.post_id AND pl.user_id = $1
         LEFT JOIN post_saves ps ON p.id = ps.post_id AND ps.user_id = $1
-        WHERE p.id = $2 AND u.is_active = true
+        WHERE p.id = $2 AND (p.user_id IS NULL OR u.is_active = true)
         "#,
         user.id,
         *id
@@ -59,17 +75,8 @@ pub async fn get_post(
             
             // Construir respuesta JSON
             // Obtener etiquetas (tags) asociadas con este post
-            let tags = match sqlx::query!(r#"
-                SELECT t.name
-                FROM tags t
-                JOIN post_tags pt ON t.id = pt.tag_id
-                WHERE pt.post_id = $1
-                "#,
-                post.id
-            )
-            .fetch_all(pool.get_ref())
-            .await {
-                Ok(tags) => tags.into_iter().map(|t| t.name).collect::<Vec<String>>(),
+            let tags = match fetch_post_tags(pool.get_ref(), &[post.id]).await {
+                Ok(mut tags) => tags.remove(&post.id).unwrap_or_default(),
                 Err(_) => vec![] // Si hay error, devolver lista vacía
             };
             
@@ -108,62 +115,125 @@ pub async fn get_post(
     }
 }
 
-pub async fn get_categories() -> impl Responder {
-    // Return some default categories for now
-    let now = Utc::now();
-    let categories = vec![
-        Category { 
-            id: 1, 
-            name: String::from("Ansiedad"),
-            description: Some(String::from("Categoría para temas relacionados con la ansiedad")),
-            created_at: now,
-            updated_at: now
-        },
-        Category { 
-            id: 2, 
-            name: String::from("Depresión"),
-            description: Some(String::from("Categoría para temas relacionados con la depresión")),
-            created_at: now,
-            updated_at: now
-        },
-        Category { 
-            id: 3, 
-            name: String::from("Estrés"),
-            description: Some(String::from("Categoría para temas relacionados con el estrés")),
-            created_at: now,
-            updated_at: now
-        },
-    ];
-    HttpResponse::Ok().json(categories)
+pub async fn get_tags(pool: web::Data<DbPool>) -> impl Responder {
+    // Solo las etiquetas que usa algún post; las que se quedan sin posts al editar no se listan
+    match sqlx::query_as!(
+        Tag,
+        r#"
+        SELECT t.id, t.name, t.description, t.created_at, t.updated_at
+        FROM tags t
+        WHERE EXISTS (SELECT 1 FROM post_tags pt WHERE pt.tag_id = t.id)
+        ORDER BY t.name
+        "#
+    )
+    .fetch_all(pool.get_ref())
+    .await {
+        Ok(tags) => HttpResponse::Ok().json(tags),
+        Err(e) => {
+            error!("Error al obtener las etiquetas: {}", e);
+            HttpResponse::InternalServerError()
+                .json(json!({ "error": "Error al obtener las etiquetas" }))
+        }
+    }
 }
 
-pub async fn get_tags() -> impl Responder {
-    // Return some default tags for now
-    let now = Utc::now();
-    let tags = vec![
-        Tag { 
-            id: 1, 
-            name: String::from("autoayuda"),
-            description: Some(String::from("Etiqueta para contenido de autoayuda")),
-            created_at: now,
-            updated_at: now
-        },
-        Tag { 
-            id: 2, 
-            name: String::from("consejos"),
-            description: Some(String::from("Etiqueta para consejos útiles")),
-            created_at: now,
-            updated_at: now
-        },
-        Tag { 
-            id: 3, 
-            name: String::from("experiencia"),
-            description: Some(String::from("Etiqueta para experiencias personales")),
-            created_at: now,
-            updated_at: now
-        },
-    ];
-    HttpResponse::Ok().json(tags)
+fn unknown_category(category: &str) -> HttpResponse {
+    HttpError::validation(format!("Unknown category '{}'", category.trim())).error_response()
+}
+
+/// Limpia las etiquetas que envía el cliente: sin `#` delante, en minúsculas, con los
+/// espacios normalizados y sin repetir.
+fn normalize_tags(tags: &[String]) -> Result<Vec<String>, HttpError> {
+    let mut normalized: Vec<String> = Vec::new();
+
+    for tag in tags {
+        let tag = tag
+            .trim()
+            .trim_start_matches('#')
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+            .to_lowercase();
+        if tag.is_empty() || normalized.contains(&tag) {
+            continue;
+        }
+        if tag.chars().count() > MAX_TAG_LENGTH {
+            return Err(HttpError::validation(format!(
+                "Tags cannot be longer than {} characters",
+                MAX_TAG_LENGTH
+            )));
+        }
+        normalized.push(tag);
+    }
+
+    if normalized.len() > MAX_TAGS_PER_POST {
+        return Err(HttpError::validation(format!(
+            "A post cannot have more than {} tags",
+            MAX_TAGS_PER_POST
+        )));
+    }
+
+    Ok(normalized)
+}
+
+// Sustituye las etiquetas del post en la misma transacción, creando las que no existan.
+async fn set_post_tags(
+    tx: &mut Transaction<'_, Postgres>,
+    post_id: i32,
+    tags: &[String],
+) -> Result<(), sqlx::Error> {
+    sqlx::query!("DELETE FROM post_tags WHERE post_id = $1", post_id)
+        .execute(&mut **tx)
+        .await?;
+
+    if tags.is_empty() {
+        return Ok(());
+    }
+
+    sqlx::query!(
+        "INSERT INTO tags (name) SELECT UNNEST($1::text[]) ON CONFLICT (name) DO NOTHING",
+        tags
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO post_tags (post_id, tag_id)
+        SELECT $1, id FROM tags WHERE name = ANY($2)
+        "#,
+        post_id,
+        tags
+    )
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(())
+}
+
+// Etiquetas de varios posts en una sola consulta, ordenadas por nombre.
+async fn fetch_post_tags(
+    executor: impl PgExecutor<'_>,
+    post_ids: &[i32],
+) -> Result<HashMap<i32, Vec<String>>, sqlx::Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT pt.post_id, t.name
+        FROM post_tags pt
+        JOIN tags t ON t.id = pt.tag_id
+        WHERE pt.post_id = ANY($1)
+        ORDER BY t.name
+        "#,
+        post_ids
+    )
+    .fetch_all(executor)
+    .await?;
+
+    let mut tags: HashMap<i32, Vec<String>> = HashMap::new();
+    for row in rows {
+        tags.entry(row.post_id).or_default().push(row.name);
+    }
+    Ok(tags)
 }
 
 #[derive(serde::Serialize)]
@@ -173,32 +243,108 @@ pub struct PostsResponse {
 
 pub async fn get_posts(
     pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
     user: web::ReqData<User>,
-    _query: web::Query<GetPostsQuery>,
+    query: web::Query<GetPostsQuery>,
+    pagination: web::Query<Pagination>,
 ) -> impl Responder {
+    let (page, limit) = pagination.resolve(10, 100);
+    // Con cursor se pagina por posición y `page` no se usa
+    let cursor = match query.cursor.as_deref().map(|c| Cursor::decode(c, &data.config.jwt_secret, POSTS_CURSOR_SCOPE)) {
+        Some(Ok(cursor)) => Some(cursor),
+        Some(Err(e)) => return e.error_response(),
+        None => None,
+    };
+    let offset = if cursor.is_some() { 0 } else { pagination::offset(page, limit) };
+    let category = query.category.as_deref().map(str::trim).filter(|c| !c.is_empty());
+    let tag = query.tag.as_deref().map(str::trim).filter(|t| !t.is_empty());
+
+    let total = match sqlx::query_scalar!(
+        r#"
+        SELECT COUNT(*) as "count!"
+        FROM posts p
+        LEFT JOIN users u ON p.user_id = u.id
+        WHERE (p.user_id IS NULL OR u.is_active = true)
+          AND ($1::text IS NULL OR LOWER(p.category) = LOWER($1))
+          AND ($2::text IS NULL OR EXISTS (
+              SELECT 1 FROM post_tags pt JOIN tags t ON t.id = pt.tag_id
+              WHERE pt.post_id = p.id AND LOWER(t.name) = LOWER($2)
+          ))
+        "#,
+        category,
+        tag
+    )
+    .fetch_one(pool.get_ref())
+    .await {
+        Ok(total) => total,
+        Err(e) => {
+            error!("Error al contar los posts: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": "Error al obtener los posts" }));
+        }
+    };
+
     // Consultar los posts con información del autor e información personalizada para el usuario actual
     match sqlx::query!(
         r#"
         SELECT 
             p.id, p.title, p.content, p.category, p.created_at, p.updated_at,
             p.likes_count, p.comments_count,
-            u.id as user_id, u.name, u.avatar,
+            u.id as "user_id?", u.name, u.avatar,
             CASE WHEN pl.user_id IS NOT NULL THEN true ELSE false END as is_liked,
             CASE WHEN ps.user_id IS NOT NULL THEN true ELSE false END as is_saved
         FROM posts p
-        JOIN users u ON p.user_id = u.id
+        LEFT JOIN users u ON p.user_id = u.id
         LEFT JOIN post_likes pl ON p.id = pl.post_id AND pl.user_id = $1
         LEFT JOIN post_saves ps ON p.id = ps.post_id AND ps.user_id = $1
-        WHERE u.is_active = true
-        ORDER BY p.created_at DESC
+        WHERE (p.user_id IS NULL OR u.is_active = true)
+          AND ($2::text IS NULL OR LOWER(p.category) = LOWER($2))
+          AND ($3::text IS NULL OR EXISTS (
+              SELECT 1 FROM post_tags pt JOIN tags t ON t.id = pt.tag_id
+              WHERE pt.post_id = p.id AND LOWER(t.name) = LOWER($3)
+          ))
+          AND ($4::timestamptz IS NULL
+               OR (NOT $6::bool AND (p.created_at, p.id) < ($4, $5::int))
+               OR ($6::bool AND (p.created_at, p.id) > ($4, $5::int)))
+        ORDER BY
+            CASE WHEN $6::bool THEN p.created_at END ASC,
+            CASE WHEN $6::bool THEN p.id END ASC,
+            p.created_at DESC, p.id DESC
+        LIMIT $7 OFFSET $8
         "#,
-        user.id
+        user.id,
+        category,
+        tag,
+        cursor.map(|c| c.created_at),
+        cursor.map(|c| c.id),
+        Cursor::is_prev(cursor.as_ref()),
+        limit as i64 + 1,
+        offset
     )
     .fetch_all(pool.get_ref())
     .await {
         Ok(records) => {
+            let page_of = cursor::paginate(
+                records,
+                limit as usize,
+                cursor.as_ref(),
+                &data.config.jwt_secret,
+                POSTS_CURSOR_SCOPE,
+                |record| (record.created_at.unwrap_or_default(), record.id, false),
+            );
+
+            let post_ids: Vec<i32> = page_of.items.iter().map(|record| record.id).collect();
+            let mut tags = match fetch_post_tags(pool.get_ref(), &post_ids).await {
+                Ok(tags) => tags,
+                Err(e) => {
+                    error!("Error al obtener las etiquetas de los posts: {}", e);
+                    return HttpResponse::InternalServerError()
+                        .json(json!({ "error": "Error al obtener los posts" }));
+                }
+            };
+
             // Mapear los resultados al formato que espera el frontend
-            let posts: Vec<serde_json::Value> = records.into_iter().map(|record| {
+            let posts: Vec<serde_json::Value> = page_of.items.into_iter().map(|record| {
                 // Formatear la fecha correctamente
                 let date_str = record.created_at
                     .map(|dt| dt.format("%Y-%m-%dT%H:%M:%S%.3fZ").to_string())
@@ -217,7 +363,7 @@ pub async fn get_posts(
                     "likes": record.likes_count,
                     "comments": record.comments_count,
                     "category": record.category,
-                    "tags": [], // Por ahora vacío, se puede poblar con otra consulta si es necesario
+                    "tags": tags.remove(&record.id).unwrap_or_default(),
                     "isLiked": record.is_liked,
                     "isSaved": record.is_saved
                 })
@@ -226,9 +372,11 @@ pub async fn get_posts(
             // Crear la respuesta con paginación
             let response = json!({
                 "posts": posts,
-                "total": posts.len(),
-                "page": 1,
-                "per_page": 10
+                "total": total,
+                "page": if cursor.is_some() { None } else { Some(page) },
+                "per_page": limit,
+                "next_cursor": page_of.next_cursor,
+                "prev_cursor": page_of.prev_cursor
             });
 
             HttpResponse::Ok()
@@ -245,30 +393,39 @@ pub async fn get_posts(
 
 pub async fn create_post(
     pool: web::Data<DbPool>,
+    data: web::Data<AppState>,
     user: web::ReqData<User>,
-    post: web::Json<PostCreate>,
+    mut post: web::Json<PostCreate>,
 ) -> impl Responder {
+    if let Err(e) = require_verified_email(&data.config, &user) {
+        return e.error_response();
+    }
 
     //Log
     log::info!("Creating post: {:?}", post);
 
+    let tags = match normalize_tags(&post.tags) {
+        Ok(tags) => tags,
+        Err(e) => return e.error_response(),
+    };
+
+    // Se guarda con el nombre de la categoría tal y como está en la tabla
+    post.category = match resolve_category(pool.get_ref(), &post.category).await {
+        Ok(Some(category)) => category,
+        Ok(None) => return unknown_category(&post.category),
+        Err(e) => {
+            error!("Error al comprobar la categoría: {}", e);
+            return HttpResponse::InternalServerError()
+                .json(json!({ "error": "Error al crear el post" }));
+        }
+    };
+
     // Obtener el ID del usuario autenticado
     let user_id = user.id;
     
-    // Insertar el nuevo post en la base de datos
-    match sqlx::query(
-        r#"
-        INSERT INTO posts (user_id, title, content, category)
-        VALUES ($1, $2, $3, $4)
-        RETURNING id, created_at, updated_at, likes_count, comments_count
-        "#)
-        .bind(user_id)
-        .bind(&post.0.post_title)
-        .bind(&post.0.content)
-        .bind(&post.0.category)
-        .fetch_one(pool.get_ref())
-        .await {
-        Ok(record) => {
+    // Insertar el nuevo post y sus etiquetas en la misma transacción
+    match insert_post(pool.get_ref(), user_id, &post.0, &tags).await {
+        Ok((record, tags)) => {
             let id: i32 = record.get("id");
             let created_at: DateTime<Utc> = record.get("created_at");
             let updated_at: Option<DateTime<Utc>> = record.get("updated_at");
@@ -286,6 +443,7 @@ pub async fn create_post(
                 updated_at,
                 likes_count,
                 comments_count,
+                tags,
             };
             
             HttpResponse::Created()
@@ -300,13 +458,42 @@ pub async fn create_post(
     }
 }
 
+async fn insert_post(
+    pool: &DbPool,
+    user_id: i32,
+    post: &PostCreate,
+    tags: &[String],
+) -> Result<(PgRow, Vec<String>), sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    let record = sqlx::query(
+        r#"
+        INSERT INTO posts (user_id, title, content, category)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id, created_at, updated_at, likes_count, comments_count
+        "#)
+        .bind(user_id)
+        .bind(&post.post_title)
+        .bind(&post.content)
+        .bind(&post.category)
+        .fetch_one(&mut *tx)
+        .await?;
+
+    let id: i32 = record.get("id");
+    set_post_tags(&mut tx, id, tags).await?;
+    let tags = fetch_post_tags(&mut *tx, &[id]).await?.remove(&id).unwrap_or_default();
+
+    tx.commit().await?;
+    Ok((record, tags))
+}
+
 // Esta función fue eliminada por estar duplicada, se mantiene la primera implementación
 
 pub async fn update_post(
     pool: web::Data<DbPool>,
     user: web::ReqData<User>,
     id: web::Path<i32>,
-    post: web::Json<PostUpdate>,
+    mut post: web::Json<PostUpdate>,
 ) -> impl Responder {
     // Verificar que el usuario sea el propietario del post
     match sqlx::query_scalar!(
@@ -316,45 +503,25 @@ pub async fn update_post(
     .fetch_optional(pool.get_ref())
     .await {
         Ok(Some(Some(post_user_id))) if post_user_id == user.id => {
+            let tags = match post.0.tags.as_deref().map(normalize_tags).transpose() {
+                Ok(tags) => tags,
+                Err(e) => return e.error_response(),
+            };
+
+            if let Some(category) = post.0.category.take() {
+                post.0.category = match resolve_category(pool.get_ref(), &category).await {
+                    Ok(Some(category)) => Some(category),
+                    Ok(None) => return unknown_category(&category),
+                    Err(e) => {
+                        error!("Error checking category: {}", e);
+                        return HttpResponse::InternalServerError().json(json!({ "error": "Failed to update post" }));
+                    }
+                };
+            }
+
             // El usuario es el propietario, proceder con la actualización
-            match sqlx::query!(
-                r#"
-                UPDATE posts
-                SET title = $1, content = $2, category = $3, updated_at = NOW()
-                WHERE id = $4
-                RETURNING id, user_id, title, content, category, created_at, updated_at, likes_count, comments_count
-                "#,
-                post.0.title,  // Cambiado de post.0.post_title a post.0.title
-                post.0.content,
-                post.0.category,
-                *id
-            )
-            .fetch_optional(pool.get_ref())
-            .await {
-                Ok(Some(record)) => {
-                    let created_at = match record.created_at {
-                        Some(dt) => dt, // Ya es DateTime<Utc>
-                        None => {
-                            error!("Post {} has no created_at timestamp", record.id);
-                            return HttpResponse::InternalServerError()
-                                .json(json!({ "error": "Post has invalid creation date" }));
-                        }
-                    };
-                    
-                    // El campo updated_at ya es Option<DateTime<Utc>>
-                    let updated_at = record.updated_at;
-                    
-                    let updated_post = Post {
-                        id: record.id,
-                        user_id: record.user_id.unwrap_or_default(),
-                        title: record.title.unwrap_or_else(|| String::from("Untitled")),
-                        content: record.content.unwrap_or_default(),
-                        category: record.category.unwrap_or_else(|| String::from("general")),
-                        created_at,
-                        updated_at,
-                        likes_count: record.likes_count.unwrap_or(0),
-                        comments_count: record.comments_count.unwrap_or(0),
-                    };
+            match apply_post_update(pool.get_ref(), *id, &post.0, tags.as_deref()).await {
+                Ok(Some(updated_post)) => {
                     HttpResponse::Ok().json(updated_post)
                 },
                 Ok(None) => {
@@ -382,6 +549,58 @@ pub async fn update_post(
     }
 }
 
+// Actualiza los campos enviados y, si vienen, las etiquetas, todo en una transacción.
+async fn apply_post_update(
+    pool: &DbPool,
+    id: i32,
+    post: &PostUpdate,
+    tags: Option<&[String]>,
+) -> Result<Option<Post>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Los campos que no se envían se quedan como estaban
+    let Some(record) = sqlx::query!(
+        r#"
+        UPDATE posts
+        SET title = COALESCE($1, title),
+            content = COALESCE($2, content),
+            category = COALESCE($3, category),
+            updated_at = NOW()
+        WHERE id = $4
+        RETURNING id, user_id, title, content, category, created_at, updated_at, likes_count, comments_count
+        "#,
+        post.title,
+        post.content,
+        post.category,
+        id
+    )
+    .fetch_optional(&mut *tx)
+    .await? else {
+        return Ok(None);
+    };
+
+    if let Some(tags) = tags {
+        set_post_tags(&mut tx, id, tags).await?;
+    }
+    let tags = fetch_post_tags(&mut *tx, &[id]).await?.remove(&id).unwrap_or_default();
+
+    tx.commit().await?;
+
+    Ok(Some(Post {
+        id: record.id,
+        user_id: record.user_id.unwrap_or_default(),
+        title: record.title.unwrap_or_else(|| String::from("Untitled")),
+        content: record.content.unwrap_or_default(),
+        category: record.category.unwrap_or_else(|| String::from("general")),
+        // Ya es DateTime<Utc>; la columna tiene valor por defecto
+        created_at: record.created_at.unwrap_or_else(now_utc),
+        updated_at: record.updated_at,
+        likes_count: record.likes_count.unwrap_or(0),
+        comments_count: record.comments_count.unwrap_or(0),
+        tags,
+    }))
+}
+
 pub async fn delete_post(
     pool: web::Data<DbPool>,
     user: web::ReqData<User>,
@@ -573,7 +792,5 @@ pub async fn save_post(
 pub struct GetPostsQuery {
     pub category: Option<String>,
     pub tag: Option<String>,
-    pub page: Option<i32>,
-    pub limit: Option<i32>,
-    pub search: Option<String>,
+    pub cursor: Option<String>,
 }
@@ -0,0 +1,23 @@
+use actix_web::{web, HttpResponse};
+use crate::models::auth::User;
+use crate::models::presence::PresenceHeartbeat;
+use crate::services::presence::PresenceTracker;
+use crate::utils::error::HttpError;
+
+use serde_json::json;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.service(web::resource("/heartbeat").route(web::post().to(heartbeat)));
+}
+
+// Para clientes sin WebSocket abierto; conviene enviarlo cada minuto o menos
+async fn heartbeat(
+    presence: web::Data<PresenceTracker>,
+    user: web::ReqData<User>,
+    body: Option<web::Json<PresenceHeartbeat>>,
+) -> Result<HttpResponse, HttpError> {
+    let active = body.is_none_or(|body| body.active);
+    let status = presence.activity(user.id, active).await;
+
+    Ok(HttpResponse::Ok().json(json!({ "status": status })))
+}
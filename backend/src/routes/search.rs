@@ -0,0 +1,252 @@
+use actix_web::{web, HttpResponse};
+use crate::models::User;
+use crate::db::DbPool;
+use crate::utils::error::HttpError;
+use crate::utils::pagination::{self, Pagination};
+
+use serde::Deserialize;
+use serde_json::json;
+
+// Marcas del fragmento que devuelve ts_headline; se cambian por <mark> después de escapar
+// el texto, para que el contenido de los usuarios nunca llegue como HTML
+const HIGHLIGHT_START: &str = "\u{2}";
+const HIGHLIGHT_STOP: &str = "\u{3}";
+const HEADLINE_OPTIONS: &str =
+    "StartSel=\u{2}, StopSel=\u{3}, MaxWords=35, MinWords=15, MaxFragments=2, FragmentDelimiter=\" … \"";
+
+const MAX_QUERY_LENGTH: usize = 200;
+
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route("", web::get().to(search));
+}
+
+/// Tipos de resultado que se pueden pedir en `type`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum SearchType {
+    Post,
+    Comment,
+    Group,
+}
+
+impl SearchType {
+    fn parse(value: &str) -> Option<Self> {
+        match value.trim().to_lowercase().as_str() {
+            "post" | "posts" => Some(SearchType::Post),
+            "comment" | "comments" => Some(SearchType::Comment),
+            "group" | "groups" => Some(SearchType::Group),
+            _ => None,
+        }
+    }
+}
+
+/// Escapa el fragmento y marca las coincidencias con `<mark>`.
+fn highlight(snippet: &str) -> String {
+    snippet
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+        .replace(HIGHLIGHT_START, "<mark>")
+        .replace(HIGHLIGHT_STOP, "</mark>")
+}
+
+/// Búsqueda de texto completo en posts, comentarios y grupos, ordenada por relevancia.
+///
+/// Admite la sintaxis de `websearch_to_tsquery` (`"frase exacta"`, `-excluir`, `or`). Los
+/// grupos de solo invitación únicamente aparecen para sus miembros, igual que en el listado.
+async fn search(
+    pool: web::Data<DbPool>,
+    user: web::ReqData<User>,
+    query: web::Query<SearchQuery>,
+    pagination: web::Query<Pagination>,
+) -> Result<HttpResponse, HttpError> {
+    let text = query.q.as_deref().map(str::trim).unwrap_or_default();
+    if text.is_empty() {
+        return Err(HttpError::validation("The search text cannot be empty"));
+    }
+    if text.chars().count() > MAX_QUERY_LENGTH {
+        return Err(HttpError::validation(format!(
+            "The search text cannot be longer than {} characters",
+            MAX_QUERY_LENGTH
+        )));
+    }
+
+    let types = query.types()?;
+    let (page, limit) = pagination.resolve(20, 50);
+    let offset = pagination::offset(page, limit);
+    let (posts, comments, groups) = (
+        types.contains(&SearchType::Post),
+        types.contains(&SearchType::Comment),
+        types.contains(&SearchType::Group),
+    );
+
+    let counts = sqlx::query!(
+        r#"
+        WITH q AS (SELECT websearch_to_tsquery('spanish_unaccent', search_unaccent($1)) AS query)
+        SELECT
+            (SELECT COUNT(*)
+             FROM posts p
+             LEFT JOIN users u ON p.user_id = u.id
+             CROSS JOIN q
+             WHERE $3 AND p.search_vector @@ q.query
+               AND (p.user_id IS NULL OR u.is_active = true)) as "posts!",
+            (SELECT COUNT(*)
+             FROM comments c
+             JOIN posts p ON p.id = c.post_id
+             LEFT JOIN users u ON p.user_id = u.id
+             LEFT JOIN users cu ON c.user_id = cu.id
+             CROSS JOIN q
+             WHERE $4 AND c.search_vector @@ q.query
+               AND (p.user_id IS NULL OR u.is_active = true)
+               AND (c.user_id IS NULL OR cu.is_active = true)) as "comments!",
+            (SELECT COUNT(*)
+             FROM groups g
+             LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $2
+             CROSS JOIN q
+             WHERE $5 AND g.search_vector @@ q.query
+               AND (g.visibility <> 'invite_only' OR gm.role IN ('owner', 'moderator', 'member'))) as "groups!"
+        "#,
+        text,
+        user.id,
+        posts,
+        comments,
+        groups
+    )
+    .fetch_one(pool.get_ref())
+    .await?;
+
+    // Los fragmentos solo se calculan para la página pedida; ts_headline es costoso
+    let rows = sqlx::query!(
+        r#"
+        WITH q AS (SELECT websearch_to_tsquery('spanish_unaccent', search_unaccent($1)) AS query),
+        results AS (
+            SELECT 'post' as kind, p.id, p.id as post_id, p.title, p.content as body,
+                   ts_rank_cd(p.search_vector, q.query, 32) as rank, p.created_at
+            FROM posts p
+            LEFT JOIN users u ON p.user_id = u.id
+            CROSS JOIN q
+            WHERE $3 AND p.search_vector @@ q.query
+              AND (p.user_id IS NULL OR u.is_active = true)
+            UNION ALL
+            SELECT 'comment', c.id, c.post_id, p.title, c.content,
+                   ts_rank_cd(c.search_vector, q.query, 32), c.created_at AT TIME ZONE 'UTC'
+            FROM comments c
+            JOIN posts p ON p.id = c.post_id
+            LEFT JOIN users u ON p.user_id = u.id
+            LEFT JOIN users cu ON c.user_id = cu.id
+            CROSS JOIN q
+            WHERE $4 AND c.search_vector @@ q.query
+              AND (p.user_id IS NULL OR u.is_active = true)
+              AND (c.user_id IS NULL OR cu.is_active = true)
+            UNION ALL
+            SELECT 'group', g.id, NULL, g.name, g.description,
+                   ts_rank_cd(g.search_vector, q.query, 32), g.created_at AT TIME ZONE 'UTC'
+            FROM groups g
+            LEFT JOIN group_members gm ON gm.group_id = g.id AND gm.user_id = $2
+            CROSS JOIN q
+            WHERE $5 AND g.search_vector @@ q.query
+              AND (g.visibility <> 'invite_only' OR gm.role IN ('owner', 'moderator', 'member'))
+        ),
+        page AS (
+            SELECT * FROM results
+            ORDER BY rank DESC, created_at DESC NULLS LAST, kind, id DESC
+            LIMIT $6 OFFSET $7
+        )
+        SELECT
+            page.kind as "kind!", page.id as "id!", page.post_id, page.title,
+            page.rank as "rank!", page.created_at,
+            -- Se quitan del texto las marcas de ts_headline para que nadie pueda escribirlas
+            ts_headline('spanish_unaccent', translate(COALESCE(page.body, ''), E'\x02\x03', ''), q.query, $8) as "snippet!"
+        FROM page
+        CROSS JOIN q
+        ORDER BY page.rank DESC, page.created_at DESC NULLS LAST, page.kind, page.id DESC
+        "#,
+        text,
+        user.id,
+        posts,
+        comments,
+        groups,
+        limit as i64,
+        offset,
+        HEADLINE_OPTIONS
+    )
+    .fetch_all(pool.get_ref())
+    .await?;
+
+    let results: Vec<serde_json::Value> = rows
+        .into_iter()
+        .map(|row| {
+            json!({
+                "type": row.kind,
+                "id": row.id,
+                "post_id": row.post_id,
+                "title": row.title,
+                "snippet": highlight(&row.snippet),
+                "rank": row.rank,
+                "created_at": row.created_at,
+            })
+        })
+        .collect();
+
+    Ok(HttpResponse::Ok().json(json!({
+        "results": results,
+        "counts": {
+            "posts": counts.posts,
+            "comments": counts.comments,
+            "groups": counts.groups,
+        },
+        "total": counts.posts + counts.comments + counts.groups,
+        "page": page,
+        "per_page": limit
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+    /// Tipos separados por comas (`posts,comments,groups`); por defecto todos.
+    #[serde(rename = "type")]
+    pub types: Option<String>,
+}
+
+impl SearchQuery {
+    fn types(&self) -> Result<Vec<SearchType>, HttpError> {
+        let Some(types) = self.types.as_deref().filter(|t| !t.trim().is_empty()) else {
+            return Ok(vec![SearchType::Post, SearchType::Comment, SearchType::Group]);
+        };
+
+        types
+            .split(',')
+            .filter(|t| !t.trim().is_empty())
+            .map(|t| {
+                SearchType::parse(t).ok_or_else(|| {
+                    HttpError::bad_request(format!(
+                        "Unknown search type '{}', expected posts, comments or groups",
+                        t.trim()
+                    ))
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn highlight_escapes_html() {
+        assert_eq!(
+            highlight(r#"<script>alert("x")</script> & 'y'"#),
+            "&lt;script&gt;alert(&quot;x&quot;)&lt;/script&gt; &amp; &#39;y&#39;"
+        );
+    }
+
+    #[test]
+    fn highlight_marks_matches_after_escaping() {
+        let snippet = format!("me da {}estrés{} el <b>trabajo</b>", HIGHLIGHT_START, HIGHLIGHT_STOP);
+
+        assert_eq!(highlight(&snippet), "me da <mark>estrés</mark> el &lt;b&gt;trabajo&lt;/b&gt;");
+    }
+}
@@ -0,0 +1,359 @@
+use std::time::Duration;
+
+use crate::db::DbPool;
+use crate::models::auth::AccountDeletionMode;
+use crate::routes::groups::sync_members_count;
+use crate::utils::audit;
+
+// Cada cuánto se buscan cuentas cuyo periodo de gracia ha terminado
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// Lanza la tarea que borra periódicamente las cuentas pendientes de eliminación.
+pub fn spawn_purger(pool: DbPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = purge_due(&pool).await {
+                log::error!("Failed to purge deleted accounts: {}", e);
+            }
+        }
+    });
+}
+
+/// Borra las cuentas cuyo periodo de gracia ya ha terminado. Devuelve cuántas se han borrado.
+pub async fn purge_due(pool: &DbPool) -> Result<usize, sqlx::Error> {
+    let due = sqlx::query!(
+        r#"
+        SELECT id, deletion_mode FROM users
+        WHERE deletion_scheduled_for <= NOW() AND is_active = false
+        "#
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut purged = 0;
+    for account in due {
+        let mode = AccountDeletionMode::from_db(account.deletion_mode.as_deref());
+        if purge_account(pool, account.id, mode).await? {
+            purged += 1;
+            log::info!("Account {} deleted ({})", account.id, mode.as_str());
+            if let Err(e) = audit::record(pool, None, "account_deleted", None, serde_json::json!({
+                "user_id": account.id,
+                "content": mode.as_str()
+            }))
+            .await
+            {
+                log::error!("Failed to write audit log: {}", e);
+            }
+        }
+    }
+
+    Ok(purged)
+}
+
+async fn purge_account(pool: &DbPool, user_id: i32, mode: AccountDeletionMode) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+
+    // Se bloquea la fila para que no se pueda restaurar a medio borrar
+    let still_due = sqlx::query_scalar!(
+        r#"
+        SELECT id FROM users
+        WHERE id = $1 AND is_active = false AND deletion_scheduled_for <= NOW()
+        FOR UPDATE
+        "#,
+        user_id
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if still_due.is_none() {
+        tx.rollback().await?;
+        return Ok(false);
+    }
+
+    // Los contadores de los contenidos de otros usuarios no se recalculan solos con el borrado en cascada.
+    // Los de group_posts no se tocan: las publicaciones de grupo no tienen likes ni comentarios
+    // por usuario (no hay tablas para ellos), así que borrar la cuenta no los desajusta. Si se
+    // añaden, hay que descontarlos aquí igual que en posts.
+    sqlx::query!(
+        r#"
+        UPDATE posts SET likes_count = GREATEST(likes_count - 1, 0)
+        WHERE id IN (SELECT post_id FROM post_likes WHERE user_id = $1)
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+        UPDATE comments SET likes_count = GREATEST(likes_count - 1, 0)
+        WHERE id IN (SELECT comment_id FROM comment_likes WHERE user_id = $1)
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    if mode == AccountDeletionMode::Delete {
+        sqlx::query!(
+            r#"
+            UPDATE posts p SET comments_count = GREATEST(p.comments_count - c.total, 0)
+            FROM (
+                SELECT post_id, COUNT(*)::int as total FROM comments
+                WHERE user_id = $1 GROUP BY post_id
+            ) c
+            WHERE p.id = c.post_id
+            "#,
+            user_id
+        )
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    if mode == AccountDeletionMode::Anonymize {
+        sqlx::query!("UPDATE posts SET user_id = NULL WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query!("UPDATE comments SET user_id = NULL WHERE user_id = $1", user_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    // Los grupos que creó pasan al moderador más antiguo o, si no hay, al miembro más antiguo
+    sqlx::query!(
+        r#"
+        UPDATE group_members gm SET role = 'owner'
+        FROM (
+            SELECT DISTINCT ON (m.group_id) m.group_id, m.user_id
+            FROM group_members m
+            JOIN group_members o ON o.group_id = m.group_id AND o.user_id = $1 AND o.role = 'owner'
+            WHERE m.user_id <> $1 AND m.role IN ('moderator', 'member')
+            ORDER BY m.group_id, (m.role = 'moderator') DESC, m.join_date ASC NULLS LAST, m.user_id
+        ) heir
+        WHERE gm.group_id = heir.group_id AND gm.user_id = heir.user_id
+        "#,
+        user_id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    let groups = sqlx::query_scalar!(
+        "SELECT group_id FROM group_members WHERE user_id = $1",
+        user_id
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    // El resto de sus datos (likes, mensajes, registros de ánimo...) se borra en cascada
+    sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    for group_id in groups {
+        sync_members_count(&mut tx, group_id).await?;
+    }
+
+    tx.commit().await?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_user, load_schema};
+
+    // Deja la cuenta desactivada con el periodo de gracia ya cumplido
+    async fn schedule(pool: &DbPool, user_id: i32, mode: AccountDeletionMode) {
+        sqlx::query(
+            r#"
+            UPDATE users SET is_active = false, deletion_mode = $2,
+                deletion_scheduled_for = NOW() - INTERVAL '1 minute'
+            WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .bind(mode.as_str())
+        .execute(pool)
+        .await
+        .unwrap();
+    }
+
+    async fn insert_id(pool: &DbPool, sql: &str, binds: &[i32]) -> i32 {
+        let mut query = sqlx::query_scalar(sql);
+        for bind in binds {
+            query = query.bind(*bind);
+        }
+        query.fetch_one(pool).await.unwrap()
+    }
+
+    async fn scalar<T>(pool: &DbPool, sql: &str, id: i32) -> T
+    where
+        T: Send + Unpin + for<'r> sqlx::Decode<'r, sqlx::Postgres> + sqlx::Type<sqlx::Postgres>,
+    {
+        sqlx::query_scalar(sql).bind(id).fetch_one(pool).await.unwrap()
+    }
+
+    struct Content {
+        other_post: i32,
+        other_comment: i32,
+        own_post: i32,
+    }
+
+    // `user_id` da like a un post y a un comentario de otro usuario, comenta dos veces en
+    // ese post y publica uno propio. Los contadores se dejan como los mantendrían los handlers
+    async fn create_content(pool: &DbPool, user_id: i32, other_id: i32) -> Content {
+        let other_post = insert_id(
+            pool,
+            "INSERT INTO posts (user_id, content, likes_count, comments_count) VALUES ($1, 'x', 2, 3) RETURNING id",
+            &[other_id],
+        )
+        .await;
+        let other_comment = insert_id(
+            pool,
+            "INSERT INTO comments (post_id, user_id, content, likes_count) VALUES ($1, $2, 'x', 1) RETURNING id",
+            &[other_post, other_id],
+        )
+        .await;
+        for _ in 0..2 {
+            insert_id(
+                pool,
+                "INSERT INTO comments (post_id, user_id, content) VALUES ($1, $2, 'x') RETURNING id",
+                &[other_post, user_id],
+            )
+            .await;
+        }
+        insert_id(pool, "INSERT INTO post_likes (post_id, user_id) VALUES ($1, $2) RETURNING post_id", &[other_post, user_id]).await;
+        insert_id(
+            pool,
+            "INSERT INTO comment_likes (comment_id, user_id) VALUES ($1, $2) RETURNING comment_id",
+            &[other_comment, user_id],
+        )
+        .await;
+        let own_post = insert_id(pool, "INSERT INTO posts (user_id, content) VALUES ($1, 'x') RETURNING id", &[user_id]).await;
+
+        Content { other_post, other_comment, own_post }
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn delete_mode_removes_content_and_fixes_counters(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "gone@example.com").await;
+        let other_id = create_user(&pool, "other@example.com").await;
+        let content = create_content(&pool, user_id, other_id).await;
+        schedule(&pool, user_id, AccountDeletionMode::Delete).await;
+
+        assert_eq!(purge_due(&pool).await.unwrap(), 1);
+
+        let post: (i32, i32) = sqlx::query_as("SELECT likes_count, comments_count FROM posts WHERE id = $1")
+            .bind(content.other_post)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(post, (1, 1));
+        assert_eq!(scalar::<i32>(&pool, "SELECT likes_count FROM comments WHERE id = $1", content.other_comment).await, 0);
+        assert_eq!(scalar::<i64>(&pool, "SELECT COUNT(*) FROM posts WHERE id = $1", content.own_post).await, 0);
+        assert_eq!(scalar::<i64>(&pool, "SELECT COUNT(*) FROM users WHERE id = $1", user_id).await, 0);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn anonymize_mode_keeps_content_without_author(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "gone@example.com").await;
+        let other_id = create_user(&pool, "other@example.com").await;
+        let content = create_content(&pool, user_id, other_id).await;
+        schedule(&pool, user_id, AccountDeletionMode::Anonymize).await;
+
+        assert_eq!(purge_due(&pool).await.unwrap(), 1);
+
+        // Los likes se van con la cuenta, pero los comentarios se quedan
+        let post: (i32, i32) = sqlx::query_as("SELECT likes_count, comments_count FROM posts WHERE id = $1")
+            .bind(content.other_post)
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(post, (1, 3));
+        assert_eq!(scalar::<i32>(&pool, "SELECT likes_count FROM comments WHERE id = $1", content.other_comment).await, 0);
+        assert_eq!(scalar::<Option<i32>>(&pool, "SELECT user_id FROM posts WHERE id = $1", content.own_post).await, None);
+        assert_eq!(
+            scalar::<i64>(&pool, "SELECT COUNT(*) FROM comments WHERE post_id = $1 AND user_id IS NULL", content.other_post).await,
+            2
+        );
+        assert_eq!(scalar::<i64>(&pool, "SELECT COUNT(*) FROM users WHERE id = $1", user_id).await, 0);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn owned_groups_pass_to_a_moderator_before_older_members(pool: DbPool) {
+        load_schema(&pool).await;
+        let owner = create_user(&pool, "owner@example.com").await;
+        let member = create_user(&pool, "member@example.com").await;
+        let moderator = create_user(&pool, "moderator@example.com").await;
+        let banned = create_user(&pool, "banned@example.com").await;
+
+        let with_moderator = insert_id(&pool, "INSERT INTO groups (name, creator_id) VALUES ('a', $1) RETURNING id", &[owner]).await;
+        let members_only = insert_id(&pool, "INSERT INTO groups (name, creator_id) VALUES ('b', $1) RETURNING id", &[owner]).await;
+        sqlx::query(
+            r#"
+            INSERT INTO group_members (group_id, user_id, role, join_date) VALUES
+                ($1, $3, 'owner', '2024-01-01'),
+                ($1, $5, 'banned', '2024-01-01'),
+                ($1, $4, 'member', '2024-01-02'),
+                ($1, $6, 'moderator', '2024-01-03'),
+                ($2, $3, 'owner', '2024-01-01'),
+                ($2, $6, 'member', '2024-01-03'),
+                ($2, $4, 'member', '2024-01-02')
+            "#,
+        )
+        .bind(with_moderator)
+        .bind(members_only)
+        .bind(owner)
+        .bind(member)
+        .bind(banned)
+        .bind(moderator)
+        .execute(&pool)
+        .await
+        .unwrap();
+        schedule(&pool, owner, AccountDeletionMode::Delete).await;
+
+        assert_eq!(purge_due(&pool).await.unwrap(), 1);
+
+        let role = |group_id: i32, user_id: i32| {
+            let pool = pool.clone();
+            async move {
+                sqlx::query_scalar::<_, String>("SELECT role FROM group_members WHERE group_id = $1 AND user_id = $2")
+                    .bind(group_id)
+                    .bind(user_id)
+                    .fetch_one(&pool)
+                    .await
+                    .unwrap()
+            }
+        };
+        assert_eq!(role(with_moderator, moderator).await, "owner");
+        assert_eq!(role(with_moderator, member).await, "member");
+        assert_eq!(role(with_moderator, banned).await, "banned");
+        assert_eq!(role(members_only, member).await, "owner");
+        assert_eq!(role(members_only, moderator).await, "member");
+
+        // Los baneados no cuentan como miembros
+        assert_eq!(scalar::<i32>(&pool, "SELECT members_count FROM groups WHERE id = $1", with_moderator).await, 2);
+        assert_eq!(scalar::<i32>(&pool, "SELECT members_count FROM groups WHERE id = $1", members_only).await, 2);
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn restored_accounts_are_not_purged(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "back@example.com").await;
+        schedule(&pool, user_id, AccountDeletionMode::Delete).await;
+
+        // Se reactiva entre la búsqueda de purge_due y el borrado: la comprobación bajo
+        // FOR UPDATE tiene que descartarla
+        sqlx::query("UPDATE users SET is_active = true, deletion_scheduled_for = NULL WHERE id = $1")
+            .bind(user_id)
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert!(!purge_account(&pool, user_id, AccountDeletionMode::Delete).await.unwrap());
+        assert_eq!(purge_due(&pool).await.unwrap(), 0);
+        assert_eq!(scalar::<i64>(&pool, "SELECT COUNT(*) FROM users WHERE id = $1", user_id).await, 1);
+    }
+}
@@ -0,0 +1,106 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::broadcast;
+
+use crate::models::messages::ChatServerEvent;
+use crate::utils::secure_token;
+
+// Eventos que puede acumular una conexión lenta antes de empezar a perderlos
+const ROOM_CAPACITY: usize = 256;
+/// Tiempo para abrir el WebSocket con un ticket.
+pub const TICKET_TTL: Duration = Duration::from_secs(30);
+
+/// Salas de chat en memoria, una por grupo con al menos una conexión abierta.
+///
+/// Cada conexión WebSocket se suscribe al canal de su grupo; los mensajes ya se han
+/// guardado en la base de datos antes de difundirse, así que un cliente que pierda
+/// eventos puede recuperarlos con el historial.
+///
+/// También guarda los tickets con los que se abre el WebSocket. Los navegadores no pueden
+/// enviar la cabecera Authorization al abrirlo y el JWT en la URL acabaría en los logs, así
+/// que el cliente pide antes un ticket de un solo uso para un grupo y lo pasa como `?ticket=`.
+#[derive(Clone, Default)]
+pub struct ChatHub {
+    rooms: Arc<Mutex<HashMap<i32, broadcast::Sender<ChatServerEvent>>>>,
+    tickets: Arc<Mutex<HashMap<String, ChatTicket>>>,
+}
+
+struct ChatTicket {
+    access_token: String,
+    group_id: i32,
+    expires_at: Instant,
+}
+
+impl ChatHub {
+    pub fn subscribe(&self, group_id: i32) -> broadcast::Receiver<ChatServerEvent> {
+        let mut rooms = self.rooms.lock().unwrap();
+        rooms
+            .entry(group_id)
+            .or_insert_with(|| broadcast::channel(ROOM_CAPACITY).0)
+            .subscribe()
+    }
+
+    pub fn publish(&self, group_id: i32, event: ChatServerEvent) {
+        let rooms = self.rooms.lock().unwrap();
+        if let Some(room) = rooms.get(&group_id) {
+            // Sin suscriptores el envío falla, y no hay nadie a quien avisar
+            let _ = room.send(event);
+        }
+    }
+
+    /// Elimina la sala si ya no queda nadie conectado. Se llama tras soltar el receptor.
+    pub fn leave(&self, group_id: i32) {
+        let mut rooms = self.rooms.lock().unwrap();
+        if rooms.get(&group_id).is_some_and(|room| room.receiver_count() == 0) {
+            rooms.remove(&group_id);
+        }
+    }
+
+    /// Crea un ticket para abrir el chat de `group_id` con la sesión de `access_token`.
+    pub fn issue_ticket(&self, access_token: &str, group_id: i32) -> String {
+        let ticket = secure_token::generate();
+        let now = Instant::now();
+
+        let mut tickets = self.tickets.lock().unwrap();
+        tickets.retain(|_, ticket| ticket.expires_at > now);
+        tickets.insert(
+            ticket.clone(),
+            ChatTicket { access_token: access_token.to_string(), group_id, expires_at: now + TICKET_TTL },
+        );
+        ticket
+    }
+
+    /// Canjea un ticket y devuelve el access token con el que se pidió. Cada ticket sirve
+    /// una sola vez y solo para su grupo.
+    pub fn redeem_ticket(&self, ticket: &str, group_id: i32) -> Option<String> {
+        let ticket = self.tickets.lock().unwrap().remove(ticket)?;
+        (ticket.group_id == group_id && ticket.expires_at > Instant::now()).then_some(ticket.access_token)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tickets_are_single_use() {
+        let hub = ChatHub::default();
+        let ticket = hub.issue_ticket("jwt", 1);
+
+        assert_eq!(hub.redeem_ticket(&ticket, 1).as_deref(), Some("jwt"));
+        assert_eq!(hub.redeem_ticket(&ticket, 1), None);
+    }
+
+    #[test]
+    fn tickets_only_open_their_group() {
+        let hub = ChatHub::default();
+        let ticket = hub.issue_ticket("jwt", 1);
+
+        assert_eq!(hub.redeem_ticket(&ticket, 2), None);
+        // El intento fallido también lo gasta
+        assert_eq!(hub.redeem_ticket(&ticket, 1), None);
+        assert_eq!(hub.redeem_ticket("inventado", 1), None);
+    }
+}
@@ -0,0 +1,493 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::config::Config;
+use crate::utils::jwt::parse_duration;
+
+// Cada cuánto se eliminan del almacén los contadores que ya no cuentan
+const SWEEP_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// Umbrales de la protección del login, leídos de la configuración.
+#[derive(Debug, Clone)]
+pub struct LoginPolicy {
+    /// Fallos seguidos de una cuenta antes de bloquearla.
+    pub max_failures: u32,
+    /// Fallos desde una misma IP, sea cual sea la cuenta, antes de bloquear la IP.
+    pub ip_max_failures: u32,
+    /// Un fallo deja de contar pasado este tiempo.
+    pub failure_window: Duration,
+    pub lockout_duration: Duration,
+    /// Espera tras el primer fallo de una cuenta; se duplica con cada fallo siguiente.
+    pub backoff_base: Duration,
+    pub backoff_max: Duration,
+}
+
+impl LoginPolicy {
+    pub fn from_config(config: &Config) -> Self {
+        LoginPolicy {
+            max_failures: config.login_max_failures.max(1),
+            ip_max_failures: config.login_ip_max_failures.max(1),
+            failure_window: Duration::seconds(parse_duration(&config.login_failure_window)),
+            lockout_duration: Duration::seconds(parse_duration(&config.login_lockout_duration)),
+            backoff_base: Duration::seconds(parse_duration(&config.login_backoff_base)),
+            backoff_max: Duration::seconds(parse_duration(&config.login_backoff_max)),
+        }
+    }
+
+    fn backoff(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::zero();
+        }
+        let factor = 1i32 << (failures - 1).min(20);
+        (self.backoff_base * factor).min(self.backoff_max)
+    }
+}
+
+/// Fallos recientes asociados a una clave (una cuenta o una IP).
+#[derive(Debug, Clone, Copy)]
+pub struct Attempts {
+    pub failures: u32,
+    pub last_failure: DateTime<Utc>,
+    pub locked_until: Option<DateTime<Utc>>,
+}
+
+/// Dónde se guardan los contadores de fallos. Con varias instancias del servidor
+/// haría falta un almacén compartido; basta con implementar este trait.
+pub trait AttemptStore: Send + Sync {
+    fn get(&self, key: &str) -> Option<Attempts>;
+    /// Modifica los fallos de la clave de forma atómica y devuelve el resultado.
+    fn update(&self, key: &str, now: DateTime<Utc>, f: &mut dyn FnMut(&mut Attempts)) -> Attempts;
+    fn remove(&self, key: &str);
+    /// Elimina las claves sin bloqueo activo cuyo último fallo es anterior a `before`.
+    fn purge(&self, before: DateTime<Utc>, now: DateTime<Utc>);
+}
+
+/// Contadores en memoria del proceso.
+#[derive(Default)]
+pub struct InMemoryAttemptStore {
+    attempts: Mutex<HashMap<String, Attempts>>,
+}
+
+impl AttemptStore for InMemoryAttemptStore {
+    fn get(&self, key: &str) -> Option<Attempts> {
+        self.attempts.lock().unwrap().get(key).copied()
+    }
+
+    fn update(&self, key: &str, now: DateTime<Utc>, f: &mut dyn FnMut(&mut Attempts)) -> Attempts {
+        let mut attempts = self.attempts.lock().unwrap();
+        let entry = attempts.entry(key.to_string()).or_insert(Attempts {
+            failures: 0,
+            last_failure: now,
+            locked_until: None,
+        });
+        f(entry);
+        *entry
+    }
+
+    fn remove(&self, key: &str) {
+        self.attempts.lock().unwrap().remove(key);
+    }
+
+    fn purge(&self, before: DateTime<Utc>, now: DateTime<Utc>) {
+        self.attempts.lock().unwrap().retain(|_, attempts| {
+            attempts.last_failure >= before || attempts.locked_until.is_some_and(|until| until > now)
+        });
+    }
+}
+
+/// Bloqueo que acaba de producirse tras un fallo.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Lockout {
+    Account { email: String, until: DateTime<Utc> },
+    Ip { ip: String, until: DateTime<Utc> },
+}
+
+/// Intento de login en curso, obtenido con `LoginGuard::begin`.
+///
+/// Cuenta como fallo desde que empieza, para que las peticiones en paralelo no se salten
+/// la espera, y se resuelve con `record_success`, `record_failure` o `release`. Si se
+/// descarta sin resolver (por ejemplo, por un error), queda contado como fallo.
+#[must_use]
+#[derive(Debug)]
+pub struct LoginAttempt {
+    email: String,
+    ip: Option<String>,
+    lockouts: Vec<Lockout>,
+}
+
+impl LoginAttempt {
+    fn locked_account(&self) -> bool {
+        self.lockouts.iter().any(|lockout| matches!(lockout, Lockout::Account { .. }))
+    }
+
+    fn locked_ip(&self) -> bool {
+        self.lockouts.iter().any(|lockout| matches!(lockout, Lockout::Ip { .. }))
+    }
+}
+
+/// Protección del login contra ataques de fuerza bruta.
+///
+/// Cada fallo de una cuenta obliga a esperar un tiempo que crece exponencialmente antes
+/// del siguiente intento, y tras `max_failures` fallos la cuenta se bloquea durante
+/// `lockout_duration`. Las IP se bloquean igual al llegar a `ip_max_failures`, para
+/// frenar a quien prueba una contraseña contra muchas cuentas.
+#[derive(Clone)]
+pub struct LoginGuard {
+    policy: LoginPolicy,
+    store: Arc<dyn AttemptStore>,
+}
+
+impl LoginGuard {
+    pub fn new(policy: LoginPolicy, store: Arc<dyn AttemptStore>) -> Self {
+        LoginGuard { policy, store }
+    }
+
+    fn account_key(email: &str) -> String {
+        format!("account:{}", email.trim().to_lowercase())
+    }
+
+    fn ip_key(ip: &str) -> String {
+        format!("ip:{}", ip)
+    }
+
+    /// Lanza la tarea que elimina periódicamente los contadores caducados.
+    pub fn spawn_sweeper(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                let now = Utc::now();
+                self.store.purge(now - self.policy.failure_window, now);
+            }
+        });
+    }
+
+    /// Empieza un intento de login. Si hay que esperar, devuelve cuánto.
+    pub fn begin(&self, email: &str, ip: Option<&str>) -> Result<LoginAttempt, Duration> {
+        self.begin_at(email, ip, Utc::now())
+    }
+
+    fn begin_at(&self, email: &str, ip: Option<&str>, now: DateTime<Utc>) -> Result<LoginAttempt, Duration> {
+        let mut attempt = LoginAttempt {
+            email: email.trim().to_lowercase(),
+            ip: ip.map(str::to_string),
+            lockouts: Vec::new(),
+        };
+
+        // La IP va primero porque su reserva se deshace sin más si la cuenta tiene que esperar
+        if let Some(ip) = ip {
+            if let Some(until) = self.reserve(&Self::ip_key(ip), self.policy.ip_max_failures, false, now)? {
+                attempt.lockouts.push(Lockout::Ip { ip: ip.to_string(), until });
+            }
+        }
+
+        match self.reserve(&Self::account_key(email), self.policy.max_failures, true, now) {
+            Ok(Some(until)) => attempt.lockouts.push(Lockout::Account { email: attempt.email.clone(), until }),
+            Ok(None) => {}
+            Err(wait) => {
+                if let Some(ip) = ip {
+                    self.unreserve(&Self::ip_key(ip), self.policy.ip_max_failures, attempt.locked_ip());
+                }
+                return Err(wait);
+            }
+        }
+
+        Ok(attempt)
+    }
+
+    fn wait_for(&self, attempts: &Attempts, now: DateTime<Utc>, with_backoff: bool) -> Option<Duration> {
+        if let Some(until) = attempts.locked_until {
+            if until > now {
+                return Some(until - now);
+            }
+            return None;
+        }
+        if !with_backoff || now - attempts.last_failure > self.policy.failure_window {
+            return None;
+        }
+        let next_allowed = attempts.last_failure + self.policy.backoff(attempts.failures);
+        (next_allowed > now).then(|| next_allowed - now)
+    }
+
+    // Comprueba la espera y, si se puede intentar, suma el intento como fallo en la misma
+    // operación del almacén. Devuelve el fin del bloqueo si con este se alcanza el máximo.
+    fn reserve(
+        &self,
+        key: &str,
+        max_failures: u32,
+        with_backoff: bool,
+        now: DateTime<Utc>,
+    ) -> Result<Option<DateTime<Utc>>, Duration> {
+        let policy = &self.policy;
+        let mut result = Ok(None);
+
+        self.store.update(key, now, &mut |attempts| {
+            if let Some(wait) = self.wait_for(attempts, now, with_backoff) {
+                result = Err(wait);
+                return;
+            }
+
+            // Un bloqueo terminado o unos fallos antiguos ya no cuentan
+            let lock_expired = attempts.locked_until.is_some_and(|until| until <= now);
+            if lock_expired || now - attempts.last_failure > policy.failure_window {
+                attempts.failures = 0;
+                attempts.locked_until = None;
+            }
+
+            attempts.failures += 1;
+            attempts.last_failure = now;
+
+            if attempts.failures >= max_failures {
+                let until = now + policy.lockout_duration;
+                attempts.failures = 0;
+                attempts.locked_until = Some(until);
+                result = Ok(Some(until));
+            }
+        });
+
+        result
+    }
+
+    // Deshace la reserva de un intento que no ha fallado, incluido el bloqueo que provocó
+    fn unreserve(&self, key: &str, max_failures: u32, locked: bool) {
+        self.store.update(key, Utc::now(), &mut |attempts| {
+            if locked {
+                attempts.locked_until = None;
+                attempts.failures = max_failures - 1;
+            } else {
+                attempts.failures = attempts.failures.saturating_sub(1);
+            }
+        });
+    }
+
+    /// El intento ha fallado; ya estaba contado. Devuelve los bloqueos que provoca.
+    pub fn record_failure(&self, attempt: LoginAttempt) -> Vec<Lockout> {
+        attempt.lockouts
+    }
+
+    /// Un inicio de sesión correcto pone a cero los fallos de la cuenta. Los de la IP se
+    /// mantienen para que entrar en una cuenta propia no sirva para seguir probando otras.
+    pub fn record_success(&self, attempt: LoginAttempt) {
+        self.store.remove(&Self::account_key(&attempt.email));
+        if let Some(ip) = &attempt.ip {
+            self.unreserve(&Self::ip_key(ip), self.policy.ip_max_failures, attempt.locked_ip());
+        }
+    }
+
+    /// Descuenta un intento que ni ha fallado ni cierra el login (falta el segundo factor),
+    /// sin poner a cero los fallos anteriores de la cuenta.
+    pub fn release(&self, attempt: LoginAttempt) {
+        self.unreserve(&Self::account_key(&attempt.email), self.policy.max_failures, attempt.locked_account());
+        if let Some(ip) = &attempt.ip {
+            self.unreserve(&Self::ip_key(ip), self.policy.ip_max_failures, attempt.locked_ip());
+        }
+    }
+
+    /// Quita el bloqueo y los fallos de una cuenta. Devuelve true si estaba bloqueada.
+    pub fn unlock(&self, email: &str) -> bool {
+        let key = Self::account_key(email);
+        let was_locked = self
+            .store
+            .get(&key)
+            .and_then(|attempts| attempts.locked_until)
+            .is_some_and(|until| until > Utc::now());
+        self.store.remove(&key);
+        was_locked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EMAIL: &str = "ana@example.com";
+    const IP: &str = "10.0.0.1";
+
+    fn policy() -> LoginPolicy {
+        LoginPolicy {
+            max_failures: 5,
+            ip_max_failures: 20,
+            failure_window: Duration::minutes(15),
+            lockout_duration: Duration::minutes(15),
+            backoff_base: Duration::seconds(1),
+            backoff_max: Duration::seconds(60),
+        }
+    }
+
+    fn guard(policy: LoginPolicy) -> LoginGuard {
+        LoginGuard::new(policy, Arc::new(InMemoryAttemptStore::default()))
+    }
+
+    fn start() -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().with_timezone(&Utc)
+    }
+
+    // Intento fallido en el instante indicado; falla el test si había que esperar
+    fn fail_at(guard: &LoginGuard, email: &str, ip: Option<&str>, now: DateTime<Utc>) -> Vec<Lockout> {
+        let attempt = guard.begin_at(email, ip, now).expect("attempt should be allowed");
+        guard.record_failure(attempt)
+    }
+
+    #[test]
+    fn backoff_doubles_up_to_the_maximum() {
+        let policy = policy();
+        let waits: Vec<i64> = (0..9).map(|failures| policy.backoff(failures).num_seconds()).collect();
+        assert_eq!(waits, vec![0, 1, 2, 4, 8, 16, 32, 60, 60]);
+    }
+
+    #[test]
+    fn each_failure_doubles_the_wait_before_the_next_attempt() {
+        let guard = guard(LoginPolicy { max_failures: 10, ..policy() });
+        let mut now = start();
+
+        for expected in [1, 2, 4, 8] {
+            fail_at(&guard, EMAIL, None, now);
+            let wait = guard.begin_at(EMAIL, None, now + Duration::milliseconds(500)).unwrap_err();
+            assert_eq!(wait, Duration::seconds(expected) - Duration::milliseconds(500));
+            now += Duration::seconds(expected);
+        }
+        assert!(guard.begin_at(EMAIL, None, now).is_ok());
+    }
+
+    #[test]
+    fn parallel_attempts_cannot_skip_the_backoff() {
+        let guard = guard(policy());
+        let now = start();
+        fail_at(&guard, EMAIL, None, now);
+
+        let later = now + Duration::seconds(1);
+        let first = guard.begin_at(EMAIL, None, later);
+        let second = guard.begin_at(EMAIL, None, later);
+        assert!(first.is_ok());
+        assert_eq!(second.unwrap_err(), Duration::seconds(2));
+    }
+
+    #[test]
+    fn account_locks_at_max_failures() {
+        let guard = guard(LoginPolicy { backoff_base: Duration::zero(), ..policy() });
+        let now = start();
+
+        for _ in 0..4 {
+            assert!(fail_at(&guard, EMAIL, None, now).is_empty());
+        }
+        let until = now + Duration::minutes(15);
+        assert_eq!(
+            fail_at(&guard, EMAIL, None, now),
+            vec![Lockout::Account { email: EMAIL.to_string(), until }]
+        );
+
+        // También con otra forma de escribir el email
+        let wait = guard.begin_at(" Ana@Example.com", None, now + Duration::minutes(5)).unwrap_err();
+        assert_eq!(wait, Duration::minutes(10));
+
+        // Acabado el bloqueo se vuelve a empezar de cero
+        for _ in 0..4 {
+            assert!(fail_at(&guard, EMAIL, None, until).is_empty());
+        }
+    }
+
+    #[test]
+    fn failures_expire_after_the_window() {
+        let guard = guard(LoginPolicy { backoff_base: Duration::zero(), ..policy() });
+        let now = start();
+
+        for _ in 0..4 {
+            fail_at(&guard, EMAIL, None, now);
+        }
+        let later = now + Duration::minutes(16);
+        for _ in 0..4 {
+            assert!(fail_at(&guard, EMAIL, None, later).is_empty());
+        }
+    }
+
+    #[test]
+    fn ip_locks_across_accounts() {
+        let guard = guard(LoginPolicy { ip_max_failures: 3, ..policy() });
+        let now = start();
+
+        assert!(fail_at(&guard, "a@example.com", Some(IP), now).is_empty());
+        assert!(fail_at(&guard, "b@example.com", Some(IP), now).is_empty());
+        assert_eq!(
+            fail_at(&guard, "c@example.com", Some(IP), now),
+            vec![Lockout::Ip { ip: IP.to_string(), until: now + Duration::minutes(15) }]
+        );
+
+        assert!(guard.begin_at("d@example.com", Some(IP), now).is_err());
+        assert!(guard.begin_at("d@example.com", Some("10.0.0.2"), now).is_ok());
+    }
+
+    #[test]
+    fn waiting_account_does_not_use_up_ip_attempts() {
+        let guard = guard(LoginPolicy { ip_max_failures: 3, ..policy() });
+        let now = start();
+        fail_at(&guard, EMAIL, Some(IP), now);
+
+        for _ in 0..5 {
+            assert!(guard.begin_at(EMAIL, Some(IP), now).is_err());
+        }
+        assert!(fail_at(&guard, "b@example.com", Some(IP), now).is_empty());
+    }
+
+    #[test]
+    fn success_resets_account_failures_but_not_ip_failures() {
+        let guard = guard(LoginPolicy { ip_max_failures: 4, backoff_base: Duration::zero(), ..policy() });
+        let now = start();
+
+        for _ in 0..4 {
+            fail_at(&guard, EMAIL, None, now);
+        }
+        let attempt = guard.begin_at(EMAIL, Some(IP), now).unwrap();
+        guard.record_success(attempt);
+        for _ in 0..4 {
+            assert!(fail_at(&guard, EMAIL, None, now).is_empty());
+        }
+
+        // El login correcto no cuenta para la IP, pero los fallos anteriores sí
+        for email in ["a@example.com", "b@example.com", "c@example.com"] {
+            assert!(fail_at(&guard, email, Some(IP), now).is_empty());
+        }
+        assert!(!fail_at(&guard, "d@example.com", Some(IP), now).is_empty());
+    }
+
+    #[test]
+    fn success_on_the_locking_attempt_lifts_the_lock() {
+        let guard = guard(LoginPolicy { backoff_base: Duration::zero(), ..policy() });
+        let now = start();
+
+        for _ in 0..4 {
+            fail_at(&guard, EMAIL, None, now);
+        }
+        let attempt = guard.begin_at(EMAIL, None, now).unwrap();
+        guard.record_success(attempt);
+        assert!(guard.begin_at(EMAIL, None, now).is_ok());
+    }
+
+    #[test]
+    fn release_keeps_previous_failures() {
+        let guard = guard(LoginPolicy { backoff_base: Duration::zero(), ..policy() });
+        let now = start();
+
+        for _ in 0..3 {
+            fail_at(&guard, EMAIL, None, now);
+        }
+        for _ in 0..10 {
+            let attempt = guard.begin_at(EMAIL, None, now).unwrap();
+            guard.release(attempt);
+        }
+        assert!(fail_at(&guard, EMAIL, None, now).is_empty());
+        assert!(!fail_at(&guard, EMAIL, None, now).is_empty());
+    }
+
+    #[test]
+    fn unlock_clears_the_lockout() {
+        let guard = guard(LoginPolicy { max_failures: 1, ..policy() });
+        fail_at(&guard, EMAIL, None, Utc::now());
+        assert!(guard.begin(EMAIL, None).is_err());
+
+        assert!(guard.unlock(EMAIL));
+        assert!(!guard.unlock(EMAIL));
+        assert!(guard.begin(EMAIL, None).is_ok());
+    }
+}
@@ -0,0 +1,94 @@
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use chrono::Utc;
+use futures_util::future::BoxFuture;
+
+use crate::config::Config;
+
+/// Correo saliente en texto plano.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum MailerError {
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+/// Envío de correos. Las implementaciones de desarrollo solo registran o guardan el mensaje;
+/// un proveedor real (SMTP, API) se añade implementando este trait.
+pub trait Mailer: Send + Sync {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailerError>>;
+}
+
+/// Escribe los correos en el log.
+pub struct LogMailer {
+    from: String,
+}
+
+impl Mailer for LogMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailerError>> {
+        Box::pin(async move {
+            log::info!(
+                "Email from {} to {}\nSubject: {}\n\n{}",
+                self.from,
+                email.to,
+                email.subject,
+                email.body
+            );
+            Ok(())
+        })
+    }
+}
+
+/// Guarda cada correo como un fichero .eml en un directorio.
+pub struct FileMailer {
+    from: String,
+    dir: PathBuf,
+}
+
+impl Mailer for FileMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailerError>> {
+        Box::pin(async move {
+            tokio::fs::create_dir_all(&self.dir).await?;
+
+            let now = Utc::now();
+            let file_name = format!(
+                "{}-{}.eml",
+                now.format("%Y%m%dT%H%M%S%.3f"),
+                uuid::Uuid::new_v4().simple()
+            );
+            let contents = format!(
+                "From: {}\r\nTo: {}\r\nSubject: {}\r\nDate: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+                self.from,
+                email.to,
+                email.subject,
+                now.to_rfc2822(),
+                email.body
+            );
+
+            tokio::fs::write(self.dir.join(file_name), contents).await?;
+            Ok(())
+        })
+    }
+}
+
+/// Crea el mailer indicado por `MAILER` ("log" o "file").
+pub fn from_config(config: &Config) -> Arc<dyn Mailer> {
+    match config.mailer.as_str() {
+        "file" => Arc::new(FileMailer {
+            from: config.mail_from.clone(),
+            dir: PathBuf::from(&config.mail_dir),
+        }),
+        "log" => Arc::new(LogMailer { from: config.mail_from.clone() }),
+        other => {
+            log::warn!("Unknown MAILER '{}', falling back to log", other);
+            Arc::new(LogMailer { from: config.mail_from.clone() })
+        }
+    }
+}
@@ -0,0 +1,6 @@
+pub mod account_deletion;
+pub mod chat;
+pub mod login_guard;
+pub mod mailer;
+pub mod oidc;
+pub mod presence;
@@ -0,0 +1,357 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use reqwest::Url;
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use tokio::sync::RwLock;
+
+use crate::config::{Config, OidcProviderConfig};
+
+// Cada cuánto se vuelven a pedir los metadatos y las claves de un proveedor
+const METADATA_TTL: Duration = Duration::from_secs(60 * 60);
+const HTTP_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("Unknown provider '{0}'")]
+    UnknownProvider(String),
+    #[error("Request to the provider failed: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("Invalid provider configuration: {0}")]
+    Discovery(String),
+    #[error("The provider rejected the authorization code: {0}")]
+    TokenExchange(String),
+    #[error("Invalid ID token: {0}")]
+    InvalidIdToken(String),
+}
+
+// Lo que usamos del documento de descubrimiento (/.well-known/openid-configuration)
+#[derive(Debug, Clone, Deserialize)]
+struct ProviderMetadata {
+    issuer: String,
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+struct CachedProvider {
+    metadata: ProviderMetadata,
+    jwks: JwkSet,
+    fetched_at: Instant,
+}
+
+#[derive(Debug, Deserialize)]
+struct TokenResponse {
+    id_token: Option<String>,
+    error: Option<String>,
+    error_description: Option<String>,
+}
+
+/// Datos del usuario que llegan en el ID token.
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdTokenClaims {
+    pub sub: String,
+    pub email: Option<String>,
+    #[serde(default, deserialize_with = "bool_or_string")]
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+    nonce: Option<String>,
+}
+
+// Algunos proveedores envían email_verified como "true"
+fn bool_or_string<'de, D: Deserializer<'de>>(deserializer: D) -> Result<bool, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum BoolOrString {
+        Bool(bool),
+        String(String),
+    }
+
+    Ok(match BoolOrString::deserialize(deserializer)? {
+        BoolOrString::Bool(value) => value,
+        BoolOrString::String(value) => value.eq_ignore_ascii_case("true"),
+    })
+}
+
+/// Challenge PKCE (S256) del `code_verifier`.
+pub fn code_challenge(code_verifier: &str) -> String {
+    BASE64URL_NOPAD.encode(&Sha256::digest(code_verifier.as_bytes()))
+}
+
+/// Cliente de los proveedores OpenID Connect configurados (flujo authorization code con PKCE).
+///
+/// Los metadatos y las claves de cada proveedor se descubren a partir de su issuer la
+/// primera vez que hacen falta y se guardan durante `METADATA_TTL`.
+pub struct OidcClient {
+    http: reqwest::Client,
+    providers: Vec<OidcProviderConfig>,
+    cache: RwLock<HashMap<String, Arc<CachedProvider>>>,
+}
+
+impl OidcClient {
+    pub fn from_config(config: &Config) -> Self {
+        Self::with_providers(config.oidc_providers.clone())
+    }
+
+    pub(crate) fn with_providers(providers: Vec<OidcProviderConfig>) -> Self {
+        OidcClient {
+            http: reqwest::Client::builder()
+                .timeout(HTTP_TIMEOUT)
+                .build()
+                .expect("Failed to build HTTP client"),
+            providers,
+            cache: RwLock::new(HashMap::new()),
+        }
+    }
+
+    pub fn providers(&self) -> &[OidcProviderConfig] {
+        &self.providers
+    }
+
+    pub fn provider(&self, name: &str) -> Result<&OidcProviderConfig, OidcError> {
+        self.providers
+            .iter()
+            .find(|provider| provider.name == name)
+            .ok_or_else(|| OidcError::UnknownProvider(name.to_string()))
+    }
+
+    async fn discover(&self, provider: &OidcProviderConfig, refresh: bool) -> Result<Arc<CachedProvider>, OidcError> {
+        if !refresh {
+            if let Some(cached) = self.cache.read().await.get(&provider.name) {
+                if cached.fetched_at.elapsed() < METADATA_TTL {
+                    return Ok(cached.clone());
+                }
+            }
+        }
+
+        let metadata: ProviderMetadata = self
+            .http
+            .get(format!("{}/.well-known/openid-configuration", provider.issuer))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // El documento tiene que ser del issuer configurado (OpenID Connect Discovery, 4.3)
+        if metadata.issuer.trim_end_matches('/') != provider.issuer {
+            return Err(OidcError::Discovery(format!(
+                "issuer mismatch: expected {}, got {}",
+                provider.issuer, metadata.issuer
+            )));
+        }
+
+        let jwks: JwkSet = self
+            .http
+            .get(&metadata.jwks_uri)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let cached = Arc::new(CachedProvider { metadata, jwks, fetched_at: Instant::now() });
+        self.cache.write().await.insert(provider.name.clone(), cached.clone());
+        Ok(cached)
+    }
+
+    /// URL del proveedor a la que se envía al usuario para que inicie sesión.
+    pub async fn authorization_url(
+        &self,
+        provider: &OidcProviderConfig,
+        redirect_uri: &str,
+        state: &str,
+        nonce: &str,
+        code_verifier: &str,
+    ) -> Result<String, OidcError> {
+        let cached = self.discover(provider, false).await?;
+
+        let url = Url::parse_with_params(
+            &cached.metadata.authorization_endpoint,
+            &[
+                ("response_type", "code"),
+                ("client_id", provider.client_id.as_str()),
+                ("redirect_uri", redirect_uri),
+                ("scope", provider.scopes.as_str()),
+                ("state", state),
+                ("nonce", nonce),
+                ("code_challenge", code_challenge(code_verifier).as_str()),
+                ("code_challenge_method", "S256"),
+            ],
+        )
+        .map_err(|e| OidcError::Discovery(format!("invalid authorization endpoint: {}", e)))?;
+
+        Ok(url.into())
+    }
+
+    /// Canjea el código de autorización y devuelve los claims del ID token ya validado.
+    pub async fn exchange_code(
+        &self,
+        provider: &OidcProviderConfig,
+        code: &str,
+        code_verifier: &str,
+        redirect_uri: &str,
+        nonce: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let cached = self.discover(provider, false).await?;
+
+        let response: TokenResponse = self
+            .http
+            .post(&cached.metadata.token_endpoint)
+            .basic_auth(&provider.client_id, Some(&provider.client_secret))
+            .form(&[
+                ("grant_type", "authorization_code"),
+                ("code", code),
+                ("redirect_uri", redirect_uri),
+                ("code_verifier", code_verifier),
+            ])
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        let id_token = match response {
+            TokenResponse { id_token: Some(id_token), .. } => id_token,
+            TokenResponse { error, error_description, .. } => {
+                return Err(OidcError::TokenExchange(
+                    error_description.or(error).unwrap_or("no ID token in response".to_string()),
+                ));
+            }
+        };
+
+        let claims = self.validate_id_token(provider, cached, &id_token).await?;
+        if claims.nonce.as_deref() != Some(nonce) {
+            return Err(OidcError::InvalidIdToken("nonce mismatch".to_string()));
+        }
+        Ok(claims)
+    }
+
+    async fn validate_id_token(
+        &self,
+        provider: &OidcProviderConfig,
+        mut cached: Arc<CachedProvider>,
+        id_token: &str,
+    ) -> Result<IdTokenClaims, OidcError> {
+        let invalid = |e: jsonwebtoken::errors::Error| OidcError::InvalidIdToken(e.to_string());
+
+        let header = decode_header(id_token).map_err(invalid)?;
+        // Solo firmas asimétricas: con HS256 la clave sería el client_secret
+        if matches!(header.alg, Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512) {
+            return Err(OidcError::InvalidIdToken(format!("unsupported algorithm {:?}", header.alg)));
+        }
+        let kid = header.kid.unwrap_or_default();
+
+        // Una clave desconocida puede deberse a que el proveedor las ha rotado
+        if cached.jwks.find(&kid).is_none() {
+            cached = self.discover(provider, true).await?;
+        }
+        let jwk = cached
+            .jwks
+            .find(&kid)
+            .ok_or_else(|| OidcError::InvalidIdToken(format!("unknown key id '{}'", kid)))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(invalid)?;
+
+        let mut validation = Validation::new(header.alg);
+        validation.set_audience(&[&provider.client_id]);
+        validation.set_issuer(&[&cached.metadata.issuer]);
+
+        Ok(decode::<IdTokenClaims>(id_token, &key, &validation).map_err(invalid)?.claims)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::MockIssuer;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+
+    const REDIRECT_URI: &str = "http://localhost:3000/login/mock/callback";
+
+    fn client(issuer: &MockIssuer) -> OidcClient {
+        OidcClient::with_providers(vec![issuer.provider()])
+    }
+
+    async fn exchange(client: &OidcClient, issuer: &MockIssuer, nonce: &str) -> Result<IdTokenClaims, OidcError> {
+        client
+            .exchange_code(&issuer.provider(), "code", "verifier", REDIRECT_URI, nonce)
+            .await
+    }
+
+    #[tokio::test]
+    async fn forwards_the_pkce_verifier() {
+        let issuer = MockIssuer::start();
+        let client = client(&issuer);
+        issuer.respond_with(issuer.sign("key-1", &issuer.claims("nonce", "ana@example.com", true)));
+
+        let url = client
+            .authorization_url(&issuer.provider(), REDIRECT_URI, "state", "nonce", "verifier")
+            .await
+            .unwrap();
+        let url = Url::parse(&url).unwrap();
+        let params: HashMap<_, _> = url.query_pairs().into_owned().collect();
+        assert_eq!(params["code_challenge"], code_challenge("verifier"));
+        assert_eq!(params["code_challenge_method"], "S256");
+
+        let claims = exchange(&client, &issuer, "nonce").await.unwrap();
+        assert_eq!(claims.email.as_deref(), Some("ana@example.com"));
+        assert!(claims.email_verified);
+        assert_eq!(issuer.code_verifier().as_deref(), Some("verifier"));
+    }
+
+    #[tokio::test]
+    async fn rejects_a_nonce_mismatch() {
+        let issuer = MockIssuer::start();
+        issuer.respond_with(issuer.sign("key-1", &issuer.claims("other", "ana@example.com", true)));
+
+        let result = exchange(&client(&issuer), &issuer, "nonce").await;
+        assert!(matches!(result, Err(OidcError::InvalidIdToken(e)) if e == "nonce mismatch"));
+    }
+
+    #[tokio::test]
+    async fn rejects_hmac_signed_tokens() {
+        let issuer = MockIssuer::start();
+        // Firmado con el client_secret, que el cliente conoce
+        let mut header = Header::new(Algorithm::HS256);
+        header.kid = Some("key-1".to_string());
+        let id_token = encode(
+            &header,
+            &issuer.claims("nonce", "ana@example.com", true),
+            &EncodingKey::from_secret(MockIssuer::CLIENT_SECRET.as_bytes()),
+        )
+        .unwrap();
+        issuer.respond_with(id_token);
+
+        let result = exchange(&client(&issuer), &issuer, "nonce").await;
+        assert!(matches!(result, Err(OidcError::InvalidIdToken(e)) if e.contains("HS256")));
+    }
+
+    #[tokio::test]
+    async fn refetches_the_keys_when_the_kid_is_unknown() {
+        let issuer = MockIssuer::start();
+        let client = client(&issuer);
+        issuer.respond_with(issuer.sign("key-1", &issuer.claims("nonce", "ana@example.com", true)));
+        exchange(&client, &issuer, "nonce").await.unwrap();
+        assert_eq!(issuer.jwks_fetches(), 1);
+
+        issuer.rotate_key("key-2");
+        issuer.respond_with(issuer.sign("key-2", &issuer.claims("nonce", "ana@example.com", true)));
+        exchange(&client, &issuer, "nonce").await.unwrap();
+        assert_eq!(issuer.jwks_fetches(), 2);
+    }
+
+    #[tokio::test]
+    async fn reads_email_verified_sent_as_a_string() {
+        let issuer = MockIssuer::start();
+        let mut claims = issuer.claims("nonce", "ana@example.com", true);
+        claims["email_verified"] = "true".into();
+        issuer.respond_with(issuer.sign("key-1", &claims));
+
+        assert!(exchange(&client(&issuer), &issuer, "nonce").await.unwrap().email_verified);
+    }
+}
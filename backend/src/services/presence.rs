@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tokio::time::Instant;
+
+use crate::db::DbPool;
+use crate::models::messages::ChatServerEvent;
+use crate::models::presence::PresenceStatus;
+use crate::services::chat::ChatHub;
+
+// Sin actividad del usuario durante este tiempo pasa a "away"
+const AWAY_AFTER: Duration = Duration::from_secs(5 * 60);
+// Sin WebSocket abierto ni heartbeat durante este tiempo la sesión se considera caducada
+const STALE_AFTER: Duration = Duration::from_secs(90);
+// Cada cuánto se revisan las sesiones para detectar las caducadas
+const SWEEP_INTERVAL: Duration = Duration::from_secs(30);
+
+struct UserPresence {
+    connections: usize,
+    last_seen: Instant,
+    last_active: Instant,
+    status: PresenceStatus,
+}
+
+impl UserPresence {
+    fn current_status(&self, now: Instant) -> PresenceStatus {
+        if self.connections == 0 && now.duration_since(self.last_seen) > STALE_AFTER {
+            PresenceStatus::Offline
+        } else if now.duration_since(self.last_active) > AWAY_AFTER {
+            PresenceStatus::Away
+        } else {
+            PresenceStatus::Online
+        }
+    }
+}
+
+/// Estado de presencia en memoria. No toca la base de datos ni el reloj: el instante
+/// llega como parámetro y el llamante guarda los cambios que se devuelven.
+#[derive(Default)]
+struct Presences {
+    users: HashMap<i32, UserPresence>,
+}
+
+impl Presences {
+    /// Aplica `change` a la presencia del usuario y devuelve su estado y si ha cambiado.
+    fn update(
+        &mut self,
+        user_id: i32,
+        now: Instant,
+        active: bool,
+        change: impl FnOnce(&mut UserPresence),
+    ) -> (PresenceStatus, bool) {
+        let presence = self.users.entry(user_id).or_insert_with(|| UserPresence {
+            connections: 0,
+            last_seen: now,
+            last_active: now,
+            status: PresenceStatus::Offline,
+        });
+
+        change(presence);
+        presence.last_seen = now;
+        if active {
+            presence.last_active = now;
+        }
+
+        let status = presence.current_status(now);
+        let changed = status != presence.status;
+        presence.status = status;
+        (status, changed)
+    }
+
+    /// Devuelve los estados que han cambiado con el paso del tiempo y olvida a los desconectados.
+    fn sweep(&mut self, now: Instant) -> Vec<(i32, PresenceStatus)> {
+        let mut changes = Vec::new();
+        for (user_id, presence) in self.users.iter_mut() {
+            let status = presence.current_status(now);
+            if status != presence.status {
+                presence.status = status;
+                changes.push((*user_id, status));
+            }
+        }
+        self.users.retain(|_, presence| presence.status != PresenceStatus::Offline);
+        changes
+    }
+}
+
+/// Presencia de los usuarios a partir de sus WebSockets del chat y de los heartbeats REST.
+///
+/// El estado vive en memoria y solo se escribe en `group_members.status` cuando cambia;
+/// cada cambio se avisa también por el chat de los grupos del usuario.
+#[derive(Clone)]
+pub struct PresenceTracker {
+    pool: DbPool,
+    hub: ChatHub,
+    users: Arc<Mutex<Presences>>,
+}
+
+impl PresenceTracker {
+    pub fn new(pool: DbPool, hub: ChatHub) -> Self {
+        PresenceTracker {
+            pool,
+            hub,
+            users: Arc::new(Mutex::new(Presences::default())),
+        }
+    }
+
+    /// Marca a todos como desconectados; las sesiones de un arranque anterior ya no existen.
+    pub async fn reset(&self) -> Result<(), sqlx::Error> {
+        sqlx::query!("UPDATE group_members SET status = 'offline' WHERE status IS DISTINCT FROM 'offline'")
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// Lanza la tarea que caduca periódicamente las sesiones sin actividad.
+    pub fn spawn_sweeper(self) {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(SWEEP_INTERVAL);
+            loop {
+                interval.tick().await;
+                self.sweep().await;
+            }
+        });
+    }
+
+    pub async fn connect(&self, user_id: i32) {
+        self.update(user_id, true, |presence| presence.connections += 1).await;
+    }
+
+    pub async fn disconnect(&self, user_id: i32) {
+        self.update(user_id, false, |presence| {
+            presence.connections = presence.connections.saturating_sub(1);
+        })
+        .await;
+    }
+
+    /// Registra actividad. `active` indica una acción del usuario y no solo que la sesión sigue viva.
+    pub async fn activity(&self, user_id: i32, active: bool) -> PresenceStatus {
+        self.update(user_id, active, |_| {}).await
+    }
+
+    async fn update(
+        &self,
+        user_id: i32,
+        active: bool,
+        change: impl FnOnce(&mut UserPresence),
+    ) -> PresenceStatus {
+        let (status, changed) = self
+            .users
+            .lock()
+            .unwrap()
+            .update(user_id, Instant::now(), active, change);
+
+        if changed {
+            self.persist(user_id, status).await;
+        }
+        status
+    }
+
+    async fn sweep(&self) {
+        let changes = self.users.lock().unwrap().sweep(Instant::now());
+
+        for (user_id, status) in changes {
+            self.persist(user_id, status).await;
+        }
+    }
+
+    // Guarda el estado en todos los grupos del usuario y lo difunde por sus chats
+    async fn persist(&self, user_id: i32, status: PresenceStatus) {
+        let groups = sqlx::query_scalar!(
+            r#"
+            UPDATE group_members SET status = $1
+            WHERE user_id = $2 AND role <> 'banned'
+            RETURNING group_id as "group_id!"
+            "#,
+            status.as_str(),
+            user_id
+        )
+        .fetch_all(&self.pool)
+        .await;
+
+        match groups {
+            Ok(groups) => {
+                for group_id in groups {
+                    self.hub.publish(group_id, ChatServerEvent::Presence { user_id, status });
+                }
+            }
+            Err(e) => log::error!("Failed to update presence for user {}: {}", user_id, e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const USER: i32 = 1;
+
+    fn connect(presences: &mut Presences, now: Instant) -> (PresenceStatus, bool) {
+        presences.update(USER, now, true, |presence| presence.connections += 1)
+    }
+
+    fn disconnect(presences: &mut Presences, now: Instant) -> (PresenceStatus, bool) {
+        presences.update(USER, now, false, |presence| {
+            presence.connections = presence.connections.saturating_sub(1);
+        })
+    }
+
+    #[test]
+    fn stays_online_until_the_last_connection_goes_stale() {
+        let mut presences = Presences::default();
+        let start = Instant::now();
+
+        assert_eq!(connect(&mut presences, start), (PresenceStatus::Online, true));
+        assert_eq!(connect(&mut presences, start), (PresenceStatus::Online, false));
+
+        // Con una pestaña todavía abierta no caduca
+        disconnect(&mut presences, start);
+        assert!(presences.sweep(start + STALE_AFTER * 2).is_empty());
+
+        // Al cerrar la última, sigue conectado hasta que pasa STALE_AFTER sin heartbeat
+        let closed = start + STALE_AFTER * 2;
+        assert_eq!(disconnect(&mut presences, closed), (PresenceStatus::Online, false));
+        assert_eq!(presences.users[&USER].connections, 0);
+        assert!(presences.sweep(closed + STALE_AFTER).is_empty());
+        assert_eq!(
+            presences.sweep(closed + STALE_AFTER + Duration::from_secs(1)),
+            [(USER, PresenceStatus::Offline)]
+        );
+        assert!(presences.users.is_empty());
+    }
+
+    #[test]
+    fn sweep_moves_from_online_to_away_to_offline() {
+        let mut presences = Presences::default();
+        let start = Instant::now();
+        connect(&mut presences, start);
+
+        let idle = start + AWAY_AFTER + Duration::from_secs(1);
+        assert_eq!(presences.sweep(idle), [(USER, PresenceStatus::Away)]);
+        // Sin más cambios no se vuelve a avisar
+        assert!(presences.sweep(idle).is_empty());
+
+        disconnect(&mut presences, idle);
+        let gone = idle + STALE_AFTER + Duration::from_secs(1);
+        assert_eq!(presences.sweep(gone), [(USER, PresenceStatus::Offline)]);
+        assert!(presences.users.is_empty());
+    }
+
+    #[test]
+    fn reports_a_change_only_when_the_status_changes() {
+        let mut presences = Presences::default();
+        let start = Instant::now();
+        connect(&mut presences, start);
+
+        // Un heartbeat sin actividad mantiene viva la sesión pero no cuenta como actividad
+        let idle = start + AWAY_AFTER + Duration::from_secs(1);
+        assert_eq!(presences.update(USER, idle, false, |_| {}), (PresenceStatus::Away, true));
+        assert_eq!(presences.update(USER, idle, false, |_| {}), (PresenceStatus::Away, false));
+
+        assert_eq!(presences.update(USER, idle, true, |_| {}), (PresenceStatus::Online, true));
+        assert_eq!(presences.update(USER, idle, true, |_| {}), (PresenceStatus::Online, false));
+    }
+}
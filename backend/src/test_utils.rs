@@ -0,0 +1,280 @@
+//! Utilidades de los tests.
+//!
+//! Los tests que usan base de datos usan `#[sqlx::test(migrations = false)]`, que crea una base de datos vacía para
+//! cada test a partir de `DATABASE_URL` (el usuario necesita permiso para crear bases de datos).
+//! `load_schema` carga en ella el esquema completo de `database_structure.sql`.
+
+use std::collections::HashMap;
+use std::net::TcpListener;
+use std::sync::{Arc, Mutex};
+
+use actix_web::{web, App, HttpResponse, HttpServer};
+use chrono::Utc;
+use data_encoding::BASE64URL_NOPAD;
+use jsonwebtoken::{encode, Algorithm, EncodingKey, Header};
+use ring::rand::SystemRandom;
+use ring::signature::{Ed25519KeyPair, KeyPair};
+use serde_json::{json, Value};
+use sqlx::{Executor, PgPool};
+
+use crate::config::{Config, OidcProviderConfig};
+use crate::services::mailer::{Email, Mailer, MailerError};
+use crate::utils::jwt::JwtKeys;
+use crate::AppState;
+use futures_util::future::BoxFuture;
+
+pub async fn load_schema(pool: &PgPool) {
+    pool.execute(include_str!("../database_structure.sql"))
+        .await
+        .expect("database_structure.sql should load into an empty database");
+}
+
+/// Crea un usuario activo con email verificado y devuelve su id.
+pub async fn create_user(pool: &PgPool, email: &str) -> i32 {
+    sqlx::query_scalar(
+        r#"
+        INSERT INTO users (email, password_hash, name, is_active, email_verified_at)
+        VALUES ($1, 'x', 'Test', true, NOW())
+        RETURNING id
+        "#,
+    )
+    .bind(email)
+    .fetch_one(pool)
+    .await
+    .expect("test user")
+}
+
+/// Configuración con los valores por defecto de `Config::from_env`.
+pub fn config() -> Config {
+    Config {
+        host: "127.0.0.1".to_string(),
+        port: 8080,
+        database_url: String::new(),
+        jwt_secret: "test-secret".to_string(),
+        jwt_expires_in: "1h".to_string(),
+        jwt_maxage: 3600,
+        jwt_keys_dir: "keys".to_string(),
+        jwt_key_id: None,
+        jwt_audience: "api".to_string(),
+        refresh_token_expires_in: "30d".to_string(),
+        password_reset_expires_in: "1h".to_string(),
+        email_verification_expires_in: "24h".to_string(),
+        email_verification_resend_interval: "1m".to_string(),
+        require_verified_email: false,
+        admin_emails: Vec::new(),
+        login_max_failures: 5,
+        login_ip_max_failures: 20,
+        login_failure_window: "15m".to_string(),
+        login_lockout_duration: "15m".to_string(),
+        login_backoff_base: "1s".to_string(),
+        login_backoff_max: "1m".to_string(),
+        totp_issuer: "Test".to_string(),
+        account_deletion_grace_period: "30d".to_string(),
+        account_confirmation_expires_in: "1h".to_string(),
+        oidc_providers: Vec::new(),
+        frontend_url: "http://localhost:3000".to_string(),
+        mailer: "log".to_string(),
+        mail_from: "no-reply@localhost".to_string(),
+        mail_dir: "mail".to_string(),
+    }
+}
+
+pub fn app_state(pool: &PgPool) -> web::Data<AppState> {
+    let config = config();
+    web::Data::new(AppState {
+        pool: pool.clone(),
+        jwt_keys: Arc::new(JwtKeys::from_secret(&config.jwt_secret, &config.jwt_audience, 3600)),
+        config,
+    })
+}
+
+/// Mailer que guarda los correos para que el test los lea.
+#[derive(Default)]
+pub struct RecordingMailer {
+    sent: Mutex<Vec<Email>>,
+}
+
+impl Mailer for RecordingMailer {
+    fn send(&self, email: Email) -> BoxFuture<'_, Result<(), MailerError>> {
+        self.sent.lock().unwrap().push(email);
+        Box::pin(async { Ok(()) })
+    }
+}
+
+impl RecordingMailer {
+    /// Devuelve el mailer y la referencia que esperan los handlers.
+    pub fn new() -> (Arc<Self>, web::Data<dyn Mailer>) {
+        let mailer = Arc::new(Self::default());
+        let data = web::Data::from(mailer.clone() as Arc<dyn Mailer>);
+        (mailer, data)
+    }
+
+    /// Espera a que los handlers, que envían los correos en segundo plano, manden uno y lo devuelve.
+    pub async fn next(&self) -> Option<Email> {
+        for _ in 0..100 {
+            if let Some(email) = self.sent.lock().unwrap().pop() {
+                return Some(email);
+            }
+            tokio::task::yield_now().await;
+        }
+        None
+    }
+}
+
+/// Valor del parámetro `token` del enlace de un correo.
+pub fn link_token(email: &Email) -> String {
+    let start = email.body.find("token=").expect("link with a token") + "token=".len();
+    email.body[start..].split_whitespace().next().unwrap().to_string()
+}
+
+/// Proveedor OpenID Connect de pruebas: sirve el documento de descubrimiento, las claves
+/// (JWKS) y el endpoint de tokens, que devuelve el ID token que le indique el test.
+pub struct MockIssuer {
+    pub issuer: String,
+    state: Arc<Mutex<MockIssuerState>>,
+}
+
+#[derive(Default)]
+struct MockIssuerState {
+    issuer: String,
+    // Claves publicadas en el JWKS: kid y clave privada en PKCS#8
+    keys: Vec<(String, Vec<u8>)>,
+    id_token: String,
+    code_verifier: Option<String>,
+    jwks_fetches: usize,
+}
+
+impl MockIssuer {
+    pub const CLIENT_ID: &'static str = "test-client";
+    pub const CLIENT_SECRET: &'static str = "test-secret";
+
+    /// Arranca el proveedor en un puerto libre con una clave `key-1`. Tiene su propio hilo
+    /// para poder usarlo también desde los tests de `sqlx::test`, que no corren en actix.
+    pub fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").expect("free port");
+        let issuer = format!("http://{}", listener.local_addr().unwrap());
+        let state = Arc::new(Mutex::new(MockIssuerState {
+            issuer: issuer.clone(),
+            keys: vec![("key-1".to_string(), generate_key())],
+            ..Default::default()
+        }));
+
+        let app_state = web::Data::from(state.clone());
+        std::thread::spawn(move || {
+            actix_web::rt::System::new().block_on(async move {
+                HttpServer::new(move || {
+                    App::new()
+                        .app_data(app_state.clone())
+                        .route("/.well-known/openid-configuration", web::get().to(discovery))
+                        .route("/jwks", web::get().to(jwks))
+                        .route("/token", web::post().to(token))
+                })
+                .workers(1)
+                .listen(listener)
+                .expect("mock issuer")
+                .run()
+                .await
+            })
+        });
+
+        MockIssuer { issuer, state }
+    }
+
+    pub fn provider(&self) -> OidcProviderConfig {
+        OidcProviderConfig {
+            name: "mock".to_string(),
+            display_name: "Mock".to_string(),
+            issuer: self.issuer.clone(),
+            client_id: Self::CLIENT_ID.to_string(),
+            client_secret: Self::CLIENT_SECRET.to_string(),
+            scopes: "openid email profile".to_string(),
+        }
+    }
+
+    /// Claims de un ID token válido para este proveedor.
+    pub fn claims(&self, nonce: &str, email: &str, email_verified: bool) -> Value {
+        let now = Utc::now().timestamp();
+        json!({
+            "iss": self.issuer,
+            "aud": Self::CLIENT_ID,
+            "sub": format!("subject-{}", email),
+            "iat": now,
+            "exp": now + 300,
+            "nonce": nonce,
+            "email": email,
+            "email_verified": email_verified,
+        })
+    }
+
+    /// Firma los claims con la clave publicada `kid`.
+    pub fn sign(&self, kid: &str, claims: &Value) -> String {
+        let state = self.state.lock().unwrap();
+        let (_, pkcs8) = state.keys.iter().find(|(id, _)| id == kid).expect("published key");
+        let mut header = Header::new(Algorithm::EdDSA);
+        header.kid = Some(kid.to_string());
+        encode(&header, claims, &EncodingKey::from_ed_der(pkcs8)).unwrap()
+    }
+
+    /// ID token que devolverá el endpoint de tokens.
+    pub fn respond_with(&self, id_token: String) {
+        self.state.lock().unwrap().id_token = id_token;
+    }
+
+    /// Sustituye las claves publicadas por una nueva con el `kid` indicado.
+    pub fn rotate_key(&self, kid: &str) {
+        self.state.lock().unwrap().keys = vec![(kid.to_string(), generate_key())];
+    }
+
+    /// `code_verifier` recibido en el último canje del código.
+    pub fn code_verifier(&self) -> Option<String> {
+        self.state.lock().unwrap().code_verifier.clone()
+    }
+
+    pub fn jwks_fetches(&self) -> usize {
+        self.state.lock().unwrap().jwks_fetches
+    }
+}
+
+fn generate_key() -> Vec<u8> {
+    Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap().as_ref().to_vec()
+}
+
+async fn discovery(state: web::Data<Mutex<MockIssuerState>>) -> HttpResponse {
+    let issuer = state.lock().unwrap().issuer.clone();
+    HttpResponse::Ok().json(json!({
+        "issuer": issuer,
+        "authorization_endpoint": format!("{}/authorize", issuer),
+        "token_endpoint": format!("{}/token", issuer),
+        "jwks_uri": format!("{}/jwks", issuer),
+    }))
+}
+
+async fn jwks(state: web::Data<Mutex<MockIssuerState>>) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    state.jwks_fetches += 1;
+    let keys: Vec<Value> = state
+        .keys
+        .iter()
+        .map(|(kid, pkcs8)| {
+            let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8).unwrap();
+            json!({
+                "kty": "OKP",
+                "crv": "Ed25519",
+                "use": "sig",
+                "alg": "EdDSA",
+                "kid": kid,
+                "x": BASE64URL_NOPAD.encode(key_pair.public_key().as_ref()),
+            })
+        })
+        .collect();
+    HttpResponse::Ok().json(json!({ "keys": keys }))
+}
+
+async fn token(
+    state: web::Data<Mutex<MockIssuerState>>,
+    form: web::Form<HashMap<String, String>>,
+) -> HttpResponse {
+    let mut state = state.lock().unwrap();
+    state.code_verifier = form.get("code_verifier").cloned();
+    HttpResponse::Ok().json(json!({ "id_token": state.id_token }))
+}
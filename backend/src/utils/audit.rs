@@ -0,0 +1,23 @@
+use serde_json::Value;
+
+use crate::db::DbPool;
+
+/// Guarda un evento de seguridad en `audit_log`.
+pub async fn record(
+    pool: &DbPool,
+    user_id: Option<i32>,
+    event: &str,
+    ip_address: Option<&str>,
+    details: Value,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO audit_log (user_id, event, ip_address, details) VALUES ($1, $2, $3, $4)",
+        user_id,
+        event,
+        ip_address,
+        details
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
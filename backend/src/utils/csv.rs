@@ -0,0 +1,168 @@
+/// Escribe una fila CSV (RFC 4180) terminada en CRLF.
+///
+/// Los campos con comas, comillas o saltos de línea se entrecomillan y las
+/// comillas internas se duplican. Los que empiezan por `=`, `+`, `-` o `@` llevan
+/// delante un `'` para que las hojas de cálculo no los evalúen como fórmulas.
+pub fn write_record<S: AsRef<str>>(fields: &[S]) -> String {
+    let mut line = fields
+        .iter()
+        .map(|field| escape_field(field.as_ref()))
+        .collect::<Vec<_>>()
+        .join(",");
+    line.push_str("\r\n");
+    line
+}
+
+fn escape_field(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", field)
+    } else {
+        field.to_string()
+    };
+
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Fila leída de un CSV junto con la línea del fichero en la que empieza.
+pub struct CsvRecord {
+    pub line: usize,
+    pub fields: Vec<String>,
+}
+
+/// Lee un CSV (RFC 4180) con el delimitador indicado, aceptando saltos de línea
+/// LF o CRLF y campos entrecomillados que ocupen varias líneas.
+///
+/// Se omiten las filas vacías. Devuelve un error si una comilla queda sin cerrar.
+pub fn parse(input: &str, delimiter: char) -> Result<Vec<CsvRecord>, String> {
+    let input = input.strip_prefix('\u{feff}').unwrap_or(input);
+    let mut records = Vec::new();
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut line = 1;
+    let mut record_line = 1;
+    let mut chars = input.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        if in_quotes {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => in_quotes = false,
+                '\n' => {
+                    line += 1;
+                    field.push(c);
+                }
+                _ => field.push(c),
+            }
+            continue;
+        }
+
+        match c {
+            '"' if field.is_empty() => in_quotes = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                fields.push(std::mem::take(&mut field));
+                push_record(&mut records, record_line, std::mem::take(&mut fields));
+                line += 1;
+                record_line = line;
+            }
+            c if c == delimiter => fields.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err(format!("Unterminated quoted field starting on line {}", record_line));
+    }
+
+    fields.push(field);
+    push_record(&mut records, record_line, fields);
+    Ok(records)
+}
+
+fn push_record(records: &mut Vec<CsvRecord>, line: usize, fields: Vec<String>) {
+    if fields.iter().any(|field| !field.trim().is_empty()) {
+        records.push(CsvRecord { line, fields });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(input: &str) -> Vec<Vec<String>> {
+        parse(input, ',').unwrap().into_iter().map(|record| record.fields).collect()
+    }
+
+    #[test]
+    fn parses_quoted_fields_with_delimiters() {
+        assert_eq!(rows("a,\"b,c\",d\n"), vec![vec!["a", "b,c", "d"]]);
+    }
+
+    #[test]
+    fn unescapes_doubled_quotes() {
+        assert_eq!(rows("\"dijo \"\"hola\"\"\",x"), vec![vec!["dijo \"hola\"", "x"]]);
+    }
+
+    #[test]
+    fn accepts_lf_and_crlf_line_endings() {
+        let expected = vec![vec!["a", "b"], vec!["c", "d"]];
+        assert_eq!(rows("a,b\nc,d\n"), expected);
+        assert_eq!(rows("a,b\r\nc,d\r\n"), expected);
+    }
+
+    #[test]
+    fn keeps_newlines_inside_quoted_fields() {
+        let records = parse("nota,id\n\"línea 1\r\nlínea 2\",1\nfin,2\n", ',').unwrap();
+        assert_eq!(records.len(), 3);
+        assert_eq!(records[1].fields, vec!["línea 1\r\nlínea 2", "1"]);
+        assert_eq!(records[1].line, 2);
+        assert_eq!(records[2].line, 4);
+    }
+
+    #[test]
+    fn strips_utf8_bom() {
+        assert_eq!(rows("\u{feff}fecha,nivel\n"), vec![vec!["fecha", "nivel"]]);
+    }
+
+    #[test]
+    fn trailing_newline_does_not_add_a_record() {
+        assert_eq!(rows("a,b\n"), rows("a,b"));
+        assert_eq!(rows("a,b\n\n\n").len(), 1);
+    }
+
+    #[test]
+    fn uses_the_given_delimiter() {
+        let records = parse("a;\"b;c\"\n", ';').unwrap();
+        assert_eq!(records[0].fields, vec!["a", "b;c"]);
+    }
+
+    #[test]
+    fn unterminated_quote_is_an_error() {
+        let error = parse("a,b\nc,\"sin cerrar\n", ',').err().unwrap();
+        assert!(error.contains("line 2"), "{}", error);
+    }
+
+    #[test]
+    fn written_formulas_are_not_evaluated() {
+        assert_eq!(
+            write_record(&["=HYPERLINK(\"http://x\")", "+1", "-2", "@SUM(A1)", "a=b"]),
+            "\"'=HYPERLINK(\"\"http://x\"\")\",'+1,'-2,'@SUM(A1),a=b\r\n"
+        );
+    }
+
+    #[test]
+    fn written_records_parse_back() {
+        let fields = ["simple", "con, coma", "con \"comillas\"", "dos\nlíneas"];
+        let records = parse(&write_record(&fields), ',').unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].fields, fields);
+    }
+}
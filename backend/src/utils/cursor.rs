@@ -0,0 +1,239 @@
+//! Cursores opacos para paginar listados ordenados por `(created_at, id)` de más nuevo a
+//! más antiguo. A diferencia de la paginación por páginas, no se salta ni repite elementos
+//! cuando se publica algo nuevo mientras el usuario va bajando.
+//!
+//! El cursor lleva la posición en JSON y una firma HMAC ligada al listado (`scope`), así
+//! que el cliente no puede modificarlo ni usarlo en otro listado.
+
+use chrono::{DateTime, Utc};
+use data_encoding::BASE64URL_NOPAD;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+use crate::utils::derived_key;
+use crate::utils::error::HttpError;
+
+// Bytes de la firma que se incluyen en el cursor
+const SIGNATURE_LENGTH: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Direction {
+    /// Elementos más antiguos que la posición.
+    #[serde(rename = "n")]
+    Next,
+    /// Elementos más nuevos que la posición.
+    #[serde(rename = "p")]
+    Prev,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct Cursor {
+    #[serde(rename = "t", with = "chrono::serde::ts_microseconds")]
+    pub created_at: DateTime<Utc>,
+    pub id: i32,
+    /// Solo en los listados en los que los elementos fijados van primero.
+    #[serde(rename = "f", default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(rename = "d")]
+    pub direction: Direction,
+}
+
+// `secret` es JWT_SECRET; los cursores se firman con una clave derivada solo para ellos
+fn sign(secret: &str, scope: &str, payload: &str) -> Hmac<Sha256> {
+    let key = derived_key::derive(secret, derived_key::PAGINATION_CURSOR);
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key).expect("HMAC accepts keys of any length");
+    mac.update(scope.as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+    mac
+}
+
+impl Cursor {
+    pub fn encode(&self, secret: &str, scope: &str) -> String {
+        let payload = BASE64URL_NOPAD.encode(&serde_json::to_vec(self).expect("cursor serializes"));
+        let signature = sign(secret, scope, &payload).finalize().into_bytes();
+        format!("{}.{}", payload, BASE64URL_NOPAD.encode(&signature[..SIGNATURE_LENGTH]))
+    }
+
+    pub fn decode(value: &str, secret: &str, scope: &str) -> Result<Cursor, HttpError> {
+        let invalid = || HttpError::bad_request("Invalid cursor");
+
+        let (payload, signature) = value.split_once('.').ok_or_else(invalid)?;
+        let signature = BASE64URL_NOPAD.decode(signature.as_bytes()).map_err(|_| invalid())?;
+        if signature.len() != SIGNATURE_LENGTH {
+            return Err(invalid());
+        }
+        sign(secret, scope, payload)
+            .verify_truncated_left(&signature)
+            .map_err(|_| invalid())?;
+
+        let payload = BASE64URL_NOPAD.decode(payload.as_bytes()).map_err(|_| invalid())?;
+        serde_json::from_slice(&payload).map_err(|_| invalid())
+    }
+
+    /// Si la consulta tiene que devolver los elementos más nuevos que la posición.
+    pub fn is_prev(cursor: Option<&Cursor>) -> bool {
+        cursor.is_some_and(|cursor| cursor.direction == Direction::Prev)
+    }
+}
+
+/// Página de un listado junto con los cursores para pedir la siguiente y la anterior.
+pub struct CursorPage<T> {
+    pub items: Vec<T>,
+    /// Elementos más antiguos; solo si quedan.
+    pub next_cursor: Option<String>,
+    /// Elementos más nuevos. Se devuelve aunque de momento no haya ninguno, para que el
+    /// cliente pueda preguntar más tarde por lo publicado después.
+    pub prev_cursor: Option<String>,
+}
+
+/// Construye la página a partir de las filas de la consulta.
+///
+/// La consulta tiene que pedir `limit + 1` filas, en orden descendente o, si el cursor es
+/// `Prev`, ascendente desde la posición. `key` devuelve `(created_at, id, pinned)` de una fila.
+pub fn paginate<T>(
+    mut rows: Vec<T>,
+    limit: usize,
+    cursor: Option<&Cursor>,
+    secret: &str,
+    scope: &str,
+    key: impl Fn(&T) -> (DateTime<Utc>, i32, bool),
+) -> CursorPage<T> {
+    let has_more = rows.len() > limit;
+    rows.truncate(limit);
+
+    let is_prev = Cursor::is_prev(cursor);
+    if is_prev {
+        rows.reverse();
+    }
+
+    let at = |row: &T, direction| {
+        let (created_at, id, pinned) = key(row);
+        Cursor { created_at, id, pinned, direction }.encode(secret, scope)
+    };
+
+    // Al volver hacia atrás, lo que hay después de la página es de donde venía el cliente
+    let next_cursor = match rows.last() {
+        Some(last) if has_more || is_prev => Some(at(last, Direction::Next)),
+        _ => None,
+    };
+    let prev_cursor = match rows.first() {
+        Some(first) => Some(at(first, Direction::Prev)),
+        None if is_prev => cursor.map(|cursor| cursor.encode(secret, scope)),
+        None => None,
+    };
+
+    CursorPage { items: rows, next_cursor, prev_cursor }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const SECRET: &str = "secreto de prueba";
+    const SCOPE: &str = "comments:1";
+
+    fn at(seconds: i64, id: i32, direction: Direction) -> Cursor {
+        Cursor {
+            created_at: Utc.timestamp_opt(seconds, 0).unwrap(),
+            id,
+            pinned: false,
+            direction,
+        }
+    }
+
+    fn is_invalid(result: Result<Cursor, HttpError>) -> bool {
+        matches!(result, Err(HttpError::BadRequest(message)) if message == "Invalid cursor")
+    }
+
+    // Filas `(segundos, id)` tal como las devolvería la consulta
+    fn rows(ids: &[i32]) -> Vec<(i64, i32)> {
+        ids.iter().map(|&id| (1_700_000_000 + id as i64, id)).collect()
+    }
+
+    fn page(rows: Vec<(i64, i32)>, limit: usize, cursor: Option<&Cursor>) -> CursorPage<(i64, i32)> {
+        paginate(rows, limit, cursor, SECRET, SCOPE, |&(seconds, id)| {
+            (Utc.timestamp_opt(seconds, 0).unwrap(), id, false)
+        })
+    }
+
+    fn decode(value: Option<String>) -> (i32, Direction) {
+        let cursor = Cursor::decode(&value.expect("cursor"), SECRET, SCOPE).unwrap();
+        (cursor.id, cursor.direction)
+    }
+
+    #[test]
+    fn round_trips() {
+        let cursor = Cursor { pinned: true, ..at(1_700_000_000, 42, Direction::Prev) };
+
+        let decoded = Cursor::decode(&cursor.encode(SECRET, SCOPE), SECRET, SCOPE).unwrap();
+
+        assert_eq!(decoded.created_at, cursor.created_at);
+        assert_eq!(decoded.id, 42);
+        assert!(decoded.pinned);
+        assert_eq!(decoded.direction, Direction::Prev);
+    }
+
+    #[test]
+    fn rejects_modified_cursors() {
+        let encoded = at(1_700_000_000, 42, Direction::Next).encode(SECRET, SCOPE);
+        let (_, signature) = encoded.split_once('.').unwrap();
+
+        let forged = BASE64URL_NOPAD.encode(
+            &serde_json::to_vec(&at(1_700_000_000, 7, Direction::Next)).unwrap(),
+        );
+        assert!(is_invalid(Cursor::decode(&format!("{}.{}", forged, signature), SECRET, SCOPE)));
+
+        let mut tampered = encoded.clone().into_bytes();
+        let first = &mut tampered[encoded.find('.').unwrap() + 1];
+        *first = if *first == b'A' { b'B' } else { b'A' };
+        assert!(is_invalid(Cursor::decode(&String::from_utf8(tampered).unwrap(), SECRET, SCOPE)));
+
+        assert!(is_invalid(Cursor::decode(&encoded, "otro secreto", SCOPE)));
+        assert!(is_invalid(Cursor::decode("no es un cursor", SECRET, SCOPE)));
+    }
+
+    #[test]
+    fn rejects_cursors_from_another_listing() {
+        let encoded = at(1_700_000_000, 42, Direction::Next).encode(SECRET, "comments:1");
+
+        assert!(is_invalid(Cursor::decode(&encoded, SECRET, "comments:2")));
+    }
+
+    #[test]
+    fn first_page_links_forward_only_while_more_remain() {
+        let first = page(rows(&[5, 4, 3]), 2, None);
+        assert_eq!(first.items.iter().map(|row| row.1).collect::<Vec<_>>(), [5, 4]);
+        assert_eq!(decode(first.next_cursor), (4, Direction::Next));
+        assert_eq!(decode(first.prev_cursor), (5, Direction::Prev));
+
+        let last = page(rows(&[2, 1]), 2, Some(&at(0, 3, Direction::Next)));
+        assert_eq!(last.items.len(), 2);
+        assert!(last.next_cursor.is_none());
+        assert_eq!(decode(last.prev_cursor), (2, Direction::Prev));
+    }
+
+    #[test]
+    fn prev_pages_come_back_in_descending_order() {
+        // Con `Prev` la consulta devuelve las filas en orden ascendente desde la posición
+        let prev = page(rows(&[4, 5]), 2, Some(&at(0, 3, Direction::Prev)));
+
+        assert_eq!(prev.items.iter().map(|row| row.1).collect::<Vec<_>>(), [5, 4]);
+        // Aunque no queden más nuevos, hacia atrás sigue estando la página de la que venía
+        assert_eq!(decode(prev.next_cursor), (4, Direction::Next));
+        assert_eq!(decode(prev.prev_cursor), (5, Direction::Prev));
+    }
+
+    #[test]
+    fn empty_prev_page_keeps_the_cursor() {
+        let cursor = at(1_700_000_000, 3, Direction::Prev);
+
+        let empty = page(Vec::new(), 2, Some(&cursor));
+
+        assert!(empty.items.is_empty());
+        assert!(empty.next_cursor.is_none());
+        assert_eq!(decode(empty.prev_cursor), (3, Direction::Prev));
+    }
+}
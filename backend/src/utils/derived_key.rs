@@ -0,0 +1,35 @@
+//! Claves independientes derivadas de `JWT_SECRET` para cada uso (cookies de sesión,
+//! cursores...), para que conocer o romper una no dé acceso a las demás.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Cookies de sesión.
+pub const SESSION_COOKIE: &str = "session-cookie";
+/// Firma de los cursores de paginación.
+pub const PAGINATION_CURSOR: &str = "pagination-cursor";
+
+/// Clave de 256 bits para `purpose`: HMAC-SHA256 del secreto sobre la etiqueta del uso.
+pub fn derive(secret: &str, purpose: &str) -> [u8; 32] {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(b"derived-key:");
+    mac.update(purpose.as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn each_purpose_gets_its_own_key() {
+        let secret = "un secreto cualquiera";
+        let cookie = derive(secret, SESSION_COOKIE);
+        let cursor = derive(secret, PAGINATION_CURSOR);
+
+        assert_ne!(cookie, cursor);
+        assert_eq!(cookie, derive(secret, SESSION_COOKIE));
+        assert_ne!(cookie, derive("otro secreto", SESSION_COOKIE));
+        assert_ne!(&cookie[..], secret.as_bytes());
+    }
+}
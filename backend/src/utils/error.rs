@@ -24,6 +24,12 @@ pub enum HttpError {
     
     #[error("Validation Error: {0}")]
     ValidationError(String),
+
+    #[error("Too Many Requests: {0}")]
+    TooManyRequests(String),
+
+    #[error("Bad Gateway: {0}")]
+    BadGateway(String),
     
     #[error("Internal Server Error")]
     InternalServerError,
@@ -38,6 +44,10 @@ impl HttpError {
         HttpError::Unauthorized(msg.to_string())
     }
     
+    pub fn forbidden<T: fmt::Display>(msg: T) -> Self {
+        HttpError::Forbidden(msg.to_string())
+    }
+
     pub fn not_found<T: fmt::Display>(msg: T) -> Self {
         HttpError::NotFound(msg.to_string())
     }
@@ -49,6 +59,14 @@ impl HttpError {
     pub fn validation<T: fmt::Display>(msg: T) -> Self {
         HttpError::ValidationError(msg.to_string())
     }
+
+    pub fn too_many_requests<T: fmt::Display>(msg: T) -> Self {
+        HttpError::TooManyRequests(msg.to_string())
+    }
+
+    pub fn bad_gateway<T: fmt::Display>(msg: T) -> Self {
+        HttpError::BadGateway(msg.to_string())
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -70,6 +88,8 @@ impl ResponseError for HttpError {
             HttpError::NotFound(_) => StatusCode::NOT_FOUND,
             HttpError::Conflict(_) => StatusCode::CONFLICT,
             HttpError::ValidationError(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            HttpError::TooManyRequests(_) => StatusCode::TOO_MANY_REQUESTS,
+            HttpError::BadGateway(_) => StatusCode::BAD_GATEWAY,
             HttpError::InternalServerError => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
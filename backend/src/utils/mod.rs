@@ -1,3 +1,12 @@
 pub mod error;
 pub mod jwt;
-pub mod datetime;
\ No newline at end of file
+pub mod refresh_token;
+pub mod revocation;
+pub mod secure_token;
+pub mod datetime;
+pub mod csv;
+pub mod audit;
+pub mod totp;
+pub mod cursor;
+pub mod pagination;
+pub mod derived_key;
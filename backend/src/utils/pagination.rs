@@ -0,0 +1,47 @@
+//! Paginación por número de página (`?page=&limit=`) común a los listados.
+//!
+//! Se extrae con su propio `web::Query<Pagination>`, aparte de los filtros de cada listado.
+
+use serde::Deserialize;
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+pub struct Pagination {
+    pub page: Option<i32>,
+    pub limit: Option<i32>,
+}
+
+impl Pagination {
+    /// Página (desde 1) y tamaño de página, acotado a `1..=max_limit`.
+    pub fn resolve(&self, default_limit: i32, max_limit: i32) -> (i32, i32) {
+        let page = self.page.unwrap_or(1).max(1);
+        let limit = self.limit.unwrap_or(default_limit).clamp(1, max_limit);
+        (page, limit)
+    }
+}
+
+/// Filas que hay que saltar para llegar a `page`. Se calcula en i64 para que un `page`
+/// muy grande no desborde.
+pub fn offset(page: i32, limit: i32) -> i64 {
+    (page as i64 - 1) * limit as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn applies_defaults_and_bounds() {
+        assert_eq!(Pagination::default().resolve(20, 100), (1, 20));
+        let pagination = Pagination { page: Some(-3), limit: Some(500) };
+        assert_eq!(pagination.resolve(20, 100), (1, 100));
+        let pagination = Pagination { page: Some(2), limit: Some(0) };
+        assert_eq!(pagination.resolve(20, 100), (2, 1));
+    }
+
+    #[test]
+    fn offset_does_not_overflow() {
+        assert_eq!(offset(1, 20), 0);
+        assert_eq!(offset(3, 20), 40);
+        assert_eq!(offset(i32::MAX, 100), (i32::MAX as i64 - 1) * 100);
+    }
+}
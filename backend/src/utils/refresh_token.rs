@@ -0,0 +1,273 @@
+use chrono::{Duration, Utc};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::models::auth::SessionInfo;
+use crate::utils::error::HttpError;
+use crate::utils::jwt::parse_duration;
+use crate::utils::secure_token;
+
+async fn insert_token(
+    tx: &mut Transaction<'_, Postgres>,
+    user_id: i32,
+    family_id: Uuid,
+    expires_in: &str,
+) -> Result<(i32, String), sqlx::Error> {
+    let token = secure_token::generate();
+    let expires_at = Utc::now() + Duration::seconds(parse_duration(expires_in));
+
+    let id = sqlx::query_scalar!(
+        r#"
+        INSERT INTO refresh_tokens (user_id, family_id, token_hash, expires_at)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+        user_id,
+        family_id,
+        secure_token::hash(&token),
+        expires_at
+    )
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok((id, token))
+}
+
+/// Emite un refresh token que abre una familia nueva (un inicio de sesión).
+///
+/// Devuelve el id de la familia, que identifica la sesión en los access tokens (`sid`).
+pub async fn issue(pool: &DbPool, user_id: i32, expires_in: &str) -> Result<(Uuid, String), sqlx::Error> {
+    let family_id = Uuid::new_v4();
+    let mut tx = pool.begin().await?;
+    let (_, token) = insert_token(&mut tx, user_id, family_id, expires_in).await?;
+    tx.commit().await?;
+    Ok((family_id, token))
+}
+
+/// Canjea un refresh token por otro de la misma familia y devuelve el usuario, la familia y el token nuevo.
+///
+/// Cada token solo se puede usar una vez. Si llega uno ya usado o revocado, alguien
+/// tiene una copia: se revoca la familia entera y ambos tendrán que volver a iniciar sesión.
+pub async fn rotate(pool: &DbPool, token: &str, expires_in: &str) -> Result<(i32, Uuid, String), HttpError> {
+    let mut tx = pool.begin().await?;
+
+    let stored = sqlx::query!(
+        r#"
+        SELECT id, user_id, family_id, expires_at, used_at, revoked_at
+        FROM refresh_tokens
+        WHERE token_hash = $1
+        FOR UPDATE
+        "#,
+        secure_token::hash(token)
+    )
+    .fetch_optional(&mut *tx)
+    .await?
+    .ok_or_else(|| HttpError::unauthorized("Invalid refresh token"))?;
+
+    if stored.used_at.is_some() || stored.revoked_at.is_some() {
+        revoke_family_in(&mut tx, stored.family_id).await?;
+        tx.commit().await?;
+        log::warn!(
+            "Refresh token reuse detected for user {}; token family revoked",
+            stored.user_id
+        );
+        return Err(HttpError::unauthorized(if stored.revoked_at.is_some() {
+            "Refresh token has been revoked"
+        } else {
+            "Refresh token has already been used"
+        }));
+    }
+
+    if stored.expires_at <= Utc::now() {
+        return Err(HttpError::unauthorized("Refresh token has expired"));
+    }
+
+    let (new_id, new_token) = insert_token(&mut tx, stored.user_id, stored.family_id, expires_in).await?;
+
+    sqlx::query!(
+        "UPDATE refresh_tokens SET used_at = NOW(), replaced_by = $1 WHERE id = $2",
+        new_id,
+        stored.id
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+    Ok((stored.user_id, stored.family_id, new_token))
+}
+
+async fn revoke_family_in(
+    tx: &mut Transaction<'_, Postgres>,
+    family_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE family_id = $1 AND revoked_at IS NULL",
+        family_id
+    )
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+/// Cierra una sesión del usuario. Devuelve false si no existe o ya estaba cerrada.
+pub async fn revoke_family(pool: &DbPool, user_id: i32, family_id: Uuid) -> Result<bool, sqlx::Error> {
+    let revoked = sqlx::query!(
+        r#"
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE family_id = $1 AND user_id = $2 AND revoked_at IS NULL
+        "#,
+        family_id,
+        user_id
+    )
+    .execute(pool)
+    .await?
+    .rows_affected();
+
+    Ok(revoked > 0)
+}
+
+/// Cierra todas las sesiones del usuario.
+pub async fn revoke_all(pool: &DbPool, user_id: i32) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE refresh_tokens SET revoked_at = NOW() WHERE user_id = $1 AND revoked_at IS NULL",
+        user_id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Cierra todas las sesiones del usuario salvo la indicada.
+pub async fn revoke_others(pool: &DbPool, user_id: i32, keep: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE refresh_tokens SET revoked_at = NOW()
+        WHERE user_id = $1 AND family_id <> $2 AND revoked_at IS NULL
+        "#,
+        user_id,
+        keep
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Sesiones abiertas del usuario: familias sin revocar cuyo último token no ha caducado.
+pub async fn list_sessions(pool: &DbPool, user_id: i32) -> Result<Vec<SessionInfo>, sqlx::Error> {
+    let sessions = sqlx::query!(
+        r#"
+        SELECT family_id,
+               MIN(created_at) as "started_at!",
+               MAX(created_at) as "last_refreshed_at!",
+               MAX(expires_at) as "expires_at!"
+        FROM refresh_tokens
+        WHERE user_id = $1
+        GROUP BY family_id
+        HAVING BOOL_AND(revoked_at IS NULL) AND MAX(expires_at) > NOW()
+        ORDER BY MAX(created_at) DESC
+        "#,
+        user_id
+    )
+    .fetch_all(pool)
+    .await?
+    .into_iter()
+    .map(|row| SessionInfo {
+        id: row.family_id,
+        started_at: row.started_at,
+        last_refreshed_at: row.last_refreshed_at,
+        expires_at: row.expires_at,
+        current: false,
+    })
+    .collect();
+
+    Ok(sessions)
+}
+
+/// Revisa si la sesión a la que pertenece un access token se ha cerrado.
+pub async fn is_family_revoked(pool: &DbPool, family_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar!(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM refresh_tokens WHERE family_id = $1 AND revoked_at IS NOT NULL
+        ) as "revoked!"
+        "#,
+        family_id
+    )
+    .fetch_one(pool)
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{create_user, load_schema};
+
+    const EXPIRES_IN: &str = "30d";
+
+    #[sqlx::test(migrations = false)]
+    async fn rotation_replaces_the_token_within_the_family(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "ana@example.com").await;
+        let (family_id, token) = issue(&pool, user_id, EXPIRES_IN).await.unwrap();
+
+        let (rotated_user, rotated_family, new_token) = rotate(&pool, &token, EXPIRES_IN).await.unwrap();
+        assert_eq!((rotated_user, rotated_family), (user_id, family_id));
+        assert_ne!(new_token, token);
+
+        // El nuevo también rota, y la sesión sigue abierta
+        assert!(rotate(&pool, &new_token, EXPIRES_IN).await.is_ok());
+        assert!(!is_family_revoked(&pool, family_id).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn reusing_a_token_revokes_the_family(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "ana@example.com").await;
+        let (family_id, token) = issue(&pool, user_id, EXPIRES_IN).await.unwrap();
+        let (_, _, new_token) = rotate(&pool, &token, EXPIRES_IN).await.unwrap();
+
+        let replayed = rotate(&pool, &token, EXPIRES_IN).await;
+        assert!(matches!(replayed, Err(HttpError::Unauthorized(_))));
+        assert!(is_family_revoked(&pool, family_id).await.unwrap());
+
+        // El token legítimo cae con la familia
+        assert!(rotate(&pool, &new_token, EXPIRES_IN).await.is_err());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn expired_tokens_are_rejected(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "ana@example.com").await;
+        let (family_id, token) = issue(&pool, user_id, EXPIRES_IN).await.unwrap();
+        sqlx::query("UPDATE refresh_tokens SET expires_at = NOW() - INTERVAL '1 second'")
+            .execute(&pool)
+            .await
+            .unwrap();
+
+        let result = rotate(&pool, &token, EXPIRES_IN).await;
+        assert!(matches!(result, Err(HttpError::Unauthorized(e)) if e.contains("expired")));
+        // Caducar no es una señal de robo: la familia no se revoca
+        assert!(!is_family_revoked(&pool, family_id).await.unwrap());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn revoke_others_keeps_the_current_session(pool: DbPool) {
+        load_schema(&pool).await;
+        let user_id = create_user(&pool, "ana@example.com").await;
+        let other_user = create_user(&pool, "other@example.com").await;
+        let (current, current_token) = issue(&pool, user_id, EXPIRES_IN).await.unwrap();
+        let (other, _) = issue(&pool, user_id, EXPIRES_IN).await.unwrap();
+        let (foreign, _) = issue(&pool, other_user, EXPIRES_IN).await.unwrap();
+
+        revoke_others(&pool, user_id, current).await.unwrap();
+
+        assert!(!is_family_revoked(&pool, current).await.unwrap());
+        assert!(is_family_revoked(&pool, other).await.unwrap());
+        assert!(!is_family_revoked(&pool, foreign).await.unwrap());
+        assert!(rotate(&pool, &current_token, EXPIRES_IN).await.is_ok());
+
+        let sessions = list_sessions(&pool, user_id).await.unwrap();
+        assert_eq!(sessions.iter().map(|session| session.id).collect::<Vec<_>>(), [current]);
+    }
+}
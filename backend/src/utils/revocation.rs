@@ -0,0 +1,118 @@
+use chrono::{TimeZone, Utc};
+use uuid::Uuid;
+
+use crate::db::DbPool;
+use crate::models::auth::TokenClaims;
+use crate::utils::refresh_token;
+
+fn parse_uuid(value: &str) -> Option<Uuid> {
+    Uuid::parse_str(value).ok()
+}
+
+/// Revoca un access token concreto hasta que caduque.
+pub async fn revoke_access_token(pool: &DbPool, claims: &TokenClaims) -> Result<(), sqlx::Error> {
+    let Some(jti) = parse_uuid(&claims.jti) else { return Ok(()) };
+    let expires_at = Utc
+        .timestamp_opt(claims.exp as i64, 0)
+        .single()
+        .unwrap_or_else(Utc::now);
+
+    // Los tokens ya caducados no hace falta recordarlos
+    sqlx::query!("DELETE FROM revoked_tokens WHERE expires_at < NOW()")
+        .execute(pool)
+        .await?;
+
+    sqlx::query!(
+        r#"
+        INSERT INTO revoked_tokens (jti, user_id, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (jti) DO NOTHING
+        "#,
+        jti,
+        claims.sub.parse::<i32>().ok(),
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok(())
+}
+
+/// Un token está revocado si lo está su jti o si se ha cerrado su sesión.
+pub async fn is_revoked(pool: &DbPool, claims: &TokenClaims) -> Result<bool, sqlx::Error> {
+    let (Some(jti), Some(sid)) = (parse_uuid(&claims.jti), parse_uuid(&claims.sid)) else {
+        return Ok(true);
+    };
+
+    let jti_revoked = sqlx::query_scalar!(
+        r#"SELECT EXISTS(SELECT 1 FROM revoked_tokens WHERE jti = $1) as "revoked!""#,
+        jti
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if jti_revoked {
+        return Ok(true);
+    }
+
+    refresh_token::is_family_revoked(pool, sid).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::middleware::authenticate;
+    use crate::test_utils::{self, create_user, load_schema};
+    use crate::utils::error::HttpError;
+    use crate::utils::jwt::JwtKeys;
+
+    // Abre una sesión y devuelve dos access tokens de ella
+    async fn session(pool: &DbPool, keys: &JwtKeys, user_id: i32) -> (Uuid, String, String) {
+        let (session_id, _) = refresh_token::issue(pool, user_id, "30d").await.unwrap();
+        (
+            session_id,
+            keys.encode_token(user_id, session_id).unwrap(),
+            keys.encode_token(user_id, session_id).unwrap(),
+        )
+    }
+
+    fn keys() -> JwtKeys {
+        let config = test_utils::config();
+        JwtKeys::from_secret(&config.jwt_secret, &config.jwt_audience, 3600)
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn revoked_access_tokens_are_rejected(pool: DbPool) {
+        load_schema(&pool).await;
+        let keys = keys();
+        let user_id = create_user(&pool, "ana@example.com").await;
+        let (_, token, other_token) = session(&pool, &keys, user_id).await;
+
+        let (_, claims) = authenticate(&pool, &keys, &token).await.unwrap();
+        revoke_access_token(&pool, &claims).await.unwrap();
+
+        assert!(is_revoked(&pool, &claims).await.unwrap());
+        let result = authenticate(&pool, &keys, &token).await;
+        assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+
+        // Solo cae ese token, no la sesión
+        assert!(authenticate(&pool, &keys, &other_token).await.is_ok());
+    }
+
+    #[sqlx::test(migrations = false)]
+    async fn closing_a_session_rejects_its_access_tokens(pool: DbPool) {
+        load_schema(&pool).await;
+        let keys = keys();
+        let user_id = create_user(&pool, "ana@example.com").await;
+        let (session_id, token, other_token) = session(&pool, &keys, user_id).await;
+        let (_, _, unrelated_token) = session(&pool, &keys, user_id).await;
+
+        assert!(refresh_token::revoke_family(&pool, user_id, session_id).await.unwrap());
+
+        for token in [&token, &other_token] {
+            let result = authenticate(&pool, &keys, token).await;
+            assert!(matches!(result, Err(HttpError::Unauthorized(_))));
+        }
+        assert!(authenticate(&pool, &keys, &unrelated_token).await.is_ok());
+    }
+}
@@ -0,0 +1,19 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+// Longitud de los tokens opacos que recibe el cliente (~380 bits de entropía)
+const TOKEN_LENGTH: usize = 64;
+
+/// Genera un token aleatorio para enviar al cliente.
+pub fn generate() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(TOKEN_LENGTH)
+        .map(char::from)
+        .collect()
+}
+
+/// Hash con el que se guarda el token; el valor en claro solo lo conoce el cliente.
+pub fn hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
@@ -0,0 +1,162 @@
+//! Códigos de un solo uso basados en tiempo (TOTP, RFC 6238) con los parámetros que
+//! entienden todas las apps de autenticación: HMAC-SHA1, 6 dígitos y pasos de 30 segundos.
+
+use data_encoding::BASE32_NOPAD;
+use hmac::{Hmac, Mac};
+use rand::{distributions::Alphanumeric, Rng, RngCore};
+use sha1::Sha1;
+
+const STEP_SECONDS: i64 = 30;
+const DIGITS: u32 = 6;
+// Pasos de margen a cada lado por si el reloj del móvil va desfasado
+const ALLOWED_DRIFT: i64 = 1;
+const SECRET_BYTES: usize = 20;
+
+/// Genera un secreto nuevo codificado en base32.
+pub fn generate_secret() -> String {
+    let mut secret = [0u8; SECRET_BYTES];
+    rand::thread_rng().fill_bytes(&mut secret);
+    BASE32_NOPAD.encode(&secret)
+}
+
+/// URI `otpauth://` que las apps de autenticación leen desde un código QR.
+pub fn provisioning_uri(issuer: &str, account: &str, secret: &str) -> String {
+    format!(
+        "otpauth://totp/{}:{}?secret={}&issuer={}&algorithm=SHA1&digits={}&period={}",
+        encode_uri_component(issuer),
+        encode_uri_component(account),
+        secret,
+        encode_uri_component(issuer),
+        DIGITS,
+        STEP_SECONDS
+    )
+}
+
+fn encode_uri_component(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => (b as char).to_string(),
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn code_at(key: &[u8], step: i64) -> u32 {
+    let mut mac = Hmac::<Sha1>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(&step.to_be_bytes());
+    let digest = mac.finalize().into_bytes();
+
+    // Truncado dinámico (RFC 4226, sección 5.3)
+    let offset = (digest[digest.len() - 1] & 0x0f) as usize;
+    let value = u32::from_be_bytes([digest[offset], digest[offset + 1], digest[offset + 2], digest[offset + 3]]) & 0x7fff_ffff;
+    value % 10u32.pow(DIGITS)
+}
+
+/// Comprueba un código para el instante `now` (segundos Unix).
+///
+/// Devuelve el paso al que corresponde para que quien llama pueda rechazar los pasos
+/// ya usados y el mismo código no sirva dos veces.
+pub fn verify(secret: &str, code: &str, now: i64) -> Option<i64> {
+    let code = code.trim().replace(' ', "");
+    if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let code: u32 = code.parse().ok()?;
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).ok()?;
+
+    let current = now / STEP_SECONDS;
+    (current - ALLOWED_DRIFT..=current + ALLOWED_DRIFT).find(|&step| code_at(&key, step) == code)
+}
+
+/// Genera códigos de recuperación con el formato `xxxxx-xxxxx`.
+pub fn generate_recovery_codes(count: usize) -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..count)
+        .map(|_| {
+            let raw: String = (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(10)
+                .map(|c| (c as char).to_ascii_lowercase())
+                .collect();
+            format!("{}-{}", &raw[..5], &raw[5..])
+        })
+        .collect()
+}
+
+/// Forma en la que se guarda y compara un código de recuperación (sin guion ni espacios).
+pub fn normalize_recovery_code(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Código vigente en el instante `now`, para los tests de quien usa este módulo.
+#[cfg(test)]
+pub(crate) fn code_for(secret: &str, now: i64) -> String {
+    let key = BASE32_NOPAD.decode(secret.as_bytes()).expect("base32 secret");
+    format!("{:06}", code_at(&key, now / STEP_SECONDS))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Secreto de los vectores de prueba del RFC 6238 (apéndice B) para SHA-1
+    const RFC_KEY: &[u8] = b"12345678901234567890";
+
+    fn rfc_secret() -> String {
+        BASE32_NOPAD.encode(RFC_KEY)
+    }
+
+    #[test]
+    fn matches_rfc_6238_sha1_vectors() {
+        // Los vectores son de 8 dígitos; con 6 dígitos salen sus 6 últimos
+        let vectors: [(i64, &str); 6] = [
+            (59, "94287082"),
+            (1111111109, "07081804"),
+            (1111111111, "14050471"),
+            (1234567890, "89005924"),
+            (2000000000, "69279037"),
+            (20000000000, "65353130"),
+        ];
+
+        for (time, expected) in vectors {
+            let code = format!("{:06}", code_at(RFC_KEY, time / STEP_SECONDS));
+            assert_eq!(code, expected[2..], "T = {}", time);
+            assert_eq!(verify(&rfc_secret(), &expected[2..], time), Some(time / STEP_SECONDS));
+        }
+    }
+
+    #[test]
+    fn accepts_one_step_of_drift_each_way() {
+        let secret = rfc_secret();
+        let time = 1111111109;
+        let step = time / STEP_SECONDS;
+        let code = code_for(&secret, time);
+
+        assert_eq!(verify(&secret, &code, time - STEP_SECONDS), Some(step));
+        assert_eq!(verify(&secret, &code, time + STEP_SECONDS), Some(step));
+        assert_eq!(verify(&secret, &code, time - 2 * STEP_SECONDS), None);
+        assert_eq!(verify(&secret, &code, time + 2 * STEP_SECONDS), None);
+    }
+
+    #[test]
+    fn rejects_malformed_codes() {
+        let secret = rfc_secret();
+        assert_eq!(verify(&secret, "081 804", 1111111109), Some(1111111109 / STEP_SECONDS));
+        assert_eq!(verify(&secret, "08180", 1111111109), None);
+        assert_eq!(verify(&secret, "07081804", 1111111109), None);
+        assert_eq!(verify(&secret, "08180a", 1111111109), None);
+        assert_eq!(verify("no es base32!", "081804", 1111111109), None);
+    }
+
+    #[test]
+    fn normalizes_recovery_codes() {
+        let codes = generate_recovery_codes(10);
+        assert_eq!(codes.len(), 10);
+        assert!(codes.iter().all(|code| code.len() == 11 && code.as_bytes()[5] == b'-'));
+        assert_eq!(normalize_recovery_code(" AbCdE-12345 "), "abcde12345");
+    }
+}
@@ -45,15 +45,17 @@ export default function Posts() {
     tags: ''
   });
 
-  const categories = [
-    { id: 'all', name: 'Todo' },
-    { id: 'superacion', name: 'Superación' },
-    { id: 'tecnicas', name: 'Técnicas' },
-    { id: 'consejos', name: 'Consejos' },
-    { id: 'testimonios', name: 'Testimonios' },
-    { id: 'logros', name: 'Logros' },
-    { id: 'motivacion', name: 'Motivación' }
-  ];
+  const [categories, setCategories] = useState<Category[]>([{ id: 'all', name: 'Todo' }]);
+
+  // Cargar categorías; los posts guardan el nombre de la categoría
+  useEffect(() => {
+    postsService.getCategories().then((loaded) => {
+      setCategories([
+        { id: 'all', name: 'Todo' },
+        ...loaded.map((category) => ({ id: category.name, name: category.name }))
+      ]);
+    });
+  }, []);
 
   // Cargar posts
   useEffect(() => {
@@ -4,6 +4,7 @@ import { useState } from 'react';
 import Link from 'next/link';
 import { useRouter } from 'next/navigation';
 import Header from '../../../components/Header';
+import { authService } from '@/services/auth.service';
 
 export default function ChangePassword() {
   const [currentPassword, setCurrentPassword] = useState('');
@@ -33,15 +34,19 @@ export default function ChangePassword() {
     setLoading(true);
 
     try {
-      // Simulación de cambio de contraseña
-      await new Promise(resolve => setTimeout(resolve, 1000));
+      await authService.changePassword(currentPassword, newPassword);
       
       setSuccess(true);
       setCurrentPassword('');
       setNewPassword('');
       setConfirmPassword('');
     } catch (err) {
-      setError('Error al cambiar la contraseña. Por favor, inténtalo de nuevo.');
+      const message = err instanceof Error ? err.message : '';
+      setError(
+        message.includes('Current password is incorrect')
+          ? 'La contraseña actual no es correcta'
+          : 'Error al cambiar la contraseña. Por favor, inténtalo de nuevo.'
+      );
       console.error('Change password error:', err);
     } finally {
       setLoading(false);
@@ -0,0 +1,125 @@
+'use client';
+
+import { useState, useEffect, useRef } from 'react';
+import Link from 'next/link';
+import { useParams, useSearchParams } from 'next/navigation';
+import { authService } from '@/services/auth.service';
+
+export default function OidcCallback() {
+  const [error, setError] = useState('');
+  const [mfaToken, setMfaToken] = useState('');
+  const [code, setCode] = useState('');
+  const [submitting, setSubmitting] = useState(false);
+  // El código del proveedor solo se puede canjear una vez
+  const started = useRef(false);
+
+  const params = useParams<{ provider: string }>();
+  const searchParams = useSearchParams();
+
+  // Recargamos para que el AuthProvider lea la sesión nueva de las cookies
+  const goToDashboard = () => {
+    window.location.href = '/dashboard';
+  };
+
+  useEffect(() => {
+    if (started.current) {
+      return;
+    }
+    started.current = true;
+
+    const authCode = searchParams.get('code');
+    const state = searchParams.get('state');
+
+    if (searchParams.get('error') || !authCode || !state) {
+      setError('No se ha podido iniciar sesión con el proveedor');
+      return;
+    }
+
+    const complete = async () => {
+      try {
+        const response = await authService.completeOidcLogin(params.provider, authCode, state);
+        if (response.status === 'mfa_required' && response.mfa_token) {
+          setMfaToken(response.mfa_token);
+          return;
+        }
+        goToDashboard();
+      } catch (err) {
+        setError(err instanceof Error ? err.message : 'No se ha podido iniciar sesión con el proveedor');
+        console.error('OIDC login error:', err);
+      }
+    };
+
+    complete();
+  }, [params.provider, searchParams]);
+
+  const handleSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setError('');
+    setSubmitting(true);
+
+    try {
+      await authService.loginTwoFactor(mfaToken, code);
+      goToDashboard();
+    } catch (err) {
+      setError('El código no es válido');
+      console.error('Two-factor login error:', err);
+    } finally {
+      setSubmitting(false);
+    }
+  };
+
+  if (mfaToken) {
+    return (
+      <div className="flex min-h-screen flex-col items-center justify-center p-8">
+        <div className="w-full max-w-md p-8 space-y-6 rounded-lg bg-white shadow-md">
+          <h2 className="text-center text-2xl font-bold text-gray-900">Verificación en dos pasos</h2>
+          {error && (
+            <div className="rounded-md bg-red-50 p-4">
+              <div className="text-sm text-red-700">{error}</div>
+            </div>
+          )}
+          <form className="space-y-4" onSubmit={handleSubmit}>
+            <input
+              type="text"
+              inputMode="numeric"
+              autoComplete="one-time-code"
+              required
+              className="block w-full appearance-none rounded-md border border-gray-300 px-3 py-2 text-gray-900 placeholder-gray-500 focus:border-blue-500 focus:outline-none focus:ring-blue-500"
+              placeholder="Código de verificación"
+              value={code}
+              onChange={(e) => setCode(e.target.value)}
+            />
+            <button
+              type="submit"
+              disabled={submitting}
+              className="flex w-full justify-center rounded-md border border-transparent bg-blue-600 px-4 py-2 text-sm font-medium text-white hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-blue-500 focus:ring-offset-2 disabled:bg-blue-300"
+            >
+              {submitting ? 'Verificando...' : 'Verificar'}
+            </button>
+          </form>
+        </div>
+      </div>
+    );
+  }
+
+  return (
+    <div className="flex min-h-screen flex-col items-center justify-center p-8">
+      <div className="w-full max-w-md p-8 space-y-6 rounded-lg bg-white shadow-md">
+        {error ? (
+          <>
+            <div className="rounded-md bg-red-50 p-4">
+              <div className="text-sm text-red-700">{error}</div>
+            </div>
+            <div className="flex justify-center">
+              <Link href="/login" className="font-medium text-blue-600 hover:text-blue-500">
+                Volver a iniciar sesión
+              </Link>
+            </div>
+          </>
+        ) : (
+          <p className="text-center">Iniciando sesión...</p>
+        )}
+      </div>
+    </div>
+  );
+}
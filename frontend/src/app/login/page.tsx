@@ -1,30 +1,98 @@
 'use client';
 
-import { useState } from 'react';
+import { useState, useEffect } from 'react';
 import Link from 'next/link';
 import { useRouter } from 'next/navigation';
 import { useAuth } from '../providers/AuthProvider';
+import { authService, OidcProvider } from '@/services/auth.service';
 
 export default function Login() {
   const [email, setEmail] = useState('');
   const [password, setPassword] = useState('');
   const [error, setError] = useState('');
+  const [mfaToken, setMfaToken] = useState('');
+  const [code, setCode] = useState('');
   const router = useRouter();
-  const { login, loading } = useAuth();
+  const { login, loginTwoFactor, loading } = useAuth();
+  const [providers, setProviders] = useState<OidcProvider[]>([]);
+
+  useEffect(() => {
+    authService.getOidcProviders()
+      .then(setProviders)
+      .catch((err) => console.error('Error loading login providers:', err));
+  }, []);
+
+  const handleProviderLogin = async (provider: string) => {
+    setError('');
+    try {
+      window.location.href = await authService.startOidcLogin(provider);
+    } catch (err) {
+      setError('No se ha podido conectar con el proveedor. Por favor, inténtalo de nuevo.');
+      console.error('OIDC login error:', err);
+    }
+  };
 
   const handleSubmit = async (e: React.FormEvent) => {
     e.preventDefault();
     setError('');
 
     try {
-      await login(email, password);
-      // No need to redirect, the auth context will handle it
+      const token = await login(email, password);
+      // Con verificación en dos pasos falta el código; si no, el contexto ya ha redirigido
+      if (token) {
+        setMfaToken(token);
+      }
     } catch (err) {
       setError('Error al iniciar sesión. Por favor, inténtalo de nuevo.');
       console.error('Login error:', err);
     }
   };
 
+  const handleTwoFactorSubmit = async (e: React.FormEvent) => {
+    e.preventDefault();
+    setError('');
+
+    try {
+      await loginTwoFactor(mfaToken, code, email);
+    } catch (err) {
+      setError('El código no es válido');
+      console.error('Two-factor login error:', err);
+    }
+  };
+
+  if (mfaToken) {
+    return (
+      <div className="flex min-h-screen flex-col items-center justify-center p-8">
+        <div className="w-full max-w-md p-8 space-y-6 rounded-lg bg-white shadow-md">
+          <h2 className="text-center text-2xl font-bold text-gray-900">Verificación en dos pasos</h2>
+          {error && (
+            <div className="rounded-md bg-red-50 p-4">
+              <div className="text-sm text-red-700">{error}</div>
+            </div>
+          )}
+          <form className="space-y-4" onSubmit={handleTwoFactorSubmit}>
+            <input
+              type="text"
+              inputMode="numeric"
+              autoComplete="one-time-code"
+              required
+              className="block w-full appearance-none rounded-md border border-gray-300 px-3 py-2 text-gray-900 placeholder-gray-500 focus:border-blue-500 focus:outline-none focus:ring-blue-500"
+              placeholder="Código de verificación"
+              value={code}
+              onChange={(e) => setCode(e.target.value)}
+            />
+            <button
+              type="submit"
+              disabled={loading}
+              className="flex w-full justify-center rounded-md border border-transparent bg-blue-600 px-4 py-2 text-sm font-medium text-white hover:bg-blue-700 focus:outline-none focus:ring-2 focus:ring-blue-500 focus:ring-offset-2 disabled:bg-blue-300"
+            >
+              {loading ? 'Verificando...' : 'Verificar'}
+            </button>
+          </form>
+        </div>
+      </div>
+    );
+  }
 
   return (
     <div className="flex min-h-screen flex-col items-center justify-center p-8">
@@ -108,6 +176,22 @@ export default function Login() {
             </button>
           </div>
         </form>
+
+        {providers.length > 0 && (
+          <div className="space-y-3">
+            <div className="text-center text-sm text-gray-500">o continúa con</div>
+            {providers.map((provider) => (
+              <button
+                key={provider.name}
+                type="button"
+                onClick={() => handleProviderLogin(provider.name)}
+                className="flex w-full justify-center rounded-md border border-gray-300 bg-white px-4 py-2 text-sm font-medium text-gray-700 hover:bg-gray-50 focus:outline-none focus:ring-2 focus:ring-blue-500 focus:ring-offset-2"
+              >
+                {provider.display_name}
+              </button>
+            ))}
+          </div>
+        )}
         
         <div className="mt-4 text-center text-sm">
           <span className="text-gray-600">¿No tienes una cuenta? </span>
@@ -3,7 +3,7 @@
 import { createContext, useContext, useState, useEffect, ReactNode } from 'react';
 import { useRouter } from 'next/navigation';
 import Cookies from 'js-cookie';
-import { authService } from '../../services/auth.service';
+import { authService, AuthResponse } from '../../services/auth.service';
 
 interface User {
   id: string;
@@ -15,7 +15,9 @@ interface User {
 interface AuthContextType {
   user: User | null;
   loading: boolean;
-  login: (email: string, password: string) => Promise<void>;
+  // Devuelve el mfa_token si la cuenta tiene verificación en dos pasos
+  login: (email: string, password: string) => Promise<string | null>;
+  loginTwoFactor: (mfaToken: string, code: string, email: string) => Promise<void>;
   register: (name: string, email: string, password: string) => Promise<void>;
   logout: () => Promise<void>;
   recoverPassword: (email: string) => Promise<void>;
@@ -65,6 +67,30 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     checkAuth();
   }, []);
 
+  // Guarda la sesión de una respuesta de login y entra en el dashboard
+  const startSession = (response: AuthResponse, email: string) => {
+    if (response.status !== 'success' || !response.token || !response.user) {
+      throw new Error('No se recibieron datos de respuesta');
+    }
+
+    // Store auth token and user data
+    Cookies.set('authToken', response.token, { expires: 7 });
+
+    // Create a user object with the required fields
+    const user = {
+      id: response.user.id.toString(), // Convert number to string to match User interface
+      email: response.user.email,
+      name: email.split('@')[0] // Use email prefix as name if not provided
+    };
+
+    // Store user data
+    Cookies.set('userData', JSON.stringify(user), { expires: 7 });
+    setUser(user);
+
+    // Redirect to dashboard
+    router.push('/dashboard');
+  };
+
   // Login function
   const login = async (email: string, password: string) => {
     setLoading(true);
@@ -79,26 +105,13 @@ export function AuthProvider({ children }: { children: ReactNode }) {
         throw new Error(response.error || 'Error al iniciar sesión');
       }
 
-      if (response.status === 'success' && response.token && response.user) {
-        // Store auth token and user data
-        Cookies.set('authToken', response.token, { expires: 7 });
-        
-        // Create a user object with the required fields
-        const user = {
-          id: response.user.id.toString(), // Convert number to string to match User interface
-          email: response.user.email,
-          name: email.split('@')[0] // Use email prefix as name if not provided
-        };
-        
-        // Store user data
-        Cookies.set('userData', JSON.stringify(user), { expires: 7 });
-        setUser(user);
-        
-        // Redirect to dashboard
-        router.push('/dashboard');
-      } else {
-        throw new Error('No se recibieron datos de respuesta');
+      // Falta el segundo paso: la página pide el código y llama a loginTwoFactor
+      if (response.status === 'mfa_required' && response.mfa_token) {
+        return response.mfa_token;
       }
+
+      startSession(response, email);
+      return null;
     } catch (error) {
       console.error('Login error:', error);
       throw error instanceof Error ? error : new Error('Error al iniciar sesión');
@@ -107,6 +120,20 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     }
   };
 
+  // Second login step for accounts with two-factor authentication
+  const loginTwoFactor = async (mfaToken: string, code: string, email: string) => {
+    setLoading(true);
+    try {
+      const response = await authService.loginTwoFactor(mfaToken, code);
+      startSession(response, email);
+    } catch (error) {
+      console.error('Two-factor login error:', error);
+      throw error instanceof Error ? error : new Error('El código no es válido');
+    } finally {
+      setLoading(false);
+    }
+  };
+
   // Register function
   const register = async (name: string, email: string, password: string) => {
     setLoading(true);
@@ -173,13 +200,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
   // Recover password function
   const recoverPassword = async (email: string) => {
     try {
-      // Simulate API call to send recovery email
-      await new Promise(resolve => setTimeout(resolve, 1000));
-      
-      // In a real app, the API would:
-      // 1. Generate a token and store it in the database with an expiry
-      // 2. Send an email with a link containing the token
-      console.log(`Recovery email would be sent to: ${email}`);
+      await authService.forgotPassword(email);
       
       // Success is handled in the component
     } catch (error) {
@@ -191,14 +212,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
   // Reset password function
   const resetPassword = async (token: string, newPassword: string) => {
     try {
-      // Simulate API call to reset password
-      await new Promise(resolve => setTimeout(resolve, 1000));
-      
-      // In a real app, the API would:
-      // 1. Verify the token is valid and not expired
-      // 2. Update the user's password
-      // 3. Invalidate the token
-      console.log(`Password would be reset with token: ${token}`);
+      await authService.resetPassword(token, newPassword);
       
       // Success is handled in the component
     } catch (error) {
@@ -211,6 +225,7 @@ export function AuthProvider({ children }: { children: ReactNode }) {
     user,
     loading,
     login,
+    loginTwoFactor,
     register,
     logout,
     recoverPassword,
@@ -4,6 +4,7 @@ import { useState, useEffect } from 'react';
 import Link from 'next/link';
 import { useRouter, useSearchParams } from 'next/navigation';
 import { useAuth } from '../providers/AuthProvider';
+import { authService } from '@/services/auth.service';
 
 export default function ResetPassword() {
   const [password, setPassword] = useState('');
@@ -28,13 +29,13 @@ export default function ResetPassword() {
       return;
     }
 
-    // Aquí simularemos la validación del token
     const validateToken = async () => {
       try {
-        // Simular llamada a la API para validar el token
-        await new Promise(resolve => setTimeout(resolve, 1000));
+        if (!(await authService.verifyResetToken(tokenFromUrl))) {
+          setError('Token no válido o expirado');
+          return;
+        }
         
-        // Para la demo, asumimos que el token es válido
         setToken(tokenFromUrl);
         setTokenValid(true);
       } catch (err) {
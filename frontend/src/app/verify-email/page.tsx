@@ -0,0 +1,72 @@
+'use client';
+
+import { useState, useEffect } from 'react';
+import Link from 'next/link';
+import { useSearchParams } from 'next/navigation';
+import { authService } from '@/services/auth.service';
+
+export default function VerifyEmail() {
+  const [error, setError] = useState('');
+  const [verifying, setVerifying] = useState(true);
+
+  const searchParams = useSearchParams();
+
+  useEffect(() => {
+    // Obtenemos el token de la URL
+    const token = searchParams.get('token');
+
+    if (!token) {
+      setError('El enlace no es válido o ha expirado');
+      setVerifying(false);
+      return;
+    }
+
+    const verify = async () => {
+      try {
+        await authService.verifyEmail(token);
+      } catch (err) {
+        setError('El enlace no es válido o ha expirado');
+        console.error('Email verification error:', err);
+      } finally {
+        setVerifying(false);
+      }
+    };
+
+    verify();
+  }, [searchParams]);
+
+  if (verifying) {
+    return (
+      <div className="flex min-h-screen flex-col items-center justify-center p-8">
+        <div className="w-full max-w-md p-8 rounded-lg bg-white shadow-md">
+          <p className="text-center">Verificando email...</p>
+        </div>
+      </div>
+    );
+  }
+
+  return (
+    <div className="flex min-h-screen flex-col items-center justify-center p-8">
+      <div className="w-full max-w-md p-8 space-y-6 rounded-lg bg-white shadow-md">
+        {error ? (
+          <div className="rounded-md bg-red-50 p-4">
+            <div className="text-sm text-red-700">
+              {error}. Puedes solicitar un nuevo enlace desde tu perfil.
+            </div>
+          </div>
+        ) : (
+          <div className="rounded-md bg-green-50 p-4">
+            <div className="text-sm text-green-700">
+              Tu email ha sido verificado correctamente.
+            </div>
+          </div>
+        )}
+        <div className="flex justify-center">
+          <Link href="/dashboard" className="font-medium text-blue-600 hover:text-blue-500">
+            Ir al inicio
+          </Link>
+        </div>
+      </div>
+    </div>
+  );
+}
@@ -2,8 +2,15 @@
  * API Service for handling backend requests
  */
 
+import Cookies from 'js-cookie';
+
 const API_URL = 'http://localhost:8080';
 
+export const REFRESH_TOKEN_KEY = 'refreshToken';
+
+// Las llamadas de login no renuevan la sesión: un 401 ahí son credenciales incorrectas
+const NO_REFRESH_ENDPOINTS = ['/api/auth/login', '/api/auth/login/2fa', '/api/auth/register', '/api/auth/refresh'];
+
 interface ApiResponse {
   status: 'success' | 'error';
   token?: string;
@@ -15,6 +22,100 @@ interface ApiResponse {
   error?: string;
 }
 
+/**
+ * Store the tokens of a session: the access token for the API and the refresh token to renew it
+ */
+export const saveSession = (token: string, refreshToken?: string) => {
+  Cookies.set('authToken', token, { expires: 7 });
+  localStorage.setItem('authToken', token);
+  if (refreshToken) {
+    localStorage.setItem(REFRESH_TOKEN_KEY, refreshToken);
+  }
+};
+
+const clearSession = () => {
+  Cookies.remove('authToken');
+  Cookies.remove('userData');
+  localStorage.removeItem('authToken');
+  localStorage.removeItem('userData');
+  localStorage.removeItem(REFRESH_TOKEN_KEY);
+};
+
+// Renovación en curso. El refresh token es de un solo uso: si dos peticiones lo canjearan
+// a la vez, el servidor lo tomaría por un robo y cerraría la sesión
+let refreshing: Promise<boolean> | null = null;
+
+const renewSession = async (): Promise<boolean> => {
+  const refreshToken = localStorage.getItem(REFRESH_TOKEN_KEY);
+  if (!refreshToken) {
+    return false;
+  }
+
+  try {
+    const response = await fetch(`${API_URL}/api/auth/refresh`, {
+      method: 'POST',
+      headers: { 'Content-Type': 'application/json' },
+      body: JSON.stringify({ refresh_token: refreshToken }),
+      credentials: 'include',
+    });
+
+    if (!response.ok) {
+      // La sesión se ha cerrado o ha caducado: hay que volver a iniciar sesión
+      if (response.status === 401) {
+        clearSession();
+      }
+      return false;
+    }
+
+    const result = await response.json();
+    saveSession(result.token, result.refresh_token);
+    return true;
+  } catch (error) {
+    console.error('Session refresh error:', error);
+    return false;
+  }
+};
+
+const refreshSession = (): Promise<boolean> => {
+  if (!refreshing) {
+    refreshing = renewSession().finally(() => {
+      refreshing = null;
+    });
+  }
+  return refreshing;
+};
+
+/**
+ * Send a request with the current access token. When it has expired (401), renew the
+ * session with the refresh token and send the request once more.
+ */
+const authorizedFetch = async (endpoint: string, init: RequestInit): Promise<Response> => {
+  const send = () => {
+    const token = localStorage.getItem('authToken');
+
+    const headers: Record<string, string> = {
+      'Content-Type': 'application/json',
+    };
+
+    // Add Authorization header if token exists
+    if (token) {
+      headers['Authorization'] = `Bearer ${token}`;
+    }
+
+    return fetch(`${API_URL}${endpoint}`, {
+      ...init,
+      headers,
+      credentials: 'include', // Include cookies in requests
+    });
+  };
+
+  const response = await send();
+  if (response.status !== 401 || NO_REFRESH_ENDPOINTS.includes(endpoint) || !(await refreshSession())) {
+    return response;
+  }
+  return send();
+};
+
 /**
  * Base API service for handling HTTP requests
  */
@@ -24,23 +125,9 @@ export const api = {
    */
   post: async <T = any>(endpoint: string, data: any): Promise<T> => {
     try {
-      // Get token from localStorage
-      const token = localStorage.getItem('authToken');
-      
-      const headers: Record<string, string> = {
-        'Content-Type': 'application/json',
-      };
-      
-      // Add Authorization header if token exists
-      if (token) {
-        headers['Authorization'] = `Bearer ${token}`;
-      }
-      
-      const response = await fetch(`${API_URL}${endpoint}`, {
+      const response = await authorizedFetch(endpoint, {
         method: 'POST',
-        headers,
         body: JSON.stringify(data),
-        credentials: 'include', // Include cookies in requests
       });
       
       // If the response is not OK, extract the error message
@@ -71,23 +158,7 @@ export const api = {
    */
   get: async <T = any>(endpoint: string): Promise<T> => {
     try {
-      // Get token from localStorage
-      const token = localStorage.getItem('authToken');
-      
-      const headers: Record<string, string> = {
-        'Content-Type': 'application/json',
-      };
-      
-      // Add Authorization header if token exists
-      if (token) {
-        headers['Authorization'] = `Bearer ${token}`;
-      }
-      
-      const response = await fetch(`${API_URL}${endpoint}`, {
-        method: 'GET',
-        headers,
-        credentials: 'include',
-      });
+      const response = await authorizedFetch(endpoint, { method: 'GET' });
 
       if (!response.ok) {
         const errorText = await response.text();
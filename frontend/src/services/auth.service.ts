@@ -1,4 +1,4 @@
-import { api } from './api.service';
+import { api, saveSession, REFRESH_TOKEN_KEY } from './api.service';
 import Cookies from 'js-cookie';
 
 export interface User {
@@ -19,9 +19,16 @@ interface LoginRequest {
   password: string;
 }
 
-interface AuthResponse {
-  status: 'success' | 'error';
+export interface OidcProvider {
+  name: string;
+  display_name: string;
+}
+
+export interface AuthResponse {
+  status: 'success' | 'error' | 'mfa_required';
   token?: string;
+  refresh_token?: string;
+  mfa_token?: string;
   user?: {
     email: string;
     id: number;
@@ -46,7 +53,7 @@ export const authService = {
     
     // Store token and user data
     if (response.token) {
-      Cookies.set('authToken', response.token, { expires: 7 });
+      saveSession(response.token, response.refresh_token);
       // Store user data in cookie as JSON string
       if (response.user) {
         Cookies.set('userData', JSON.stringify(response.user), { expires: 7 });
@@ -67,11 +74,15 @@ export const authService = {
         throw new Error(response.error);
       }
       
+      // La cuenta tiene verificación en dos pasos: falta enviar el código con loginTwoFactor
+      if (response.status === 'mfa_required') {
+        return response;
+      }
+
       // Store token and user data if provided
       if (response.token) {
         // Store in cookies and localStorage for consistency
-        Cookies.set('authToken', response.token, { expires: 7 });
-        localStorage.setItem('authToken', response.token);
+        saveSession(response.token, response.refresh_token);
         
         // Store user data
         if (response.user) {
@@ -116,7 +127,106 @@ export const authService = {
       // Also clear localStorage
       localStorage.removeItem('authToken');
       localStorage.removeItem('userData');
+      localStorage.removeItem(REFRESH_TOKEN_KEY);
+    }
+  },
+
+  /**
+   * Request a password reset email. The API answers the same whether or not the email exists.
+   */
+  forgotPassword: async (email: string): Promise<void> => {
+    await api.post('/api/auth/forgot-password', { email });
+  },
+
+  /**
+   * Check that a password reset token is still valid
+   */
+  verifyResetToken: async (token: string): Promise<boolean> => {
+    const response = await api.post<{ valid: boolean }>('/api/auth/reset-password/verify', { token });
+    return response.valid;
+  },
+
+  /**
+   * Set a new password using a reset token
+   */
+  resetPassword: async (token: string, newPassword: string): Promise<void> => {
+    await api.post('/api/auth/reset-password', { token, new_password: newPassword });
+  },
+
+  /**
+   * Second login step for accounts with two-factor authentication
+   */
+  loginTwoFactor: async (mfaToken: string, code: string): Promise<AuthResponse> => {
+    const response = await api.post<AuthResponse>('/api/auth/login/2fa', { mfa_token: mfaToken, code });
+
+    if (response.token) {
+      saveSession(response.token, response.refresh_token);
+
+      if (response.user) {
+        Cookies.set('userData', JSON.stringify(response.user), { expires: 7 });
+        localStorage.setItem('userData', JSON.stringify(response.user));
+      }
     }
+
+    return response;
+  },
+
+  /**
+   * Identity providers available for social login
+   */
+  getOidcProviders: async (): Promise<OidcProvider[]> => {
+    const response = await api.get<{ providers: OidcProvider[] }>('/api/auth/oidc/providers');
+    return response.providers;
+  },
+
+  /**
+   * Get the provider URL where the user has to sign in
+   */
+  startOidcLogin: async (provider: string): Promise<string> => {
+    const response = await api.get<{ authorization_url: string }>(`/api/auth/oidc/${provider}/authorize`);
+    return response.authorization_url;
+  },
+
+  /**
+   * Finish a social login with the code returned by the provider
+   */
+  completeOidcLogin: async (provider: string, code: string, state: string): Promise<AuthResponse> => {
+    const response = await api.post<AuthResponse>(`/api/auth/oidc/${provider}/callback`, { code, state });
+
+    if (response.token) {
+      saveSession(response.token, response.refresh_token);
+
+      if (response.user) {
+        Cookies.set('userData', JSON.stringify(response.user), { expires: 7 });
+        localStorage.setItem('userData', JSON.stringify(response.user));
+      }
+    }
+
+    return response;
+  },
+
+  /**
+   * Confirm an email address using the token sent by email
+   */
+  verifyEmail: async (token: string): Promise<void> => {
+    await api.post('/api/auth/verify-email', { token });
+  },
+
+  /**
+   * Send the verification email again
+   */
+  resendVerification: async (): Promise<void> => {
+    await api.post('/api/auth/verify-email/resend', {});
+  },
+
+  /**
+   * Change the password of the authenticated user
+   */
+  changePassword: async (currentPassword: string, newPassword: string): Promise<void> => {
+    await api.post('/api/auth/change-password', {
+      current_password: currentPassword,
+      new_password: newPassword,
+    });
   },
 
   /**
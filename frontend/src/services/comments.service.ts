@@ -16,9 +16,22 @@ interface Comment {
 interface PaginatedComments {
   comments: Comment[];
   total: number;
+  next_cursor: string | null;
+  prev_cursor: string | null;
 }
 
 export const commentsService = {
+  /**
+   * Una página de comentarios; para la siguiente se pasa el `next_cursor` de la anterior
+   */
+  list: async (postId: number, cursor?: string): Promise<PaginatedComments> => {
+    const query = new URLSearchParams({ post_id: String(postId) });
+    if (cursor) {
+      query.set('cursor', cursor);
+    }
+    return api.get<PaginatedComments>(`/api/comments?${query.toString()}`);
+  },
+
   /**
    * Obtener comentarios para un post específico
    */
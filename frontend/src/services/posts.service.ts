@@ -22,8 +22,26 @@ interface Post {
 interface PaginatedResponse<T> {
   posts: T[];
   total: number;
-  page: number;
+  // null al paginar con cursor
+  page: number | null;
   per_page: number;
+  next_cursor: string | null;
+  prev_cursor: string | null;
+}
+
+export interface PostCategory {
+  id: number;
+  name: string;
+  description: string | null;
+  posts_count: number;
+}
+
+interface PostsQuery {
+  page?: number;
+  limit?: number;
+  category?: string;
+  tag?: string;
+  cursor?: string;
 }
 
 export const postsService = {
@@ -32,6 +50,31 @@ export const postsService = {
     return response?.posts || [];
   },
 
+  /**
+   * One page of the feed, optionally filtered by category, tag or text.
+   * Pass the `next_cursor` of the previous page to keep scrolling without gaps
+   */
+  list: async (params: PostsQuery = {}): Promise<PaginatedResponse<Post>> => {
+    const query = new URLSearchParams();
+    Object.entries(params).forEach(([key, value]) => {
+      if (value !== undefined && value !== '') {
+        query.set(key, String(value));
+      }
+    });
+    const qs = query.toString();
+    return api.get<PaginatedResponse<Post>>(`/api/posts${qs ? `?${qs}` : ''}`);
+  },
+
+  getCategories: async (): Promise<PostCategory[]> => {
+    try {
+      const response = await api.get<PostCategory[]>('/api/posts/categories');
+      return response || [];
+    } catch (error) {
+      console.error('Error fetching categories:', error);
+      return [];
+    }
+  },
+
   getById: async (postId: number): Promise<Post | null> => {
     try {
       const response = await api.get<Post>(`/api/posts/${postId}`);
@@ -0,0 +1,45 @@
+import { api } from './api.service';
+
+export type SearchType = 'posts' | 'comments' | 'groups';
+
+export interface SearchResult {
+  type: 'post' | 'comment' | 'group';
+  id: number;
+  // Post al que pertenece un comentario; null en los grupos
+  post_id: number | null;
+  title: string | null;
+  // HTML escapado con las coincidencias entre <mark>
+  snippet: string;
+  rank: number;
+  created_at: string | null;
+}
+
+export interface SearchResponse {
+  results: SearchResult[];
+  counts: Record<SearchType, number>;
+  total: number;
+  page: number;
+  per_page: number;
+}
+
+export const searchService = {
+  /**
+   * Búsqueda de texto completo en posts, comentarios y grupos
+   */
+  search: async (
+    q: string,
+    options: { types?: SearchType[]; page?: number; limit?: number } = {}
+  ): Promise<SearchResponse> => {
+    const query = new URLSearchParams({ q });
+    if (options.types?.length) {
+      query.set('type', options.types.join(','));
+    }
+    if (options.page) {
+      query.set('page', String(options.page));
+    }
+    if (options.limit) {
+      query.set('limit', String(options.limit));
+    }
+    return api.get<SearchResponse>(`/api/search?${query.toString()}`);
+  },
+};